@@ -5,6 +5,7 @@ use xtask::tasks::{
     coverage::coverage,
     database::{db_command, migrate_postgres_db, postgres_db, sqlx_prepare},
     distribute::dist,
+    openapi::openapi,
     test::xtest,
 };
 
@@ -23,6 +24,7 @@ fn try_main() -> Result<(), anyhow::Error> {
         Some("db") => db_command(),
         Some("dist") => dist(),
         Some("migrate") => migrate_postgres_db(),
+        Some("openapi") => openapi(),
         Some("postgres") => postgres_db(),
         Some("redis") => xtask::tasks::database::setup_redis(),
         Some("sqlxprepare") => sqlx_prepare(),
@@ -44,6 +46,7 @@ Tasks:
   sqlxprepare     runs the correct sqlx prepare command
   postgres        starts up a postgres docker container and runs migrations
   migrate         runs postgres database migrations
+  openapi         writes the OpenAPI document of the JSON API to zero2prod/openapi.json
   redis           starts up a redis server
   db              alias for 'postgres' then 'redis'
 "#
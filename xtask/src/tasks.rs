@@ -2,4 +2,5 @@ pub mod ci;
 pub mod coverage;
 pub mod database;
 pub mod distribute;
+pub mod openapi;
 pub mod test;
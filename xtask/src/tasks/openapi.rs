@@ -0,0 +1,24 @@
+use std::{fs, process::Command};
+
+use crate::project_root;
+
+/// Write the OpenAPI document of the JSON API to `zero2prod/openapi.json`,
+/// so changes to the API show up in diffs.
+pub fn openapi() -> Result<(), anyhow::Error> {
+    println!("Generating the OpenAPI document...");
+    let output = Command::new("cargo")
+        .current_dir(project_root())
+        .args(["run", "--quiet", "-p", "zero2prod", "--example", "openapi"])
+        .output()?;
+    if !output.status.success() {
+        anyhow::bail!(
+            "Generating the OpenAPI document failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let destination = project_root().join("zero2prod/openapi.json");
+    fs::write(&destination, output.stdout)?;
+    println!("Wrote {}", destination.display());
+    Ok(())
+}
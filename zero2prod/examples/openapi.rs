@@ -0,0 +1,10 @@
+//! Print the OpenAPI document of the JSON API. Run through `cargo xtask openapi`.
+use utoipa::OpenApi;
+use zero2prod::routes::api::ApiDoc;
+
+fn main() {
+    let document = ApiDoc::openapi()
+        .to_pretty_json()
+        .expect("Failed to serialize the OpenAPI document");
+    println!("{}", document);
+}
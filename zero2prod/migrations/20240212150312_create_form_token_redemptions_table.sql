@@ -0,0 +1,5 @@
+CREATE TABLE form_token_redemptions (
+    nonce TEXT NOT NULL,
+    redeemed_at timestamptz NOT NULL,
+    PRIMARY KEY (nonce)
+);
@@ -0,0 +1,12 @@
+ALTER TABLE users ADD COLUMN disabled BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE user_invitations (
+    invitation_token_hash TEXT NOT NULL,
+    username TEXT NOT NULL,
+    email TEXT NOT NULL,
+    invited_by uuid REFERENCES users(user_id) ON DELETE SET NULL,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    accepted_at timestamptz,
+    PRIMARY KEY (invitation_token_hash)
+);
@@ -0,0 +1,10 @@
+-- Everybody who could log in so far had full access to the admin section.
+ALTER TABLE users
+    ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE users ALTER COLUMN role DROP DEFAULT;
+
+ALTER TABLE user_invitations
+    ADD COLUMN role TEXT NOT NULL DEFAULT 'editor'
+    CHECK (role IN ('owner', 'editor', 'viewer'));
+ALTER TABLE user_invitations ALTER COLUMN role DROP DEFAULT;
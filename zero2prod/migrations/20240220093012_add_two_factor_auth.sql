@@ -0,0 +1,11 @@
+-- Base32 TOTP secret, only set once the user has confirmed enrollment with a valid code.
+ALTER TABLE users ADD COLUMN totp_secret TEXT;
+-- The last 30 second time step a code was accepted for, so a code cannot be replayed.
+ALTER TABLE users ADD COLUMN totp_last_used_step BIGINT;
+
+CREATE TABLE user_recovery_codes (
+    user_id uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    code_hash TEXT NOT NULL,
+    used_at timestamptz,
+    PRIMARY KEY (user_id, code_hash)
+);
@@ -0,0 +1,18 @@
+-- One row per login, so all of a user's sessions can be invalidated at once.
+CREATE TABLE user_sessions (
+    session_id uuid NOT NULL,
+    user_id uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    revoked_at timestamptz,
+    PRIMARY KEY (session_id)
+);
+CREATE INDEX user_sessions_user_id_idx ON user_sessions (user_id);
+
+CREATE TABLE password_reset_tokens (
+    reset_token_hash TEXT NOT NULL,
+    user_id uuid NOT NULL REFERENCES users(user_id) ON DELETE CASCADE,
+    created_at timestamptz NOT NULL,
+    expires_at timestamptz NOT NULL,
+    used_at timestamptz,
+    PRIMARY KEY (reset_token_hash)
+);
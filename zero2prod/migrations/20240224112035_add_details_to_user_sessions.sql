@@ -0,0 +1,5 @@
+ALTER TABLE user_sessions ADD COLUMN ip_address TEXT;
+ALTER TABLE user_sessions ADD COLUMN user_agent TEXT;
+ALTER TABLE user_sessions ADD COLUMN last_seen_at timestamptz;
+UPDATE user_sessions SET last_seen_at = created_at;
+ALTER TABLE user_sessions ALTER COLUMN last_seen_at SET NOT NULL;
@@ -0,0 +1,13 @@
+-- Actors are not foreign keys, so the history of deleted users is kept.
+CREATE TABLE audit_events(
+    audit_event_id uuid PRIMARY KEY,
+    occurred_at timestamptz NOT NULL DEFAULT now(),
+    actor_user_id uuid NULL,
+    actor_username TEXT NULL,
+    action TEXT NOT NULL,
+    target TEXT NULL,
+    ip_address TEXT NULL,
+    request_id TEXT NULL
+);
+CREATE INDEX audit_events_occurred_at_idx ON audit_events (occurred_at DESC);
+CREATE INDEX audit_events_action_idx ON audit_events (action, occurred_at DESC);
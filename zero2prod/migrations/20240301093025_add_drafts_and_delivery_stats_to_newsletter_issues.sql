@@ -0,0 +1,17 @@
+-- Issues can be drafted through the API and published later
+ALTER TABLE newsletter_issues
+    ALTER COLUMN published_at TYPE timestamptz USING published_at::timestamptz,
+    ALTER COLUMN published_at DROP NOT NULL,
+    ADD COLUMN created_at timestamptz NOT NULL DEFAULT now();
+UPDATE newsletter_issues SET created_at = published_at;
+
+-- Delivery outcomes, as tasks are removed from the queue once they are done.
+-- Nobody counted them for the issues published so far, so those are left unknown.
+ALTER TABLE newsletter_issues
+    ADD COLUMN recipient_count INTEGER NULL,
+    ADD COLUMN delivered_count INTEGER NULL,
+    ADD COLUMN failed_count INTEGER NULL;
+ALTER TABLE newsletter_issues
+    ALTER COLUMN recipient_count SET DEFAULT 0,
+    ALTER COLUMN delivered_count SET DEFAULT 0,
+    ALTER COLUMN failed_count SET DEFAULT 0;
@@ -0,0 +1,6 @@
+-- Background workers check in here, so readiness checks can tell whether they are alive.
+CREATE TABLE worker_heartbeats (
+    worker_name TEXT NOT NULL,
+    last_beat_at timestamptz NOT NULL,
+    PRIMARY KEY (worker_name)
+);
@@ -0,0 +1,2 @@
+-- The W3C traceparent of the request that queued the delivery, so sends join its trace.
+ALTER TABLE issue_delivery_queue ADD COLUMN trace_context TEXT;
@@ -0,0 +1,11 @@
+-- Deliveries that failed for good are kept here, so they can be queued again once the
+-- cause has been fixed.
+CREATE TABLE issue_delivery_dead_letters (
+    newsletter_issue_id uuid NOT NULL
+        REFERENCES newsletter_issues (newsletter_issue_id),
+    subscriber_email TEXT NOT NULL,
+    retries INTEGER NOT NULL,
+    trace_context TEXT,
+    failed_at timestamptz NOT NULL,
+    PRIMARY KEY (newsletter_issue_id, subscriber_email)
+);
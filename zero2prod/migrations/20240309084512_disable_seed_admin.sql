@@ -0,0 +1,7 @@
+-- The seed admin's password hash shipped with the source, so anybody could log in as it.
+-- Leave it alone if its password has been changed since.
+UPDATE users
+SET disabled = true
+WHERE
+    user_id = '45f07f34-6967-453a-95da-712461c732ae' AND
+    password_hash = '$argon2id$v=19$m=15000,t=2,p=1$H/unjuDN6hj4FT/XcJHHZg$Y4y9clzdbZtlNp5ueVT0Sq0PDfFbrlyp8gtuxd42bAk';
@@ -0,0 +1,289 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use chrono::{DateTime, Days, NaiveDate, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::{csv::csv_row, request_metadata::RequestMetadata};
+
+/// Something a user did that should be kept on record.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditAction {
+    LoginSucceeded,
+    LoginFailed,
+    LoggedOut,
+    PasswordChanged,
+    PasswordReset,
+    NewsletterPublished,
+    UserInvited,
+    InvitationAccepted,
+    UserRoleChanged,
+    UserDisabled,
+    UserEnabled,
+    UserDeleted,
+    TwoFactorEnabled,
+    TwoFactorDisabled,
+    SessionRevoked,
+    OtherSessionsRevoked,
+    ApiTokenCreated,
+    ApiTokenRevoked,
+    LogFilterChanged,
+    UserCreated,
+}
+
+impl AuditAction {
+    pub const ALL: [AuditAction; 20] = [
+        AuditAction::LoginSucceeded,
+        AuditAction::LoginFailed,
+        AuditAction::LoggedOut,
+        AuditAction::PasswordChanged,
+        AuditAction::PasswordReset,
+        AuditAction::NewsletterPublished,
+        AuditAction::UserInvited,
+        AuditAction::InvitationAccepted,
+        AuditAction::UserRoleChanged,
+        AuditAction::UserDisabled,
+        AuditAction::UserEnabled,
+        AuditAction::UserDeleted,
+        AuditAction::TwoFactorEnabled,
+        AuditAction::TwoFactorDisabled,
+        AuditAction::SessionRevoked,
+        AuditAction::OtherSessionsRevoked,
+        AuditAction::ApiTokenCreated,
+        AuditAction::ApiTokenRevoked,
+        AuditAction::LogFilterChanged,
+        AuditAction::UserCreated,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditAction::LoginSucceeded => "login_succeeded",
+            AuditAction::LoginFailed => "login_failed",
+            AuditAction::LoggedOut => "logged_out",
+            AuditAction::PasswordChanged => "password_changed",
+            AuditAction::PasswordReset => "password_reset",
+            AuditAction::NewsletterPublished => "newsletter_published",
+            AuditAction::UserInvited => "user_invited",
+            AuditAction::InvitationAccepted => "invitation_accepted",
+            AuditAction::UserRoleChanged => "user_role_changed",
+            AuditAction::UserDisabled => "user_disabled",
+            AuditAction::UserEnabled => "user_enabled",
+            AuditAction::UserDeleted => "user_deleted",
+            AuditAction::TwoFactorEnabled => "two_factor_enabled",
+            AuditAction::TwoFactorDisabled => "two_factor_disabled",
+            AuditAction::SessionRevoked => "session_revoked",
+            AuditAction::OtherSessionsRevoked => "other_sessions_revoked",
+            AuditAction::ApiTokenCreated => "api_token_created",
+            AuditAction::ApiTokenRevoked => "api_token_revoked",
+            AuditAction::LogFilterChanged => "log_filter_changed",
+            AuditAction::UserCreated => "user_created",
+        }
+    }
+}
+
+impl std::fmt::Display for AuditAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for AuditAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|action| action.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid audit action", s))
+    }
+}
+
+/// An audit event waiting to be written.
+///
+/// The actor's username is looked up when the event is stored, so the record
+/// still reads sensibly after the user has been renamed or deleted.
+#[derive(Debug)]
+pub struct AuditEvent<'a> {
+    action: AuditAction,
+    actor_user_id: Option<Uuid>,
+    target: Option<String>,
+    request: Option<&'a RequestMetadata>,
+}
+
+impl<'a> AuditEvent<'a> {
+    pub fn new(action: AuditAction, request: &'a RequestMetadata) -> Self {
+        Self {
+            action,
+            actor_user_id: None,
+            target: None,
+            request: Some(request),
+        }
+    }
+
+    /// An event with no request behind it, e.g. from the command line or at startup.
+    pub fn without_request(action: AuditAction) -> Self {
+        Self {
+            action,
+            actor_user_id: None,
+            target: None,
+            request: None,
+        }
+    }
+
+    pub fn actor(mut self, user_id: Uuid) -> Self {
+        self.actor_user_id = Some(user_id);
+        self
+    }
+
+    /// What the action was performed on, e.g. `user:<id>` or `issue:<id>`.
+    pub fn target(mut self, target: impl Into<String>) -> Self {
+        self.target = Some(target.into());
+        self
+    }
+}
+
+/// Store an audit event, e.g. as part of the transaction that performs the action.
+#[tracing::instrument(name = "Insert audit event", skip_all, fields(action = %event.action))]
+pub async fn insert_audit_event(
+    executor: impl PgExecutor<'_>,
+    event: AuditEvent<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO audit_events (
+            audit_event_id,
+            actor_user_id,
+            actor_username,
+            action,
+            target,
+            ip_address,
+            request_id
+        )
+        VALUES ($1, $2, (SELECT username FROM users WHERE user_id = $2), $3, $4, $5, $6)
+        "#,
+        Uuid::new_v4(),
+        event.actor_user_id,
+        event.action.as_str(),
+        event.target,
+        event.request.map(|request| request.ip.to_string()),
+        event
+            .request
+            .and_then(|request| request.request_id.as_deref())
+    )
+    .execute(executor)
+    .await?;
+    Ok(())
+}
+
+/// Store an audit event for an action that has already happened.
+///
+/// Failing to write the record must not turn a completed action into an error
+/// page, so failures are only logged.
+pub async fn record_audit_event(pool: &PgPool, event: AuditEvent<'_>) {
+    if let Err(e) = insert_audit_event(pool, event).await {
+        tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to record an audit event");
+    }
+}
+
+/// Narrows down the audit events that are listed. Empty fields match everything.
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub actor: Option<String>,
+    pub action: Option<AuditAction>,
+    pub from: Option<NaiveDate>,
+    /// Inclusive: events from anywhere on this day match.
+    pub to: Option<NaiveDate>,
+}
+
+#[derive(Debug)]
+pub struct AuditRecord {
+    pub occurred_at: DateTime<Utc>,
+    pub actor_username: Option<String>,
+    pub action: String,
+    pub target: Option<String>,
+    pub ip_address: Option<String>,
+    pub request_id: Option<String>,
+}
+
+/// The matching audit events, most recent first.
+#[tracing::instrument(name = "List audit events", skip(pool))]
+pub async fn list_audit_events(
+    filter: &AuditFilter,
+    limit: Option<i64>,
+    pool: &PgPool,
+) -> Result<Vec<AuditRecord>, anyhow::Error> {
+    let from = filter
+        .from
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    let to = filter
+        .to
+        .and_then(|date| date.checked_add_days(Days::new(1)))
+        .map(|date| date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    let records = sqlx::query_as!(
+        AuditRecord,
+        r#"
+        SELECT occurred_at, actor_username, action, target, ip_address, request_id
+        FROM audit_events
+        WHERE
+            ($1::text IS NULL OR actor_username = $1)
+            AND ($2::text IS NULL OR action = $2)
+            AND ($3::timestamptz IS NULL OR occurred_at >= $3)
+            AND ($4::timestamptz IS NULL OR occurred_at < $4)
+        ORDER BY occurred_at DESC
+        LIMIT $5
+        "#,
+        filter.actor,
+        filter.action.map(|action| action.as_str()),
+        from,
+        to,
+        limit
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to retrieve audit events.")?;
+    Ok(records)
+}
+
+/// Render audit events as CSV, with a header row.
+pub fn audit_events_to_csv(records: &[AuditRecord]) -> String {
+    let mut csv = csv_row(&[
+        "occurred_at",
+        "actor",
+        "action",
+        "target",
+        "ip_address",
+        "request_id",
+    ]);
+    for record in records {
+        let fields = [
+            record.occurred_at.to_rfc3339(),
+            record.actor_username.clone().unwrap_or_default(),
+            record.action.clone(),
+            record.target.clone().unwrap_or_default(),
+            record.ip_address.clone().unwrap_or_default(),
+            record.request_id.clone().unwrap_or_default(),
+        ];
+        csv.push_str(&csv_row(&fields));
+    }
+    csv
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+
+    use super::AuditAction;
+
+    #[test]
+    fn actions_round_trip_through_their_names() {
+        for action in AuditAction::ALL {
+            assert_ok_eq!(action.as_str().parse::<AuditAction>(), action);
+        }
+    }
+
+    #[test]
+    fn unknown_actions_are_rejected() {
+        assert_err!("launched_rockets".parse::<AuditAction>());
+    }
+}
@@ -1,7 +1,51 @@
+mod api_token;
+mod bootstrap;
+mod invitation;
+mod login_guard;
 mod middleware;
 mod password;
+mod password_policy;
+mod password_reset;
+mod role;
+mod session;
+mod token;
+mod two_factor;
 mod user;
 
-pub use middleware::{reject_anonymous_users, UserId};
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
-pub use user::get_username;
+pub use api_token::{
+    authenticate_api_token, create_api_token, list_api_tokens, revoke_api_token, ApiScope,
+    ApiToken, ApiTokenSummary,
+};
+pub use bootstrap::bootstrap_admin;
+pub use invitation::{
+    accept_invitation, create_invitation, get_pending_invitation, InvitationError,
+    PendingInvitation,
+};
+pub use login_guard::{FailureOutcome, LoginGate, LoginGuard};
+pub use middleware::{
+    reject_anonymous_users, reject_invalid_api_tokens, require_role, require_scope, UserId,
+};
+pub use password::{
+    change_password, validate_credentials, AuthError, Credentials, PasswordHashing,
+};
+pub use password_policy::{NewPasswordError, PasswordPolicy};
+pub use password_reset::{
+    create_password_reset_token, get_password_reset_account, redeem_password_reset_token,
+    PasswordResetAccount, PasswordResetRequest,
+};
+pub use role::Role;
+pub use session::{
+    create_user_session, get_active_session_role, list_active_user_sessions,
+    revoke_all_user_sessions, revoke_user_session, SessionIdleTimeout, SessionMaxLifetime,
+    UserSession,
+};
+pub use token::{generate_token, hash_token};
+pub use two_factor::{
+    count_unused_recovery_codes, disable_two_factor, enable_two_factor, generate_totp_secret,
+    get_totp_secret, verify_second_factor, TotpEnrollment,
+};
+pub use user::{
+    create_user, delete_user, enable_user, get_user_email, get_user_id, get_username,
+    is_valid_username, list_users, set_user_disabled, set_user_role, UserManagementError,
+    UserSummary,
+};
@@ -0,0 +1,307 @@
+use std::str::FromStr;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::{
+    token::{generate_token, hash_token},
+    Role,
+};
+
+/// Marks API tokens, so they are easy to spot in logs and secret scanners.
+const API_TOKEN_PREFIX: &str = "z2p_";
+/// How stale `last_used_at` may get before a request refreshes it, to spare a write per request.
+const LAST_USED_RESOLUTION_SECONDS: f64 = 60.0;
+
+/// What an API token may be used for.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize, ToSchema)]
+pub enum ApiScope {
+    /// List newsletter issues and read their delivery stats.
+    #[serde(rename = "newsletters:read")]
+    NewslettersRead,
+    /// Create and publish newsletter issues.
+    #[serde(rename = "newsletters:write")]
+    NewslettersWrite,
+    /// List, search and export subscribers.
+    #[serde(rename = "subscribers:read")]
+    SubscribersRead,
+    /// Scrape the Prometheus metrics from the application port.
+    #[serde(rename = "metrics:read")]
+    MetricsRead,
+}
+
+impl ApiScope {
+    pub const ALL: [ApiScope; 4] = [
+        ApiScope::NewslettersRead,
+        ApiScope::NewslettersWrite,
+        ApiScope::SubscribersRead,
+        ApiScope::MetricsRead,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::NewslettersRead => "newsletters:read",
+            ApiScope::NewslettersWrite => "newsletters:write",
+            ApiScope::SubscribersRead => "subscribers:read",
+            ApiScope::MetricsRead => "metrics:read",
+        }
+    }
+
+    pub fn description(&self) -> &'static str {
+        match self {
+            ApiScope::NewslettersRead => "List newsletter issues and their delivery stats",
+            ApiScope::NewslettersWrite => "Create and publish newsletter issues",
+            ApiScope::SubscribersRead => "List, search and export subscribers",
+            ApiScope::MetricsRead => "Scrape the Prometheus metrics",
+        }
+    }
+
+    /// The role a token's owner needs to use the scope, the same as in the admin section.
+    pub fn required_role(&self) -> Role {
+        match self {
+            ApiScope::NewslettersRead | ApiScope::SubscribersRead => Role::Viewer,
+            ApiScope::NewslettersWrite => Role::Editor,
+            // They reveal how busy every part of the application is
+            ApiScope::MetricsRead => Role::Owner,
+        }
+    }
+}
+
+impl std::fmt::Display for ApiScope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for ApiScope {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|scope| scope.as_str() == s)
+            .ok_or_else(|| format!("{} is not a valid API scope", s))
+    }
+}
+
+/// A valid API token presented with a request, together with its owner's current role.
+#[derive(Clone, Debug)]
+pub struct ApiToken {
+    pub api_token_id: Uuid,
+    pub user_id: Uuid,
+    pub name: String,
+    pub role: Role,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiToken {
+    /// The token carries the scope and its owner still has the role to use it.
+    pub fn allows(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope) && self.role >= scope.required_role()
+    }
+}
+
+/// An API token as listed to its owner. The token itself is only shown once.
+pub struct ApiTokenSummary {
+    pub api_token_id: Uuid,
+    pub name: String,
+    pub scopes: Vec<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+/// Issue a new API token, returning it. Only its hash is stored.
+#[tracing::instrument(name = "Create API token", skip(pool))]
+pub async fn create_api_token(
+    user_id: Uuid,
+    name: &str,
+    scopes: &[ApiScope],
+    expires_at: Option<DateTime<Utc>>,
+    pool: &PgPool,
+) -> Result<(Uuid, String), anyhow::Error> {
+    let api_token_id = Uuid::new_v4();
+    let token = format!("{}{}", API_TOKEN_PREFIX, generate_token());
+    let scopes: Vec<String> = scopes.iter().map(|scope| scope.to_string()).collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO api_tokens (
+            api_token_id,
+            user_id,
+            name,
+            token_hash,
+            scopes,
+            created_at,
+            expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now(), $6)
+        "#,
+        api_token_id,
+        user_id,
+        name,
+        hash_token(&token),
+        &scopes,
+        expires_at
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store a new API token.")?;
+    Ok((api_token_id, token))
+}
+
+/// The user's API tokens that have been neither revoked nor expired, newest first.
+#[tracing::instrument(name = "List API tokens", skip(pool))]
+pub async fn list_api_tokens(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Vec<ApiTokenSummary>, anyhow::Error> {
+    let tokens = sqlx::query_as!(
+        ApiTokenSummary,
+        r#"
+        SELECT api_token_id, name, scopes, created_at, expires_at, last_used_at
+        FROM api_tokens
+        WHERE
+            user_id = $1 AND
+            revoked_at IS NULL AND
+            (expires_at IS NULL OR expires_at > now())
+        ORDER BY created_at DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list API tokens.")?;
+    Ok(tokens)
+}
+
+/// Revoke one of the user's API tokens, returning `false` if there was no such active token.
+#[tracing::instrument(name = "Revoke API token", skip(pool))]
+pub async fn revoke_api_token(
+    user_id: Uuid,
+    api_token_id: Uuid,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let result = sqlx::query!(
+        r#"
+        UPDATE api_tokens
+        SET revoked_at = now()
+        WHERE api_token_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        api_token_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke an API token.")?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Look up the token presented with a request.
+///
+/// Returns `None` if it is unknown, revoked or expired, or its owner has been disabled.
+/// Also keeps the token's last-used time up to date.
+#[tracing::instrument(name = "Authenticate API token", skip_all)]
+pub async fn authenticate_api_token(
+    token: &str,
+    pool: &PgPool,
+) -> Result<Option<ApiToken>, anyhow::Error> {
+    let Some(row) = sqlx::query!(
+        r#"
+        SELECT
+            api_tokens.api_token_id,
+            api_tokens.user_id,
+            api_tokens.name,
+            api_tokens.scopes,
+            users.role,
+            (
+                api_tokens.last_used_at IS NULL OR
+                api_tokens.last_used_at < now() - make_interval(secs => $2)
+            ) AS "stale!"
+        FROM api_tokens
+        JOIN users ON users.user_id = api_tokens.user_id
+        WHERE
+            api_tokens.token_hash = $1 AND
+            api_tokens.revoked_at IS NULL AND
+            (api_tokens.expires_at IS NULL OR api_tokens.expires_at > now()) AND
+            users.disabled = false
+        "#,
+        hash_token(token),
+        LAST_USED_RESOLUTION_SECONDS
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to authenticate an API token.")?
+    else {
+        return Ok(None);
+    };
+
+    if row.stale {
+        sqlx::query!(
+            r#"
+            UPDATE api_tokens
+            SET last_used_at = now()
+            WHERE api_token_id = $1
+            "#,
+            row.api_token_id
+        )
+        .execute(pool)
+        .await
+        .context("Failed to update the API token's last used time.")?;
+    }
+    Ok(Some(ApiToken {
+        api_token_id: row.api_token_id,
+        user_id: row.user_id,
+        name: row.name,
+        role: row.role.parse().map_err(anyhow::Error::msg)?,
+        // Scopes that no longer exist are dropped rather than failing every request.
+        scopes: row
+            .scopes
+            .iter()
+            .filter_map(|scope| scope.parse().ok())
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+    use uuid::Uuid;
+
+    use super::{ApiScope, ApiToken};
+    use crate::authentication::Role;
+
+    fn token(role: Role, scopes: Vec<ApiScope>) -> ApiToken {
+        ApiToken {
+            api_token_id: Uuid::new_v4(),
+            user_id: Uuid::new_v4(),
+            name: "cms".into(),
+            role,
+            scopes,
+        }
+    }
+
+    #[test]
+    fn scopes_round_trip_through_their_names() {
+        for scope in ApiScope::ALL {
+            assert_ok_eq!(scope.as_str().parse::<ApiScope>(), scope);
+        }
+        assert_err!("everything".parse::<ApiScope>());
+    }
+
+    #[test]
+    fn a_token_only_allows_its_own_scopes() {
+        let token = token(Role::Owner, vec![ApiScope::NewslettersRead]);
+        assert!(token.allows(ApiScope::NewslettersRead));
+        assert!(!token.allows(ApiScope::SubscribersRead));
+    }
+
+    #[test]
+    fn a_token_cannot_outgrow_its_owners_role() {
+        let token = token(Role::Viewer, vec![ApiScope::NewslettersWrite]);
+        assert!(!token.allows(ApiScope::NewslettersWrite));
+    }
+}
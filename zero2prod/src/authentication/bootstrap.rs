@@ -0,0 +1,99 @@
+use anyhow::Context;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{insert_audit_event, AuditAction, AuditEvent},
+    configuration::BootstrapAdminSettings,
+    domain::SubscriberEmail,
+};
+
+use super::{
+    user::{create_user, is_valid_username},
+    PasswordHashing, PasswordPolicy, Role,
+};
+
+/// The `admin` seeded, and later disabled, by the migrations.
+const SEED_ADMIN_ID: Uuid = uuid::uuid!("45f07f34-6967-453a-95da-712461c732ae");
+
+/// Create the configured owner if a password is set and there are no users yet, returning
+/// their id.
+///
+/// The seed admin does not count, as it is all there is on a new install. Once anybody else
+/// exists nothing happens, even if they are all disabled, so the password can stay
+/// configured after the first start.
+#[tracing::instrument(name = "Bootstrap admin", skip_all, fields(username = %settings.username))]
+pub async fn bootstrap_admin(
+    settings: &BootstrapAdminSettings,
+    policy: &PasswordPolicy,
+    hashing: &PasswordHashing,
+    pool: &PgPool,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let Some(password) = &settings.password else {
+        return Ok(None);
+    };
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    // Instances starting side by side must not each create an owner
+    sqlx::query!("LOCK TABLE users IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to lock the users table.")?;
+    let r = sqlx::query!(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM users WHERE user_id <> $2) AS "any_users!",
+            EXISTS (SELECT 1 FROM users WHERE username = $1) AS "username_taken!"
+        "#,
+        settings.username,
+        SEED_ADMIN_ID
+    )
+    .fetch_one(&mut *transaction)
+    .await
+    .context("Failed to look for existing users.")?;
+    if r.any_users {
+        return Ok(None);
+    }
+
+    if !is_valid_username(&settings.username) {
+        anyhow::bail!("The username of the bootstrap admin is invalid.");
+    }
+    if r.username_taken {
+        anyhow::bail!(
+            "The username of the bootstrap admin belongs to the seed admin, pick another one."
+        );
+    }
+    let email = SubscriberEmail::parse(settings.email.clone()).map_err(anyhow::Error::msg)?;
+    policy
+        .check(password, password, &[&settings.username, email.as_ref()])
+        .await
+        .context("The password of the bootstrap admin was rejected.")?;
+
+    let user_id = create_user(
+        &mut transaction,
+        &settings.username,
+        email.as_ref(),
+        Role::Owner,
+        password.clone(),
+        hashing,
+    )
+    .await?;
+    insert_audit_event(
+        &mut *transaction,
+        AuditEvent::without_request(AuditAction::UserCreated).target(format!(
+            "user:{} ({})",
+            user_id,
+            Role::Owner
+        )),
+    )
+    .await
+    .context("Failed to record the bootstrap admin in the audit log.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to bootstrap an admin.")?;
+    tracing::info!("Created the first owner.");
+    Ok(Some(user_id))
+}
@@ -0,0 +1,189 @@
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::error_chain_fmt;
+
+use super::{
+    token::{generate_token, hash_token},
+    user::create_user,
+    PasswordHashing, Role,
+};
+
+/// How long an invitation link stays valid.
+const INVITATION_VALIDITY_DAYS: i32 = 3;
+
+pub struct PendingInvitation {
+    pub username: String,
+    pub email: String,
+    pub role: Role,
+}
+
+#[derive(thiserror::Error)]
+pub enum InvitationError {
+    #[error("That username is already taken.")]
+    UsernameTaken,
+    #[error("That email address already belongs to a user.")]
+    EmailTaken,
+    #[error("This invitation is invalid or has expired.")]
+    InvalidInvitation,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for InvitationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Store an invitation for a new user, returning the token to send them.
+///
+/// Usernames and emails of existing users and of pending invitations are both off limits.
+#[tracing::instrument(name = "Create invitation", skip(pool))]
+pub async fn create_invitation(
+    pool: &PgPool,
+    username: &str,
+    email: &str,
+    role: Role,
+    invited_by: Uuid,
+) -> Result<String, InvitationError> {
+    let existing = sqlx::query!(
+        r#"
+        SELECT bool_or(username = $1) AS "username_taken!"
+        FROM (
+            SELECT username, email FROM users
+            UNION ALL
+            SELECT username, email FROM user_invitations
+            WHERE accepted_at IS NULL AND expires_at > now()
+        ) AS taken
+        WHERE username = $1 OR email = $2
+        HAVING count(*) > 0
+        "#,
+        username,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to check for existing users.")?;
+    match existing {
+        Some(r) if r.username_taken => return Err(InvitationError::UsernameTaken),
+        Some(_) => return Err(InvitationError::EmailTaken),
+        None => {}
+    }
+
+    let token = generate_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_invitations (
+            invitation_token_hash,
+            username,
+            email,
+            role,
+            invited_by,
+            created_at,
+            expires_at
+        )
+        VALUES ($1, $2, $3, $4, $5, now(), now() + make_interval(days => $6))
+        "#,
+        hash_token(&token),
+        username,
+        email,
+        role.as_str(),
+        invited_by,
+        INVITATION_VALIDITY_DAYS
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the invitation.")?;
+    Ok(token)
+}
+
+#[tracing::instrument(name = "Get pending invitation", skip_all)]
+pub async fn get_pending_invitation(
+    pool: &PgPool,
+    token: &str,
+) -> Result<Option<PendingInvitation>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT username, email, role
+        FROM user_invitations
+        WHERE
+            invitation_token_hash = $1 AND
+            accepted_at IS NULL AND
+            expires_at > now()
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the invitation.")?;
+    row.map(|r| {
+        Ok(PendingInvitation {
+            username: r.username,
+            email: r.email,
+            role: r.role.parse().map_err(anyhow::Error::msg)?,
+        })
+    })
+    .transpose()
+}
+
+/// Create the invited user with the password they picked, using up the invitation.
+#[tracing::instrument(name = "Accept invitation", skip_all)]
+pub async fn accept_invitation(
+    pool: &PgPool,
+    token: &str,
+    password: Secret<String>,
+    hashing: &PasswordHashing,
+) -> Result<Uuid, InvitationError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let token_hash = hash_token(token);
+    let invitation = sqlx::query!(
+        r#"
+        SELECT username, email, role
+        FROM user_invitations
+        WHERE
+            invitation_token_hash = $1 AND
+            accepted_at IS NULL AND
+            expires_at > now()
+        FOR UPDATE
+        "#,
+        token_hash
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to look up the invitation.")?
+    .ok_or(InvitationError::InvalidInvitation)?;
+    let role = invitation.role.parse().map_err(anyhow::Error::msg)?;
+
+    let user_id = create_user(
+        &mut transaction,
+        &invitation.username,
+        &invitation.email,
+        role,
+        password,
+        hashing,
+    )
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE user_invitations
+        SET accepted_at = now()
+        WHERE invitation_token_hash = $1
+        "#,
+        token_hash
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to mark the invitation as accepted.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to accept an invitation.")?;
+    Ok(user_id)
+}
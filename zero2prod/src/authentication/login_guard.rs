@@ -0,0 +1,251 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use redis_pool::SingleRedisPool;
+
+use crate::{client_ip::ClientIp, configuration::LoginProtectionSettings, rate_limit::hashed};
+
+/// Remembers failed login attempts per username and per client IP in Redis, slowing
+/// down and eventually locking out whoever keeps guessing.
+#[derive(Clone)]
+pub struct LoginGuard {
+    redis_pool: SingleRedisPool,
+    settings: LoginProtectionSettings,
+}
+
+/// Whether a login attempt may go ahead.
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoginGate {
+    /// The attempt may proceed once the given delay has elapsed.
+    Open { delay: Duration },
+    /// The username or the client IP is temporarily locked out.
+    Locked,
+}
+
+/// What recording a failed attempt led to.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FailureOutcome {
+    Recorded,
+    /// This failure locked the account. Reported once per lock.
+    AccountLocked {
+        lock_duration: Duration,
+    },
+}
+
+impl LoginGuard {
+    pub fn new(redis_pool: SingleRedisPool, settings: LoginProtectionSettings) -> Self {
+        Self {
+            redis_pool,
+            settings,
+        }
+    }
+
+    /// Decide whether a login attempt may go ahead and how long it should be held back.
+    #[tracing::instrument(name = "Check login guard", skip(self, username))]
+    pub async fn check(&self, username: &str, ip: ClientIp) -> Result<LoginGate, anyhow::Error> {
+        let mut connection = self.connection().await?;
+        let (user_locked, ip_locked, user_failures, ip_failures): (
+            bool,
+            bool,
+            Option<u64>,
+            Option<u64>,
+        ) = redis::pipe()
+            .exists(self.user_lock_key(username))
+            .exists(self.ip_lock_key(ip))
+            .get(self.user_failures_key(username))
+            .get(self.ip_failures_key(ip))
+            .query_async(&mut *connection)
+            .await
+            .context("Failed to read login attempt counters.")?;
+
+        if user_locked || ip_locked {
+            return Ok(LoginGate::Locked);
+        }
+        let failures = user_failures.unwrap_or(0).max(ip_failures.unwrap_or(0));
+        Ok(LoginGate::Open {
+            delay: self.delay_for(failures),
+        })
+    }
+
+    /// Count a failed attempt against both the username and the client IP,
+    /// locking either out once it crosses its threshold.
+    #[tracing::instrument(name = "Record failed login", skip(self, username))]
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        ip: ClientIp,
+    ) -> Result<FailureOutcome, anyhow::Error> {
+        let window = self.settings.failure_window_seconds;
+        let user_failures_key = self.user_failures_key(username);
+        let ip_failures_key = self.ip_failures_key(ip);
+        let mut connection = self.connection().await?;
+
+        let (user_failures, ip_failures): (u64, u64) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&user_failures_key)
+            .arg(0)
+            .arg("EX")
+            .arg(window)
+            .arg("NX")
+            .ignore()
+            .incr(&user_failures_key, 1)
+            .cmd("SET")
+            .arg(&ip_failures_key)
+            .arg(0)
+            .arg("EX")
+            .arg(window)
+            .arg("NX")
+            .ignore()
+            .incr(&ip_failures_key, 1)
+            .query_async(&mut *connection)
+            .await
+            .context("Failed to record a failed login attempt.")?;
+
+        let lock_seconds = self.settings.lock_seconds;
+        if ip_failures >= self.settings.ip_lock_after_failures {
+            let _: Option<String> = redis::cmd("SET")
+                .arg(self.ip_lock_key(ip))
+                .arg(1)
+                .arg("EX")
+                .arg(lock_seconds)
+                .arg("NX")
+                .query_async(&mut *connection)
+                .await
+                .context("Failed to lock out a client IP.")?;
+        }
+
+        if user_failures >= self.settings.lock_after_failures {
+            let newly_locked: Option<String> = redis::cmd("SET")
+                .arg(self.user_lock_key(username))
+                .arg(1)
+                .arg("EX")
+                .arg(lock_seconds)
+                .arg("NX")
+                .query_async(&mut *connection)
+                .await
+                .context("Failed to lock out a username.")?;
+            if newly_locked.is_some() {
+                // Start from a clean slate once the lock expires.
+                redis::cmd("DEL")
+                    .arg(&user_failures_key)
+                    .query_async::<_, ()>(&mut *connection)
+                    .await
+                    .context("Failed to reset failed login counter.")?;
+                return Ok(FailureOutcome::AccountLocked {
+                    lock_duration: Duration::from_secs(lock_seconds),
+                });
+            }
+        }
+
+        Ok(FailureOutcome::Recorded)
+    }
+
+    /// Forget previous failures for a username after a successful login.
+    #[tracing::instrument(name = "Record successful login", skip(self, username))]
+    pub async fn record_success(&self, username: &str) -> Result<(), anyhow::Error> {
+        let mut connection = self.connection().await?;
+        redis::cmd("DEL")
+            .arg(self.user_failures_key(username))
+            .query_async::<_, ()>(&mut *connection)
+            .await
+            .context("Failed to reset failed login counter.")?;
+        Ok(())
+    }
+
+    fn delay_for(&self, failures: u64) -> Duration {
+        if failures < self.settings.delay_after_failures {
+            return Duration::ZERO;
+        }
+        let doublings = (failures - self.settings.delay_after_failures).min(16) as u32;
+        let delay = self
+            .settings
+            .base_delay_milliseconds
+            .saturating_mul(2u64.pow(doublings));
+        Duration::from_millis(delay.min(self.settings.max_delay_milliseconds))
+    }
+
+    async fn connection(
+        &self,
+    ) -> Result<redis_pool::connection::RedisPoolConnection<redis::aio::Connection>, anyhow::Error>
+    {
+        self.redis_pool
+            .aquire()
+            .await
+            .context("Failed to acquire a Redis connection from the pool.")
+    }
+
+    fn user_failures_key(&self, username: &str) -> String {
+        format!(
+            "{}:failures:user:{}",
+            self.settings.key_prefix,
+            hashed(username)
+        )
+    }
+
+    fn ip_failures_key(&self, ip: ClientIp) -> String {
+        format!("{}:failures:ip:{}", self.settings.key_prefix, ip)
+    }
+
+    fn user_lock_key(&self, username: &str) -> String {
+        format!(
+            "{}:lock:user:{}",
+            self.settings.key_prefix,
+            hashed(username)
+        )
+    }
+
+    fn ip_lock_key(&self, ip: ClientIp) -> String {
+        format!("{}:lock:ip:{}", self.settings.key_prefix, ip)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use redis_pool::RedisPool;
+
+    use super::LoginGuard;
+    use crate::configuration::LoginProtectionSettings;
+
+    fn guard() -> LoginGuard {
+        let client = redis::Client::open("redis://127.0.0.1:6379").unwrap();
+        LoginGuard::new(
+            RedisPool::from(client),
+            LoginProtectionSettings {
+                key_prefix: "login_protection".into(),
+                failure_window_seconds: 900,
+                delay_after_failures: 3,
+                base_delay_milliseconds: 250,
+                max_delay_milliseconds: 2000,
+                lock_after_failures: 5,
+                ip_lock_after_failures: 20,
+                lock_seconds: 900,
+            },
+        )
+    }
+
+    #[test]
+    fn there_is_no_delay_before_the_threshold() {
+        let guard = guard();
+        for failures in 0..3 {
+            assert_eq!(guard.delay_for(failures), Duration::ZERO);
+        }
+    }
+
+    #[test]
+    fn the_delay_doubles_with_every_failure_past_the_threshold() {
+        let guard = guard();
+        assert_eq!(guard.delay_for(3), Duration::from_millis(250));
+        assert_eq!(guard.delay_for(4), Duration::from_millis(500));
+        assert_eq!(guard.delay_for(5), Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        let guard = guard();
+        assert_eq!(guard.delay_for(7), Duration::from_millis(2000));
+        assert_eq!(guard.delay_for(u64::MAX), Duration::from_millis(2000));
+    }
+}
@@ -2,38 +2,144 @@ use std::ops::Deref;
 
 use axum::{
     body::Body,
+    extract::State,
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
+    Extension,
 };
 use axum_session::SessionRedisPool;
-use http::Request;
+use http::{header::AUTHORIZATION, Request, StatusCode};
+use sqlx::PgPool;
 use uuid::Uuid;
 
-use crate::session_state::TypedSession;
+use crate::{
+    e500,
+    error::{ApiError, Problem},
+    session_state::TypedSession,
+};
+
+use super::{authenticate_api_token, get_active_session_role, ApiScope, Role, SessionMaxLifetime};
 
 pub async fn reject_anonymous_users(
+    State(pool): State<PgPool>,
+    State(max_lifetime): State<SessionMaxLifetime>,
     session: TypedSession<SessionRedisPool>,
     mut request: Request<Body>,
     next: Next,
-) -> Result<Response, impl IntoResponse> {
-    match session.get_user_id() {
-        Some(uid) => {
-            request.extensions_mut().insert(UserId(uid));
+) -> Result<Response, Response> {
+    let (Some(uid), Some(session_id)) = (session.get_user_id(), session.get_session_id()) else {
+        tracing::error!("User has not logged in.");
+        return Err(Redirect::to("/login").into_response());
+    };
+
+    // Sessions can be revoked, and accounts disabled or deleted, while their owner is logged in.
+    match get_active_session_role(uid, session_id, max_lifetime, &pool).await {
+        Ok(Some(role)) => {
+            request.extensions_mut().insert(UserId { id: uid, role });
             Ok(next.run(request).await)
         }
-        None => {
-            tracing::error!("User has not logged in.");
-            Err(Redirect::to("/login"))
+        Ok(None) => {
+            tracing::warn!(
+                "Session of user {} was revoked or has expired, or the user is disabled or no longer exists.",
+                uid
+            );
+            session.log_out();
+            Err(Redirect::to("/login").into_response())
         }
+        Err(e) => Err(e500(e).into_response()),
+    }
+}
+
+/// Only let through users whose role is at least the one the middleware was built with.
+///
+/// Must be layered inside [`reject_anonymous_users`], which provides the [`UserId`]:
+/// `middleware::from_fn_with_state(Role::Editor, require_role)`.
+pub async fn require_role(
+    State(required): State<Role>,
+    Extension(user_id): Extension<UserId>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, Response> {
+    if user_id.role() < required {
+        tracing::warn!(
+            "User {} is a {} but {} is required.",
+            user_id,
+            user_id.role(),
+            required
+        );
+        return Err(Problem::new(StatusCode::FORBIDDEN)
+            .detail("You do not have permission to perform this action.")
+            .into_response());
     }
+    Ok(next.run(request).await)
+}
+
+/// Authenticate JSON API requests with an `Authorization: Bearer` API token.
+///
+/// Provides the [`ApiToken`](super::ApiToken) to the handlers and to [`require_scope`].
+pub async fn reject_invalid_api_tokens(
+    State(pool): State<PgPool>,
+    mut request: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .ok_or_else(|| ApiError::Unauthorized("An API token is required.".into()))?;
+
+    match authenticate_api_token(token, &pool).await? {
+        Some(api_token) => {
+            request.extensions_mut().insert(api_token);
+            Ok(next.run(request).await)
+        }
+        None => Err(ApiError::Unauthorized(
+            "The API token is invalid, expired or revoked.".into(),
+        )),
+    }
+}
+
+/// Only let through API tokens that allow the scope the middleware was built with.
+///
+/// Must be layered inside [`reject_invalid_api_tokens`]:
+/// `middleware::from_fn_with_state(ApiScope::NewslettersWrite, require_scope)`.
+pub async fn require_scope(
+    State(required): State<ApiScope>,
+    Extension(api_token): Extension<super::ApiToken>,
+    request: Request<Body>,
+    next: Next,
+) -> Result<Response, ApiError> {
+    if !api_token.allows(required) {
+        tracing::warn!(
+            "API token {} does not allow {}.",
+            api_token.api_token_id,
+            required
+        );
+        return Err(ApiError::Forbidden(format!(
+            "This API token does not allow {}.",
+            required
+        )));
+    }
+    Ok(next.run(request).await)
 }
 
 #[derive(Clone, Copy, Debug)]
-pub struct UserId(Uuid);
+pub struct UserId {
+    id: Uuid,
+    role: Role,
+}
+
+impl UserId {
+    pub fn role(&self) -> Role {
+        self.role
+    }
+}
 
 impl std::fmt::Display for UserId {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.0.fmt(f)
+        self.id.fmt(f)
     }
 }
 
@@ -41,6 +147,6 @@ impl Deref for UserId {
     type Target = Uuid;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.id
     }
 }
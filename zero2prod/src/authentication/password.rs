@@ -4,9 +4,12 @@ use argon2::{
     Version,
 };
 use secrecy::{ExposeSecret, Secret};
-use sqlx::PgPool;
+use sqlx::{PgExecutor, PgPool};
 
-use crate::{error_chain_fmt, telemetry::spawn_blocking_with_tracing};
+use super::token::generate_token;
+use crate::{
+    configuration::PasswordHashingSettings, error_chain_fmt, telemetry::spawn_blocking_with_tracing,
+};
 
 #[derive(thiserror::Error)]
 pub enum AuthError {
@@ -28,20 +31,16 @@ pub struct Credentials {
     pub(crate) password: Secret<String>,
 }
 
-#[tracing::instrument(name = "Validate credentials", skip(credentials, pool))]
+#[tracing::instrument(name = "Validate credentials", skip(credentials, hashing, pool))]
 pub async fn validate_credentials(
     credentials: Credentials,
+    hashing: &PasswordHashing,
     pool: &PgPool,
 ) -> Result<uuid::Uuid, AuthError> {
-    //Create a fallback password hash to enforce doing the same amount
+    //Use a fallback password hash to enforce doing the same amount
     //of work whether we have a user account in the db or not.
     let mut user_id = None;
-    let mut expected_password_hash = Secret::new(
-        "$argon2id$v=19$m=15000,t=2,p=1$\
-        gZiV/M1gPc22ElAH/Jh1Hw$\
-        CWOrkoo7oJBQ/iyh7uJ0LO2aLEfrHwTWllSAxT0zRno"
-            .to_string(),
-    );
+    let mut expected_password_hash = hashing.dummy_hash.clone();
 
     if let Some((stored_user_id, stored_password_hash)) =
         get_stored_credentials(&credentials.username, pool)
@@ -52,24 +51,52 @@ pub async fn validate_credentials(
         expected_password_hash = stored_password_hash
     }
 
-    spawn_blocking_with_tracing(move || {
-        verify_password_hash(expected_password_hash, credentials.password)
+    let previous_password_hash = expected_password_hash.clone();
+    let verifying_hashing = hashing.clone();
+    let upgraded_password_hash = spawn_blocking_with_tracing(move || {
+        verify_password_hash(
+            expected_password_hash,
+            credentials.password,
+            &verifying_hashing,
+        )
     })
     .await
     .context("Failed to spawn blocking task.")
     .map_err(AuthError::UnexpectedError)??;
 
-    user_id.ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))
+    let user_id = user_id
+        .ok_or_else(|| AuthError::InvalidCredentials(anyhow::anyhow!("Unknown username.")))?;
+    if let Some(upgraded_password_hash) = upgraded_password_hash {
+        // The login succeeded either way, so a failed upgrade is retried next time.
+        if let Err(e) = store_upgraded_password_hash(
+            user_id,
+            &previous_password_hash,
+            &upgraded_password_hash,
+            pool,
+        )
+        .await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to store an upgraded password hash."
+            );
+        }
+    }
+    Ok(user_id)
 }
 
+/// Verify a password, returning a fresh hash of it if the expected hash was made
+/// with weaker parameters than the configured ones.
 #[tracing::instrument(
     name = "Verify password hash"
-    skip(expected_password_hash, password_candidate)
+    skip(expected_password_hash, password_candidate, hashing)
 )]
 fn verify_password_hash(
     expected_password_hash: Secret<String>,
     password_candidate: Secret<String>,
-) -> Result<(), AuthError> {
+    hashing: &PasswordHashing,
+) -> Result<Option<Secret<String>>, AuthError> {
     let expected_password_hash = PasswordHash::new(expected_password_hash.expose_secret())
         .context("Failed to parse hash in PHC string format.")
         .map_err(AuthError::UnexpectedError)?;
@@ -80,7 +107,40 @@ fn verify_password_hash(
             &expected_password_hash,
         )
         .context("Invalid password.")
-        .map_err(AuthError::InvalidCredentials)
+        .map_err(AuthError::InvalidCredentials)?;
+
+    if !hashing.is_weaker(&expected_password_hash) {
+        return Ok(None);
+    }
+    tracing::info!("Upgrading a password hash to the current parameters.");
+    hashing
+        .hash(password_candidate)
+        .map(Some)
+        .map_err(AuthError::UnexpectedError)
+}
+
+/// Replace a password hash, unless the password has been changed in the meantime.
+#[tracing::instrument(name = "Store upgraded password hash", skip_all)]
+async fn store_upgraded_password_hash(
+    user_id: uuid::Uuid,
+    previous_password_hash: &Secret<String>,
+    upgraded_password_hash: &Secret<String>,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET password_hash = $3
+        WHERE user_id = $1 AND password_hash = $2
+        "#,
+        user_id,
+        previous_password_hash.expose_secret(),
+        upgraded_password_hash.expose_secret()
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the upgraded password hash.")?;
+    Ok(())
 }
 
 #[tracing::instrument(name = "Get stored credentials", skip(username, pool))]
@@ -92,7 +152,7 @@ async fn get_stored_credentials(
         r#"
         SELECT user_id, password_hash
         FROM users
-        WHERE username = $1
+        WHERE username = $1 AND disabled = false
         "#,
         username,
     )
@@ -104,13 +164,15 @@ async fn get_stored_credentials(
     Ok(row)
 }
 
-#[tracing::instrument(name = "Change password", skip(password, pool))]
+#[tracing::instrument(name = "Change password", skip(password, hashing, executor))]
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
-    pool: &PgPool,
+    hashing: &PasswordHashing,
+    executor: impl PgExecutor<'_>,
 ) -> Result<(), anyhow::Error> {
-    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
+    let hashing = hashing.clone();
+    let password_hash = spawn_blocking_with_tracing(move || hashing.hash(password))
         .await?
         .context("Failed to hash password")?;
 
@@ -123,21 +185,126 @@ pub async fn change_password(
         password_hash.expose_secret(),
         user_id
     )
-    .execute(pool)
+    .execute(executor)
     .await
     .context("Failed to change user's password in the database.")?;
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
-    let salt = SaltString::generate(&mut rand::thread_rng());
-    let password_hash = Argon2::new(
-        argon2::Algorithm::Argon2id,
-        Version::V0x13,
-        Params::new(15000, 2, 1, None).unwrap(),
-    )
-    .hash_password(password.expose_secret().as_bytes(), &salt)?
-    .to_string();
+/// Hashes passwords with Argon2id, using the cost parameters from the configuration.
+///
+/// The parameters can be raised over time: hashes made with weaker ones are
+/// upgraded the next time their owner logs in.
+#[derive(Clone)]
+pub struct PasswordHashing {
+    params: Params,
+    /// Verified against when the username is unknown, so that takes as long as a real check.
+    dummy_hash: Secret<String>,
+}
 
-    Ok(Secret::new(password_hash))
+impl PasswordHashing {
+    pub fn new(settings: &PasswordHashingSettings) -> Result<Self, anyhow::Error> {
+        let params = Params::new(
+            settings.memory_kib,
+            settings.iterations,
+            settings.parallelism,
+            None,
+        )
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+        let mut hashing = Self {
+            params,
+            dummy_hash: Secret::new(String::new()),
+        };
+        hashing.dummy_hash = hashing.hash(Secret::new(generate_token()))?;
+        Ok(hashing)
+    }
+
+    pub fn hash(&self, password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+        let salt = SaltString::generate(&mut rand::thread_rng());
+        let password_hash = Argon2::new(
+            argon2::Algorithm::Argon2id,
+            Version::V0x13,
+            self.params.clone(),
+        )
+        .hash_password(password.expose_secret().as_bytes(), &salt)?
+        .to_string();
+
+        Ok(Secret::new(password_hash))
+    }
+
+    /// Whether `hash` was made with a weaker algorithm or lower costs than the current ones.
+    ///
+    /// Hashes made with higher costs are left alone, so lowering the costs again
+    /// does not downgrade them.
+    fn is_weaker(&self, hash: &PasswordHash) -> bool {
+        if hash.algorithm != argon2::Algorithm::Argon2id.ident()
+            || hash.version != Some(Version::V0x13.into())
+        {
+            return true;
+        }
+        match Params::try_from(hash) {
+            Ok(params) => {
+                params.m_cost() < self.params.m_cost()
+                    || params.t_cost() < self.params.t_cost()
+                    || params.p_cost() < self.params.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use argon2::PasswordHash;
+    use secrecy::{ExposeSecret, Secret};
+
+    use super::PasswordHashing;
+    use crate::configuration::PasswordHashingSettings;
+
+    fn hashing(memory_kib: u32, iterations: u32) -> PasswordHashing {
+        PasswordHashing::new(&PasswordHashingSettings {
+            memory_kib,
+            iterations,
+            parallelism: 1,
+        })
+        .unwrap()
+    }
+
+    fn hash_with(hashing: &PasswordHashing) -> String {
+        hashing
+            .hash(Secret::new("correct horse battery staple".to_string()))
+            .unwrap()
+            .expose_secret()
+            .clone()
+    }
+
+    #[test]
+    fn hashes_with_the_current_parameters_are_kept() {
+        let current = hashing(8192, 2);
+        let hash = hash_with(&current);
+        assert!(!current.is_weaker(&PasswordHash::new(&hash).unwrap()));
+    }
+
+    #[test]
+    fn hashes_with_lower_costs_are_upgraded() {
+        let current = hashing(8192, 2);
+        let less_memory = hash_with(&hashing(4096, 2));
+        let fewer_iterations = hash_with(&hashing(8192, 1));
+        assert!(current.is_weaker(&PasswordHash::new(&less_memory).unwrap()));
+        assert!(current.is_weaker(&PasswordHash::new(&fewer_iterations).unwrap()));
+    }
+
+    #[test]
+    fn hashes_with_higher_costs_are_not_downgraded() {
+        let current = hashing(4096, 1);
+        let hash = hash_with(&hashing(8192, 2));
+        assert!(!current.is_weaker(&PasswordHash::new(&hash).unwrap()));
+    }
+
+    #[test]
+    fn argon2i_hashes_are_upgraded() {
+        let hash = "$argon2i$v=19$m=8192,t=2,p=1$c29tZXNhbHQ$\
+            iWh06vD8Fy27wf9npn6FXWiCX4K6pW6Ue1Bnzz07Z8A";
+        assert!(hashing(8192, 2).is_weaker(&PasswordHash::new(hash).unwrap()));
+    }
 }
@@ -0,0 +1,319 @@
+use std::path::PathBuf;
+
+use anyhow::Context;
+use secrecy::{ExposeSecret, Secret};
+use sha1::{Digest, Sha1};
+
+use crate::{configuration::PasswordPolicySettings, error_chain_fmt};
+
+/// Frequently used passwords and the words they are built from, one per line.
+const COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+/// Shorter dictionary words are too likely to show up by chance inside random passwords.
+const MIN_WORD_LENGTH: usize = 4;
+
+#[derive(thiserror::Error)]
+pub enum NewPasswordError {
+    #[error("The new password should be between 12 and 128 characters long.")]
+    InvalidLength,
+    #[error("You entered two different new passwords - the field values must match.")]
+    Mismatch,
+    #[error("The new password must not contain your username or email address.")]
+    ContainsPersonalInfo,
+    #[error(
+        "The new password is too easy to guess. Avoid common words, repeated characters \
+        and sequences like 1234, or use a longer passphrase."
+    )]
+    TooWeak,
+    #[error(
+        "The new password has appeared in a data breach, so attackers will try it. \
+        Please choose a different one."
+    )]
+    Breached,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for NewPasswordError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// The rules every newly chosen password must follow.
+#[derive(Clone, Debug)]
+pub struct PasswordPolicy {
+    min_strength_bits: f64,
+    /// A directory of Have I Been Pwned range files, see [`is_breached`](Self::is_breached).
+    breached_passwords_directory: Option<PathBuf>,
+}
+
+impl PasswordPolicy {
+    pub fn new(settings: PasswordPolicySettings) -> Self {
+        Self {
+            min_strength_bits: settings.min_strength_bits,
+            breached_passwords_directory: settings.breached_passwords_directory.map(PathBuf::from),
+        }
+    }
+
+    /// Check a newly chosen password and its confirmation.
+    ///
+    /// `personal_info` holds what the password must not be built from, such as the
+    /// username and email address of the account.
+    #[tracing::instrument(name = "Check new password", skip_all)]
+    pub async fn check(
+        &self,
+        password: &Secret<String>,
+        password_check: &Secret<String>,
+        personal_info: &[&str],
+    ) -> Result<(), NewPasswordError> {
+        let password = password.expose_secret();
+        if !(12..=128).contains(&password.len()) {
+            return Err(NewPasswordError::InvalidLength);
+        }
+        if password != password_check.expose_secret() {
+            return Err(NewPasswordError::Mismatch);
+        }
+        let personal_words = personal_words(personal_info);
+        let lowercase = password.to_lowercase();
+        if personal_words.iter().any(|word| lowercase.contains(word)) {
+            return Err(NewPasswordError::ContainsPersonalInfo);
+        }
+        if estimate_strength_bits(password, &personal_words) < self.min_strength_bits {
+            return Err(NewPasswordError::TooWeak);
+        }
+        if self.is_breached(password).await? {
+            return Err(NewPasswordError::Breached);
+        }
+        Ok(())
+    }
+
+    /// Look the password up in an offline copy of the Have I Been Pwned dataset.
+    ///
+    /// The directory holds one file per 5 character SHA-1 prefix, e.g. `5BAA6.txt`, with
+    /// `SUFFIX:COUNT` lines - the same format as the range API and its official downloader.
+    /// A missing file means no breached password starts with that prefix.
+    async fn is_breached(&self, password: &str) -> Result<bool, anyhow::Error> {
+        let Some(directory) = &self.breached_passwords_directory else {
+            return Ok(false);
+        };
+        let hash = format!("{:X}", Sha1::digest(password.as_bytes()));
+        let (prefix, suffix) = hash.split_at(5);
+        let range = match tokio::fs::read_to_string(directory.join(format!("{}.txt", prefix))).await
+        {
+            Ok(range) => range,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e).context("Failed to read the breached passwords file."),
+        };
+        Ok(range_contains(&range, suffix))
+    }
+}
+
+/// Whether a range file lists `suffix` with a non-zero count.
+fn range_contains(range: &str, suffix: &str) -> bool {
+    range.lines().any(|line| {
+        let (line_suffix, count) = line.trim().split_once(':').unwrap_or((line.trim(), "1"));
+        // Padded ranges list made up suffixes with a count of 0.
+        line_suffix.eq_ignore_ascii_case(suffix) && count.trim() != "0"
+    })
+}
+
+/// The lowercase words from the account details that are long enough to matter.
+fn personal_words(personal_info: &[&str]) -> Vec<String> {
+    personal_info
+        .iter()
+        .flat_map(|info| {
+            let info = info.to_lowercase();
+            // Both the whole email address and the part before the @
+            let local_part = info.split('@').next().map(str::to_owned);
+            std::iter::once(info).chain(local_part)
+        })
+        .filter(|word| word.chars().count() >= 3)
+        .collect()
+}
+
+/// A rough estimate of how many guesses, in bits, it would take to find the password.
+///
+/// Every character is worth the bits of the character classes in use, except that
+/// characters continuing a repetition or a sequence like `abc` or `321` are worth one
+/// bit, and a dictionary word, even with letters swapped for look-alike digits, is
+/// worth no more than picking it from the list.
+fn estimate_strength_bits(password: &str, personal_words: &[String]) -> f64 {
+    let characters: Vec<char> = password.chars().collect();
+    let lowercase: Vec<char> = characters.iter().map(|c| c.to_ascii_lowercase()).collect();
+    let normalized: Vec<char> = characters.iter().map(|c| unleet(*c)).collect();
+    let dictionary: Vec<Vec<char>> = COMMON_PASSWORDS
+        .lines()
+        .map(str::to_owned)
+        .chain(personal_words.iter().cloned())
+        .filter(|word| word.chars().count() >= MIN_WORD_LENGTH)
+        .map(|word| word.chars().collect())
+        .collect();
+    let word_bits = (dictionary.len() as f64).log2() + 1.0;
+    let character_bits = (character_pool_size(password) as f64).log2();
+
+    let mut bits = 0.0;
+    let mut i = 0;
+    while i < normalized.len() {
+        let longest_word = dictionary
+            .iter()
+            .filter(|word| lowercase[i..].starts_with(word) || normalized[i..].starts_with(word))
+            .map(Vec::len)
+            .max();
+        if let Some(length) = longest_word {
+            bits += word_bits;
+            i += length;
+            continue;
+        }
+        bits += if i > 0 && continues_pattern(characters[i - 1], characters[i]) {
+            1.0
+        } else {
+            character_bits
+        };
+        i += 1;
+    }
+    bits
+}
+
+fn character_pool_size(password: &str) -> u32 {
+    let mut size = 0;
+    if password.chars().any(|c| c.is_ascii_lowercase()) {
+        size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_uppercase()) {
+        size += 26;
+    }
+    if password.chars().any(|c| c.is_ascii_digit()) {
+        size += 10;
+    }
+    if password
+        .chars()
+        .any(|c| c.is_ascii() && !c.is_ascii_alphanumeric())
+    {
+        size += 33;
+    }
+    if !password.is_ascii() {
+        size += 100;
+    }
+    size
+}
+
+/// Whether `current` repeats `previous` or is the next or previous character after it.
+fn continues_pattern(previous: char, current: char) -> bool {
+    let (previous, current) = (
+        previous.to_ascii_lowercase() as i64,
+        current.to_ascii_lowercase() as i64,
+    );
+    (current - previous).abs() <= 1
+}
+
+/// Undo the usual letter to look-alike substitutions, and lowercase.
+fn unleet(c: char) -> char {
+    match c.to_ascii_lowercase() {
+        '0' => 'o',
+        '1' | '!' => 'i',
+        '3' => 'e',
+        '4' | '@' => 'a',
+        '5' | '$' => 's',
+        '7' => 't',
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+
+    use super::{estimate_strength_bits, range_contains, NewPasswordError, PasswordPolicy};
+    use crate::configuration::PasswordPolicySettings;
+
+    fn policy() -> PasswordPolicy {
+        PasswordPolicy::new(PasswordPolicySettings {
+            min_strength_bits: 50.0,
+            breached_passwords_directory: None,
+        })
+    }
+
+    async fn check(password: &str, personal_info: &[&str]) -> Result<(), NewPasswordError> {
+        let password = Secret::new(password.to_string());
+        policy()
+            .check(&password, &password.clone(), personal_info)
+            .await
+    }
+
+    #[tokio::test]
+    async fn random_passwords_are_accepted() {
+        for password in [
+            "f3k9-x2mq-77ab-pqzr",
+            "correct horse battery staple",
+            "Tr0ub4dor&3xylophonics",
+        ] {
+            assert!(check(password, &[]).await.is_ok(), "{}", password);
+        }
+    }
+
+    #[tokio::test]
+    async fn common_words_with_decorations_are_too_weak() {
+        for password in [
+            "Password123!",
+            "P@ssw0rd2024!",
+            "qwertyuiop12",
+            "letmein!letmein!",
+        ] {
+            assert!(
+                matches!(check(password, &[]).await, Err(NewPasswordError::TooWeak)),
+                "{}",
+                password
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn repetitions_and_sequences_are_too_weak() {
+        for password in ["aaaaaaaaaaaaaaaa", "abcdefghijklmnop", "1234567890987654"] {
+            assert!(
+                matches!(check(password, &[]).await, Err(NewPasswordError::TooWeak)),
+                "{}",
+                password
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn passwords_built_from_the_account_details_are_rejected() {
+        let outcome = check("ursula-rocks-7781x", &["Ursula", "ulla@example.com"]).await;
+        assert!(matches!(
+            outcome,
+            Err(NewPasswordError::ContainsPersonalInfo)
+        ));
+        let outcome = check("x9ULLA77-keep-it-up", &["Ursula", "ulla@example.com"]).await;
+        assert!(matches!(
+            outcome,
+            Err(NewPasswordError::ContainsPersonalInfo)
+        ));
+    }
+
+    #[test]
+    fn dictionary_words_count_as_one_pick_from_the_list() {
+        let word = estimate_strength_bits("password", &[]);
+        let random = estimate_strength_bits("xkqvmwzj", &[]);
+        assert!(word < 10.0);
+        assert!(random > 35.0);
+    }
+
+    #[test]
+    fn range_files_are_matched_by_suffix_and_count() {
+        let range = "0018A45C4D1DEF81644B54AB7F969B88D65:1\r\n\
+            1E4C9B93F3F0682250B6CF8331B7EE68FD8:9545824\r\n\
+            011053FD0102E94D6AE2F8B83D76FAF94F6:0\r\n";
+        assert!(range_contains(range, "1E4C9B93F3F0682250B6CF8331B7EE68FD8"));
+        assert!(range_contains(range, "1e4c9b93f3f0682250b6cf8331b7ee68fd8"));
+        assert!(!range_contains(
+            range,
+            "011053FD0102E94D6AE2F8B83D76FAF94F6"
+        ));
+        assert!(!range_contains(
+            range,
+            "FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+        ));
+    }
+}
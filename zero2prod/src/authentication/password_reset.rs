@@ -0,0 +1,118 @@
+use anyhow::Context;
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use super::token::{generate_token, hash_token};
+
+/// How long a password reset link stays valid.
+const PASSWORD_RESET_VALIDITY_MINUTES: i32 = 60;
+
+/// An account that asked for a password reset.
+pub struct PasswordResetRequest {
+    pub username: String,
+    pub token: String,
+}
+
+/// Issue a reset token for the active user with this email address, if there is one.
+#[tracing::instrument(name = "Create password reset token", skip(pool))]
+pub async fn create_password_reset_token(
+    email: &str,
+    pool: &PgPool,
+) -> Result<Option<PasswordResetRequest>, anyhow::Error> {
+    let Some(user) = sqlx::query!(
+        r#"
+        SELECT user_id, username
+        FROM users
+        WHERE lower(email) = lower($1) AND disabled = false
+        "#,
+        email
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the user by email.")?
+    else {
+        return Ok(None);
+    };
+
+    let token = generate_token();
+    sqlx::query!(
+        r#"
+        INSERT INTO password_reset_tokens (reset_token_hash, user_id, created_at, expires_at)
+        VALUES ($1, $2, now(), now() + make_interval(mins => $3))
+        "#,
+        hash_token(&token),
+        user.user_id,
+        PASSWORD_RESET_VALIDITY_MINUTES
+    )
+    .execute(pool)
+    .await
+    .context("Failed to store the password reset token.")?;
+    Ok(Some(PasswordResetRequest {
+        username: user.username,
+        token,
+    }))
+}
+
+/// The account a still valid reset token was issued for.
+pub struct PasswordResetAccount {
+    pub username: String,
+    pub email: Option<String>,
+}
+
+#[tracing::instrument(name = "Check password reset token", skip_all)]
+pub async fn get_password_reset_account(
+    token: &str,
+    pool: &PgPool,
+) -> Result<Option<PasswordResetAccount>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT users.username, users.email
+        FROM password_reset_tokens
+        JOIN users ON users.user_id = password_reset_tokens.user_id
+        WHERE
+            reset_token_hash = $1 AND
+            used_at IS NULL AND
+            expires_at > now()
+        "#,
+        hash_token(token)
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to look up the password reset token.")?;
+    Ok(row.map(|r| PasswordResetAccount {
+        username: r.username,
+        email: r.email,
+    }))
+}
+
+/// Use up the token, returning the user it was issued for if it was still valid.
+///
+/// Every other outstanding token for that user is used up as well.
+#[tracing::instrument(name = "Redeem password reset token", skip_all)]
+pub async fn redeem_password_reset_token(
+    token: &str,
+    executor: impl PgExecutor<'_>,
+) -> Result<Option<Uuid>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        WITH redeemed AS (
+            SELECT user_id
+            FROM password_reset_tokens
+            WHERE
+                reset_token_hash = $1 AND
+                used_at IS NULL AND
+                expires_at > now()
+            FOR UPDATE
+        )
+        UPDATE password_reset_tokens
+        SET used_at = now()
+        WHERE user_id IN (SELECT user_id FROM redeemed) AND used_at IS NULL
+        RETURNING user_id
+        "#,
+        hash_token(token)
+    )
+    .fetch_all(executor)
+    .await
+    .context("Failed to redeem the password reset token.")?;
+    Ok(row.first().map(|r| r.user_id))
+}
@@ -0,0 +1,73 @@
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+/// What a user is allowed to do in the admin section.
+///
+/// Roles are ordered: every role can do everything the roles below it can.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can look at the dashboards.
+    Viewer,
+    /// Can also write and publish newsletter issues.
+    Editor,
+    /// Can also manage other users.
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Viewer];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl FromStr for Role {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "viewer" => Ok(Role::Viewer),
+            "editor" => Ok(Role::Editor),
+            "owner" => Ok(Role::Owner),
+            other => Err(format!("{} is not a valid role", other)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok_eq};
+
+    use super::Role;
+
+    #[test]
+    fn roles_round_trip_through_their_names() {
+        for role in Role::ALL {
+            assert_ok_eq!(role.as_str().parse::<Role>(), role);
+        }
+    }
+
+    #[test]
+    fn unknown_roles_are_rejected() {
+        assert_err!("admin".parse::<Role>());
+    }
+
+    #[test]
+    fn higher_roles_include_lower_ones() {
+        assert!(Role::Owner > Role::Editor);
+        assert!(Role::Editor > Role::Viewer);
+    }
+}
@@ -0,0 +1,190 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgExecutor, PgPool};
+use uuid::Uuid;
+
+use crate::request_metadata::RequestMetadata;
+
+use super::Role;
+
+/// How stale `last_seen_at` may get before a request refreshes it, to spare a write per request.
+const LAST_SEEN_RESOLUTION_SECONDS: f64 = 60.0;
+
+/// How long after the login a session stops being accepted, however active it is.
+#[derive(Clone, Copy, Debug)]
+pub struct SessionMaxLifetime(pub std::time::Duration);
+
+/// How long a session may go without a request before it expires.
+#[derive(Clone, Copy, Debug)]
+pub struct SessionIdleTimeout(pub std::time::Duration);
+
+pub struct UserSession {
+    pub session_id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub last_seen_at: DateTime<Utc>,
+}
+
+/// Record a new login for `user_id`, returning the id to keep in their session.
+#[tracing::instrument(name = "Create user session", skip(origin, pool))]
+pub async fn create_user_session(
+    user_id: Uuid,
+    origin: &RequestMetadata,
+    pool: &PgPool,
+) -> Result<Uuid, anyhow::Error> {
+    let session_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_sessions (
+            session_id,
+            user_id,
+            ip_address,
+            user_agent,
+            created_at,
+            last_seen_at
+        )
+        VALUES ($1, $2, $3, $4, now(), now())
+        "#,
+        session_id,
+        user_id,
+        origin.ip.to_string(),
+        origin.user_agent
+    )
+    .execute(pool)
+    .await
+    .context("Failed to record a new user session.")?;
+    Ok(session_id)
+}
+
+/// The user's role, or `None` if the session was revoked or the user disabled or deleted.
+///
+/// Also keeps the session's last-seen time up to date.
+#[tracing::instrument(name = "Get active session role", skip(pool))]
+pub async fn get_active_session_role(
+    user_id: Uuid,
+    session_id: Uuid,
+    max_lifetime: SessionMaxLifetime,
+    pool: &PgPool,
+) -> Result<Option<Role>, anyhow::Error> {
+    let Some(row) = sqlx::query!(
+        r#"
+        SELECT
+            users.role,
+            user_sessions.last_seen_at < now() - make_interval(secs => $3) AS "stale!"
+        FROM user_sessions
+        JOIN users ON users.user_id = user_sessions.user_id
+        WHERE
+            user_sessions.session_id = $1 AND
+            user_sessions.user_id = $2 AND
+            user_sessions.revoked_at IS NULL AND
+            user_sessions.created_at > now() - make_interval(secs => $4) AND
+            users.disabled = false
+        "#,
+        session_id,
+        user_id,
+        LAST_SEEN_RESOLUTION_SECONDS,
+        max_lifetime.0.as_secs_f64()
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to check whether a session is active.")?
+    else {
+        return Ok(None);
+    };
+
+    if row.stale {
+        sqlx::query!(
+            r#"
+            UPDATE user_sessions
+            SET last_seen_at = now()
+            WHERE session_id = $1
+            "#,
+            session_id
+        )
+        .execute(pool)
+        .await
+        .context("Failed to update the session's last seen time.")?;
+    }
+    let role = row.role.parse().map_err(anyhow::Error::msg)?;
+    Ok(Some(role))
+}
+
+/// The user's sessions that have neither been revoked nor expired, most recently used first.
+#[tracing::instrument(name = "List user sessions", skip(pool))]
+pub async fn list_active_user_sessions(
+    user_id: Uuid,
+    max_lifetime: SessionMaxLifetime,
+    idle_timeout: SessionIdleTimeout,
+    pool: &PgPool,
+) -> Result<Vec<UserSession>, anyhow::Error> {
+    // `last_seen_at` lags behind by up to the resolution, so allow for that when idle
+    let sessions = sqlx::query_as!(
+        UserSession,
+        r#"
+        SELECT session_id, ip_address, user_agent, created_at, last_seen_at
+        FROM user_sessions
+        WHERE
+            user_id = $1 AND
+            revoked_at IS NULL AND
+            created_at > now() - make_interval(secs => $2) AND
+            last_seen_at > now() - make_interval(secs => $3)
+        ORDER BY last_seen_at DESC
+        "#,
+        user_id,
+        max_lifetime.0.as_secs_f64(),
+        idle_timeout.0.as_secs_f64() + LAST_SEEN_RESOLUTION_SECONDS
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list the user's sessions.")?;
+    Ok(sessions)
+}
+
+/// Revoke one of the user's sessions, returning `false` if there was no such active session.
+#[tracing::instrument(name = "Revoke user session", skip(pool))]
+pub async fn revoke_user_session(
+    user_id: Uuid,
+    session_id: Uuid,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE user_sessions
+        SET revoked_at = now()
+        WHERE session_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        session_id,
+        user_id
+    )
+    .execute(pool)
+    .await
+    .context("Failed to revoke a user session.")?
+    .rows_affected();
+    Ok(revoked > 0)
+}
+
+/// Log the user out everywhere, except for the session in `keep`.
+#[tracing::instrument(name = "Revoke all user sessions", skip(executor))]
+pub async fn revoke_all_user_sessions(
+    user_id: Uuid,
+    keep: Option<Uuid>,
+    executor: impl PgExecutor<'_>,
+) -> Result<(), anyhow::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE user_sessions
+        SET revoked_at = now()
+        WHERE
+            user_id = $1 AND
+            revoked_at IS NULL AND
+            session_id IS DISTINCT FROM $2
+        "#,
+        user_id,
+        keep
+    )
+    .execute(executor)
+    .await
+    .context("Failed to revoke the user's sessions.")?;
+    Ok(())
+}
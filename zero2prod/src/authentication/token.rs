@@ -0,0 +1,18 @@
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use sha2::{Digest, Sha256};
+
+/// Generate a random token to be handed out in a link, e.g. an invitation.
+pub fn generate_token() -> String {
+    let mut rng = thread_rng();
+    std::iter::repeat_with(|| rng.sample(Alphanumeric))
+        .map(char::from)
+        .take(32)
+        .collect()
+}
+
+/// Hash a token before it is stored, so a database leak does not leak usable tokens.
+///
+/// Tokens are long and random, so a fast hash is enough - unlike passwords.
+pub fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
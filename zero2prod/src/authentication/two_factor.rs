@@ -0,0 +1,374 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::Context;
+use qrcode::{render::svg, QrCode};
+use rand::{thread_rng, Rng, RngCore};
+use sqlx::PgPool;
+use totp_rs::{Algorithm, Secret, TOTP};
+use uuid::Uuid;
+
+use super::token::hash_token;
+
+/// Shown next to the account name in authenticator apps.
+const TOTP_ISSUER: &str = "zero2prod";
+/// Length of a time step in seconds, the default every authenticator app expects.
+const TOTP_STEP_SECONDS: u64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+/// Ambiguous characters (0/o, 1/l/i) are left out so the codes can be copied by hand.
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Generate a new base32 encoded TOTP secret of 160 bits, as recommended by RFC 4226.
+pub fn generate_totp_secret() -> String {
+    let mut secret = [0u8; 20];
+    thread_rng().fill_bytes(&mut secret);
+    match Secret::Raw(secret.to_vec()).to_encoded() {
+        Secret::Encoded(encoded) => encoded,
+        Secret::Raw(_) => unreachable!("to_encoded always returns an encoded secret"),
+    }
+}
+
+/// A TOTP secret together with the account it belongs to.
+pub struct TotpEnrollment {
+    totp: TOTP,
+}
+
+impl TotpEnrollment {
+    pub fn new(secret: &str, username: &str) -> Result<Self, anyhow::Error> {
+        let secret = Secret::Encoded(secret.to_owned())
+            .to_bytes()
+            .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {:?}", e))?;
+        // Colons separate the issuer from the account name in otpauth URIs.
+        let account_name = username.replace(':', "_");
+        let totp = TOTP::new(
+            Algorithm::SHA1,
+            6,
+            0,
+            TOTP_STEP_SECONDS,
+            secret,
+            Some(TOTP_ISSUER.to_owned()),
+            account_name,
+        )
+        .context("Failed to build a TOTP generator.")?;
+        Ok(Self { totp })
+    }
+
+    /// The `otpauth://` URI authenticator apps import.
+    pub fn otpauth_uri(&self) -> String {
+        self.totp.get_url()
+    }
+
+    /// The URI rendered as an inline SVG QR code.
+    pub fn qr_code_svg(&self) -> Result<String, anyhow::Error> {
+        let code = QrCode::new(self.otpauth_uri().as_bytes())
+            .context("Failed to encode the otpauth URI as a QR code.")?;
+        Ok(code.render::<svg::Color>().min_dimensions(200, 200).build())
+    }
+
+    /// The time step `code` is valid for, allowing one step of clock drift either way.
+    ///
+    /// Steps up to and including `last_used_step` are rejected, so a code cannot be used twice.
+    pub fn verify(&self, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("System time is before the UNIX epoch")
+            .as_secs();
+        let current_step = (now / TOTP_STEP_SECONDS) as i64;
+        (current_step - 1..=current_step + 1)
+            .filter(|step| last_used_step.is_none_or(|last| *step > last))
+            .find(|step| self.totp.check(code, *step as u64 * TOTP_STEP_SECONDS))
+    }
+}
+
+/// The user's TOTP secret, if they have enabled two-factor authentication.
+#[tracing::instrument(name = "Get TOTP secret", skip(pool))]
+pub async fn get_totp_secret(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT totp_secret
+        FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a TOTP secret.")?;
+    Ok(row.and_then(|r| r.totp_secret))
+}
+
+/// Turn on two-factor authentication, returning a fresh set of recovery codes.
+///
+/// `confirmed_step` is the time step of the code the user entered to prove their app works,
+/// so that code cannot be reused to log in.
+#[tracing::instrument(name = "Enable two-factor authentication", skip(secret, pool))]
+pub async fn enable_two_factor(
+    user_id: Uuid,
+    secret: &str,
+    confirmed_step: i64,
+    pool: &PgPool,
+) -> Result<Vec<String>, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = $2, totp_last_used_step = $3
+        WHERE user_id = $1
+        "#,
+        user_id,
+        secret,
+        confirmed_step
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store the TOTP secret.")?;
+    sqlx::query!(
+        r#"
+        DELETE FROM user_recovery_codes
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove old recovery codes.")?;
+
+    let codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+        .map(|_| generate_recovery_code())
+        .collect();
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|code| hash_token(&normalize_recovery_code(code)))
+        .collect();
+    sqlx::query!(
+        r#"
+        INSERT INTO user_recovery_codes (user_id, code_hash)
+        SELECT $1, code_hash FROM UNNEST($2::text[]) AS code_hash
+        "#,
+        user_id,
+        &hashes
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to store recovery codes.")?;
+
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to enable two-factor authentication.")?;
+    Ok(codes)
+}
+
+#[tracing::instrument(name = "Disable two-factor authentication", skip(pool))]
+pub async fn disable_two_factor(user_id: Uuid, pool: &PgPool) -> Result<(), anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET totp_secret = NULL, totp_last_used_step = NULL
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove the TOTP secret.")?;
+    sqlx::query!(
+        r#"
+        DELETE FROM user_recovery_codes
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to remove recovery codes.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to disable two-factor authentication.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Count unused recovery codes", skip(pool))]
+pub async fn count_unused_recovery_codes(
+    user_id: Uuid,
+    pool: &PgPool,
+) -> Result<i64, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT count(*) AS "count!"
+        FROM user_recovery_codes
+        WHERE user_id = $1 AND used_at IS NULL
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+    .context("Failed to count unused recovery codes.")?;
+    Ok(row.count)
+}
+
+/// Check the second factor entered at login, either a TOTP code or a recovery code.
+///
+/// Accepted codes are used up: the TOTP time step is remembered and recovery codes are marked.
+#[tracing::instrument(name = "Verify second factor", skip(code, pool))]
+pub async fn verify_second_factor(
+    user_id: Uuid,
+    code: &str,
+    pool: &PgPool,
+) -> Result<bool, anyhow::Error> {
+    let code = code.trim();
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let row = sqlx::query!(
+        r#"
+        SELECT username, totp_secret, totp_last_used_step
+        FROM users
+        WHERE user_id = $1
+        FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *transaction)
+    .await
+    .context("Failed to retrieve the TOTP secret.")?;
+    let Some((username, Some(secret), last_used_step)) =
+        row.map(|r| (r.username, r.totp_secret, r.totp_last_used_step))
+    else {
+        return Ok(false);
+    };
+
+    let accepted = if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        let Some(step) = TotpEnrollment::new(&secret, &username)?.verify(code, last_used_step)
+        else {
+            return Ok(false);
+        };
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_last_used_step = $2
+            WHERE user_id = $1
+            "#,
+            user_id,
+            step
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to record the TOTP time step.")?;
+        true
+    } else {
+        sqlx::query!(
+            r#"
+            UPDATE user_recovery_codes
+            SET used_at = now()
+            WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+            "#,
+            user_id,
+            hash_token(&normalize_recovery_code(code))
+        )
+        .execute(&mut *transaction)
+        .await
+        .context("Failed to use up a recovery code.")?
+        .rows_affected()
+            > 0
+    };
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to verify a second factor.")?;
+    Ok(accepted)
+}
+
+/// A recovery code of the form `xxxxx-xxxxx`.
+fn generate_recovery_code() -> String {
+    let mut rng = thread_rng();
+    let mut code: String = (0..10)
+        .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+        .collect();
+    code.insert(5, '-');
+    code
+}
+
+/// Recovery codes are accepted regardless of case, dashes and spaces.
+fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use claims::{assert_none, assert_some};
+
+    use super::{
+        generate_recovery_code, generate_totp_secret, normalize_recovery_code, TotpEnrollment,
+        TOTP_STEP_SECONDS,
+    };
+
+    fn current_step() -> i64 {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        (now / TOTP_STEP_SECONDS) as i64
+    }
+
+    fn code_for_step(enrollment: &TotpEnrollment, step: i64) -> String {
+        enrollment.totp.generate(step as u64 * TOTP_STEP_SECONDS)
+    }
+
+    #[test]
+    fn the_current_code_is_accepted() {
+        let enrollment = TotpEnrollment::new(&generate_totp_secret(), "ursula").unwrap();
+        let step = current_step();
+        let code = code_for_step(&enrollment, step);
+        assert_some!(enrollment.verify(&code, None));
+    }
+
+    #[test]
+    fn a_code_cannot_be_replayed() {
+        let enrollment = TotpEnrollment::new(&generate_totp_secret(), "ursula").unwrap();
+        let step = current_step() + 1;
+        let code = code_for_step(&enrollment, step);
+        assert_none!(enrollment.verify(&code, Some(step)));
+    }
+
+    #[test]
+    fn codes_from_far_away_steps_are_rejected() {
+        let enrollment = TotpEnrollment::new(&generate_totp_secret(), "ursula").unwrap();
+        let code = code_for_step(&enrollment, current_step() - 10);
+        assert_none!(enrollment.verify(&code, None));
+    }
+
+    #[test]
+    fn the_otpauth_uri_names_the_issuer_and_account() {
+        let enrollment = TotpEnrollment::new(&generate_totp_secret(), "ursula").unwrap();
+        let uri = enrollment.otpauth_uri();
+        assert!(uri.starts_with("otpauth://totp/zero2prod:ursula?"));
+        assert!(uri.contains("issuer=zero2prod"));
+    }
+
+    #[test]
+    fn recovery_codes_are_normalized() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert_eq!(
+            normalize_recovery_code(&code.to_uppercase().replace('-', " ")),
+            normalize_recovery_code(&code)
+        );
+    }
+}
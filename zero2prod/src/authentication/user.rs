@@ -1,7 +1,60 @@
 use anyhow::Context;
-use sqlx::PgPool;
+use secrecy::{ExposeSecret, Secret};
+use sqlx::{PgExecutor, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
+use crate::{error_chain_fmt, telemetry::spawn_blocking_with_tracing};
+
+use super::{PasswordHashing, Role};
+
+pub struct UserSummary {
+    pub user_id: Uuid,
+    pub username: String,
+    pub email: Option<String>,
+    pub role: Role,
+    pub disabled: bool,
+}
+
+#[derive(thiserror::Error)]
+pub enum UserManagementError {
+    #[error("The user does not exist.")]
+    UnknownUser,
+    #[error("At least one active owner must remain.")]
+    LastActiveOwner,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UserManagementError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+/// Usernames are 1 to 64 letters, digits, '.', '-' or '_'.
+pub fn is_valid_username(username: &str) -> bool {
+    (1..=64).contains(&username.len())
+        && username
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_'))
+}
+
+#[tracing::instrument(name = "Get user id", skip(pool))]
+pub async fn get_user_id(username: &str, pool: &PgPool) -> Result<Option<Uuid>, anyhow::Error> {
+    let user_id = sqlx::query_scalar!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE username = $1
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a user id.")?;
+    Ok(user_id)
+}
+
 #[tracing::instrument(name = "Get username", skip(pool))]
 pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow::Error> {
     let row = sqlx::query!(
@@ -17,3 +70,236 @@ pub async fn get_username(user_id: Uuid, pool: &PgPool) -> Result<String, anyhow
     .context("Failed to perform a query to retrieve a username.")?;
     Ok(row.username)
 }
+
+#[tracing::instrument(name = "Get user email", skip(pool))]
+pub async fn get_user_email(
+    username: &str,
+    pool: &PgPool,
+) -> Result<Option<String>, anyhow::Error> {
+    let row = sqlx::query!(
+        r#"
+        SELECT email
+        FROM users
+        WHERE username = $1
+        "#,
+        username
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a user's email.")?;
+    Ok(row.and_then(|r| r.email))
+}
+
+#[tracing::instrument(name = "List users", skip(pool))]
+pub async fn list_users(pool: &PgPool) -> Result<Vec<UserSummary>, anyhow::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT user_id, username, email, role, disabled
+        FROM users
+        ORDER BY username
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list users.")?;
+    rows.into_iter()
+        .map(|r| {
+            Ok(UserSummary {
+                user_id: r.user_id,
+                username: r.username,
+                email: r.email,
+                role: r.role.parse().map_err(anyhow::Error::msg)?,
+                disabled: r.disabled,
+            })
+        })
+        .collect()
+}
+
+#[tracing::instrument(name = "Create user", skip(password, hashing, transaction))]
+pub async fn create_user(
+    transaction: &mut Transaction<'_, Postgres>,
+    username: &str,
+    email: &str,
+    role: Role,
+    password: Secret<String>,
+    hashing: &PasswordHashing,
+) -> Result<Uuid, anyhow::Error> {
+    let hashing = hashing.clone();
+    let password_hash = spawn_blocking_with_tracing(move || hashing.hash(password))
+        .await?
+        .context("Failed to hash password")?;
+    let user_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO users (user_id, username, password_hash, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        username,
+        password_hash.expose_secret(),
+        email,
+        role.as_str()
+    )
+    .execute(&mut **transaction)
+    .await
+    .context("Failed to insert a new user in the database.")?;
+    Ok(user_id)
+}
+
+#[tracing::instrument(name = "Set user disabled", skip(pool))]
+pub async fn set_user_disabled(
+    user_id: Uuid,
+    disabled: bool,
+    pool: &PgPool,
+) -> Result<(), UserManagementError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    if disabled {
+        ensure_another_active_owner(&mut transaction, user_id).await?;
+    }
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users
+        SET disabled = $2
+        WHERE user_id = $1
+        "#,
+        user_id,
+        disabled
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update the user's status.")?
+    .rows_affected();
+    if updated == 0 {
+        return Err(UserManagementError::UnknownUser);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a user's status.")?;
+    Ok(())
+}
+
+/// Let a disabled user log in again, e.g. as part of resetting their password.
+/// Returns whether they were disabled.
+#[tracing::instrument(name = "Enable user", skip(executor))]
+pub async fn enable_user(
+    user_id: Uuid,
+    executor: impl PgExecutor<'_>,
+) -> Result<bool, anyhow::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users
+        SET disabled = false
+        WHERE user_id = $1 AND disabled = true
+        "#,
+        user_id
+    )
+    .execute(executor)
+    .await
+    .context("Failed to enable the user.")?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+#[tracing::instrument(name = "Delete user", skip(pool))]
+pub async fn delete_user(user_id: Uuid, pool: &PgPool) -> Result<(), UserManagementError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    ensure_another_active_owner(&mut transaction, user_id).await?;
+    sqlx::query!(
+        r#"
+        DELETE FROM idempotency
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the user's idempotency records.")?;
+    let deleted = sqlx::query!(
+        r#"
+        DELETE FROM users
+        WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to delete the user.")?
+    .rows_affected();
+    if deleted == 0 {
+        return Err(UserManagementError::UnknownUser);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to delete a user.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Set user role", skip(pool))]
+pub async fn set_user_role(
+    user_id: Uuid,
+    role: Role,
+    pool: &PgPool,
+) -> Result<(), UserManagementError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    if role != Role::Owner {
+        ensure_another_active_owner(&mut transaction, user_id).await?;
+    }
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users
+        SET role = $2
+        WHERE user_id = $1
+        "#,
+        user_id,
+        role.as_str()
+    )
+    .execute(&mut *transaction)
+    .await
+    .context("Failed to update the user's role.")?
+    .rows_affected();
+    if updated == 0 {
+        return Err(UserManagementError::UnknownUser);
+    }
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to update a user's role.")?;
+    Ok(())
+}
+
+/// Refuse to take `user_id` out of service if nobody else could still manage users.
+///
+/// All active owners are locked until the transaction ends, so two owners cannot
+/// demote or disable each other at the same time.
+async fn ensure_another_active_owner(
+    transaction: &mut Transaction<'_, Postgres>,
+    user_id: Uuid,
+) -> Result<(), UserManagementError> {
+    let active_owners = sqlx::query!(
+        r#"
+        SELECT user_id
+        FROM users
+        WHERE disabled = false AND role = 'owner'
+        FOR UPDATE
+        "#
+    )
+    .fetch_all(&mut **transaction)
+    .await
+    .context("Failed to count the active owners.")?;
+    let is_owner = active_owners.iter().any(|r| r.user_id == user_id);
+    if is_owner && active_owners.len() == 1 {
+        return Err(UserManagementError::LastActiveOwner);
+    }
+    Ok(())
+}
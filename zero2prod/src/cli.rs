@@ -0,0 +1,161 @@
+use uuid::Uuid;
+
+mod operations;
+
+/// What an invocation of the `zero2prod` binary runs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// The HTTP server and every worker, in one process.
+    All,
+    /// Only the HTTP server.
+    Serve,
+    /// Only one of the background workers, so it can be scaled on its own.
+    Worker(Worker),
+    /// A one-off task for operators, which exits once done.
+    Operation(Operation),
+    Help,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Worker {
+    Delivery,
+    IdempotencyCleanup,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Operation {
+    Migrate,
+    CreateAdmin { username: String, email: String },
+    ResetPassword { username: String },
+    SendTestEmail { recipient: String },
+    QueueDepth,
+    RequeueDeadLetters { newsletter_issue_id: Option<Uuid> },
+}
+
+pub const USAGE: &str = r#"
+Usage: zero2prod [command]
+
+Commands:
+  all                               runs the HTTP server and every worker (default)
+  serve                             runs the HTTP server
+  worker delivery                   runs the newsletter issue delivery worker
+  worker idempotency-cleanup        runs the worker removing expired idempotency keys
+  migrate                           applies the pending database migrations
+  admin create <username> <email>   creates an owner, reading their password from stdin
+  admin reset-password <username>   sets a user's password from stdin, re-enables them
+                                    and signs them out everywhere
+  send-test-email <recipient>       sends an email through the configured email provider
+  queue depth                       lists the deliveries left for each issue
+  queue requeue-dead [issue id]     queues the deliveries that failed for good again,
+                                    of one issue or of all of them
+  help                              prints this message
+"#;
+
+impl Command {
+    /// Parse the arguments following the name of the binary.
+    pub fn parse<I>(args: I) -> Result<Self, anyhow::Error>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let args: Vec<String> = args.into_iter().collect();
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let command = match args.as_slice() {
+            [] | ["all"] => Command::All,
+            ["serve"] => Command::Serve,
+            ["worker", "delivery"] => Command::Worker(Worker::Delivery),
+            ["worker", "idempotency-cleanup"] => Command::Worker(Worker::IdempotencyCleanup),
+            ["worker", ..] => anyhow::bail!("Unknown worker: {}", args[1..].join(" ")),
+            ["migrate"] => Command::Operation(Operation::Migrate),
+            ["admin", "create", username, email] => Command::Operation(Operation::CreateAdmin {
+                username: username.to_string(),
+                email: email.to_string(),
+            }),
+            ["admin", "reset-password", username] => Command::Operation(Operation::ResetPassword {
+                username: username.to_string(),
+            }),
+            ["send-test-email", recipient] => Command::Operation(Operation::SendTestEmail {
+                recipient: recipient.to_string(),
+            }),
+            ["queue", "depth"] => Command::Operation(Operation::QueueDepth),
+            ["queue", "requeue-dead", rest @ ..] if rest.len() <= 1 => {
+                let newsletter_issue_id = rest
+                    .first()
+                    .map(|id| Uuid::parse_str(id))
+                    .transpose()
+                    .map_err(|_| anyhow::anyhow!("Not an issue id: {}", rest[0]))?;
+                Command::Operation(Operation::RequeueDeadLetters {
+                    newsletter_issue_id,
+                })
+            }
+            ["help" | "--help" | "-h"] => Command::Help,
+            _ => anyhow::bail!("Unknown command: {}", args.join(" ")),
+        };
+        Ok(command)
+    }
+
+    pub fn runs_server(&self) -> bool {
+        matches!(self, Command::All | Command::Serve)
+    }
+
+    pub fn runs_worker(&self, worker: Worker) -> bool {
+        match self {
+            Command::All => true,
+            Command::Worker(w) => *w == worker,
+            Command::Serve | Command::Operation(_) | Command::Help => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Command, Operation, Worker};
+
+    fn parse(args: &[&str]) -> Result<Command, anyhow::Error> {
+        Command::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn everything_runs_without_a_command() {
+        let command = parse(&[]).unwrap();
+
+        assert_eq!(command, Command::All);
+        assert!(command.runs_server());
+        assert!(command.runs_worker(Worker::Delivery));
+        assert!(command.runs_worker(Worker::IdempotencyCleanup));
+    }
+
+    #[test]
+    fn a_worker_runs_on_its_own() {
+        let command = parse(&["worker", "delivery"]).unwrap();
+
+        assert!(!command.runs_server());
+        assert!(command.runs_worker(Worker::Delivery));
+        assert!(!command.runs_worker(Worker::IdempotencyCleanup));
+    }
+
+    #[test]
+    fn unknown_commands_are_rejected() {
+        assert!(parse(&["worker"]).is_err());
+        assert!(parse(&["worker", "unknown"]).is_err());
+        assert!(parse(&["serve", "now"]).is_err());
+    }
+
+    #[test]
+    fn operations_take_their_arguments() {
+        assert_eq!(
+            parse(&["admin", "create", "ada", "ada@example.com"]).unwrap(),
+            Command::Operation(Operation::CreateAdmin {
+                username: "ada".into(),
+                email: "ada@example.com".into(),
+            })
+        );
+        assert_eq!(
+            parse(&["queue", "requeue-dead"]).unwrap(),
+            Command::Operation(Operation::RequeueDeadLetters {
+                newsletter_issue_id: None
+            })
+        );
+        assert!(parse(&["queue", "requeue-dead", "not-an-id"]).is_err());
+        assert!(parse(&["admin", "create", "ada"]).is_err());
+    }
+}
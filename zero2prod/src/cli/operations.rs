@@ -0,0 +1,200 @@
+use std::io::{BufRead, Write};
+
+use anyhow::Context;
+use secrecy::Secret;
+use sqlx::PgPool;
+
+use super::Operation;
+use crate::{
+    audit::{insert_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        change_password, create_user, enable_user, get_user_email, get_user_id, is_valid_username,
+        revoke_all_user_sessions, PasswordHashing, PasswordPolicy, Role,
+    },
+    configuration::Settings,
+    domain::SubscriberEmail,
+    issue_delivery_worker::{get_queue_depths, requeue_dead_letters},
+    startup::{get_db_pool, MIGRATOR},
+};
+
+impl Operation {
+    /// Run the operation to completion, reporting what it did on stdout.
+    pub async fn run(self, configuration: Settings) -> Result<(), anyhow::Error> {
+        let pool = get_db_pool(&configuration.database);
+        match self {
+            Operation::Migrate => {
+                MIGRATOR
+                    .run(&pool)
+                    .await
+                    .context("Failed to migrate the database.")?;
+                println!("The database is up to date.");
+            }
+            Operation::CreateAdmin { username, email } => {
+                create_admin(&username, email, &configuration, &pool).await?;
+                println!("Created the owner '{}'.", username);
+            }
+            Operation::ResetPassword { username } => {
+                reset_password(&username, &configuration, &pool).await?;
+                println!(
+                    "Reset the password of '{}' and signed them out everywhere.",
+                    username
+                );
+            }
+            Operation::SendTestEmail { recipient } => {
+                let recipient = SubscriberEmail::parse(recipient).map_err(anyhow::Error::msg)?;
+                configuration
+                    .email_client
+                    .client()
+                    .send_email(
+                        &recipient,
+                        "Test email",
+                        "<p>The email provider is configured correctly.</p>",
+                        "The email provider is configured correctly.",
+                    )
+                    .await
+                    .context("The email provider rejected the test email.")?;
+                println!("Sent a test email to {}.", recipient);
+            }
+            Operation::QueueDepth => {
+                let depths = get_queue_depths(&pool).await?;
+                println!(
+                    "{:<36}  {:>8}  {:>8}  {:>8}  TITLE",
+                    "ISSUE", "PENDING", "RETRYING", "DEAD"
+                );
+                for depth in depths {
+                    println!(
+                        "{:<36}  {:>8}  {:>8}  {:>8}  {}",
+                        depth.newsletter_issue_id,
+                        depth.pending,
+                        depth.retrying,
+                        depth.dead_letters,
+                        depth.title
+                    );
+                }
+            }
+            Operation::RequeueDeadLetters {
+                newsletter_issue_id,
+            } => {
+                let requeued = requeue_dead_letters(newsletter_issue_id, &pool).await?;
+                println!("Queued {} deliveries again.", requeued);
+            }
+        }
+        Ok(())
+    }
+}
+
+async fn create_admin(
+    username: &str,
+    email: String,
+    configuration: &Settings,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    if !is_valid_username(username) {
+        anyhow::bail!(
+            "Usernames must be 1 to 64 characters long and only contain letters, \
+            digits, '.', '-' or '_'."
+        );
+    }
+    let email = SubscriberEmail::parse(email).map_err(anyhow::Error::msg)?;
+    if get_user_id(username, pool).await?.is_some() {
+        anyhow::bail!(
+            "The user '{}' already exists, reset their password instead.",
+            username
+        );
+    }
+    let password = read_new_password(&[username, email.as_ref()], configuration).await?;
+
+    let hashing = PasswordHashing::new(&configuration.password_hashing)?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let user_id = create_user(
+        &mut transaction,
+        username,
+        email.as_ref(),
+        Role::Owner,
+        password,
+        &hashing,
+    )
+    .await?;
+    insert_audit_event(
+        &mut *transaction,
+        AuditEvent::without_request(AuditAction::UserCreated).target(format!(
+            "user:{} ({})",
+            user_id,
+            Role::Owner
+        )),
+    )
+    .await
+    .context("Failed to record the new user in the audit log.")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to create a user.")?;
+    Ok(())
+}
+
+async fn reset_password(
+    username: &str,
+    configuration: &Settings,
+    pool: &PgPool,
+) -> Result<(), anyhow::Error> {
+    let user_id = get_user_id(username, pool)
+        .await?
+        .with_context(|| format!("There is no user called '{}'.", username))?;
+    let email = get_user_email(username, pool).await?;
+    let mut personal_info = vec![username];
+    personal_info.extend(email.as_deref());
+    let password = read_new_password(&personal_info, configuration).await?;
+
+    let hashing = PasswordHashing::new(&configuration.password_hashing)?;
+    // All or nothing, so a failure cannot leave the old sessions of an unlocked account
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    change_password(user_id, password, &hashing, &mut *transaction).await?;
+    let target = format!("user:{}", user_id);
+    insert_audit_event(
+        &mut *transaction,
+        AuditEvent::without_request(AuditAction::PasswordReset).target(&target),
+    )
+    .await
+    .context("Failed to record the password reset in the audit log.")?;
+    if enable_user(user_id, &mut *transaction).await? {
+        insert_audit_event(
+            &mut *transaction,
+            AuditEvent::without_request(AuditAction::UserEnabled).target(target),
+        )
+        .await
+        .context("Failed to record enabling the user in the audit log.")?;
+    }
+    revoke_all_user_sessions(user_id, None, &mut *transaction).await?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to reset a password.")?;
+    Ok(())
+}
+
+/// Read a password from the first line of stdin, so it stays out of the shell history,
+/// and check it against the password policy.
+async fn read_new_password(
+    personal_info: &[&str],
+    configuration: &Settings,
+) -> Result<Secret<String>, anyhow::Error> {
+    eprint!("Password: ");
+    std::io::stderr().flush()?;
+    let mut line = String::new();
+    std::io::stdin()
+        .lock()
+        .read_line(&mut line)
+        .context("Failed to read the password from stdin.")?;
+    let password = Secret::new(line.trim_end_matches(['\r', '\n']).to_string());
+
+    PasswordPolicy::new(configuration.password_policy.clone())
+        .check(&password, &password, personal_info)
+        .await?;
+    Ok(password)
+}
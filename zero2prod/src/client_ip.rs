@@ -0,0 +1,117 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts},
+};
+use http::{request::Parts, StatusCode};
+
+/// Whether the application runs behind a reverse proxy that appends the address of the
+/// client it accepted the request from to `X-Forwarded-For`.
+///
+/// Only that last entry is trusted: anything before it was sent by the client.
+#[derive(Clone, Copy, Debug)]
+pub struct TrustForwardedFor(pub bool);
+
+/// The IP address of the client that sent the request.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+impl std::fmt::Display for ClientIp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ClientIp {
+    pub fn from_parts(parts: &Parts, trust_forwarded_for: TrustForwardedFor) -> Option<Self> {
+        if trust_forwarded_for.0 {
+            let forwarded = parts
+                .headers
+                .get_all("x-forwarded-for")
+                .iter()
+                .next_back()
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|value| value.trim().parse::<IpAddr>().ok());
+            if let Some(ip) = forwarded {
+                return Some(Self(ip));
+            }
+        }
+        parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(address)| Self(address.ip()))
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientIp
+where
+    TrustForwardedFor: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        Self::from_parts(parts, TrustForwardedFor::from_ref(state)).ok_or((
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Unable to determine the client address.",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, SocketAddr};
+
+    use axum::extract::ConnectInfo;
+    use http::Request;
+
+    use super::{ClientIp, TrustForwardedFor};
+
+    const PROXY: &str = "10.0.0.2:41000";
+
+    fn client_ip(forwarded_for: &[&str], trust_forwarded_for: bool) -> IpAddr {
+        let mut request = Request::builder();
+        for value in forwarded_for {
+            request = request.header("x-forwarded-for", *value);
+        }
+        let (mut parts, _) = request.body(()).unwrap().into_parts();
+        parts
+            .extensions
+            .insert(ConnectInfo(PROXY.parse::<SocketAddr>().unwrap()));
+        ClientIp::from_parts(&parts, TrustForwardedFor(trust_forwarded_for))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn the_address_appended_by_the_proxy_is_used() {
+        assert_eq!(
+            client_ip(&["203.0.113.7"], true),
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn addresses_sent_by_the_client_are_ignored() {
+        // The client sent its own header, and the proxy appended the real address
+        let spoofed = client_ip(&["198.51.100.1, 203.0.113.7"], true);
+        let spoofed_in_another_header = client_ip(&["198.51.100.1", "203.0.113.7"], true);
+
+        assert_eq!(spoofed, "203.0.113.7".parse::<IpAddr>().unwrap());
+        assert_eq!(
+            spoofed_in_another_header,
+            "203.0.113.7".parse::<IpAddr>().unwrap()
+        );
+    }
+
+    #[test]
+    fn the_header_is_ignored_without_a_trusted_proxy() {
+        assert_eq!(
+            client_ip(&["203.0.113.7"], false),
+            "10.0.0.2".parse::<IpAddr>().unwrap()
+        );
+    }
+}
@@ -1,3 +1,4 @@
+use axum_session::{SameSite, SessionConfig};
 use secrecy::{ExposeSecret, Secret};
 use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
@@ -6,7 +7,10 @@ use sqlx::{
     ConnectOptions,
 };
 
-use crate::{domain::SubscriberEmail, email_client::EmailClient};
+use crate::{
+    domain::SubscriberEmail, email_client::EmailClient, form_token::FormTokenSigner,
+    shutdown::Shutdown,
+};
 
 pub fn get_configuration() -> Result<Settings, config::ConfigError> {
     // Grab the execution directory
@@ -49,6 +53,17 @@ pub struct Settings {
     pub database: DatabaseSettings,
     pub email_client: EmailClientSettings,
     pub redis: RedisSettings,
+    pub subscriptions: SubscriptionSettings,
+    pub rate_limit: RateLimitSettings,
+    pub login_protection: LoginProtectionSettings,
+    pub password_hashing: PasswordHashingSettings,
+    pub password_policy: PasswordPolicySettings,
+    pub bootstrap_admin: BootstrapAdminSettings,
+    pub session: SessionSettings,
+    pub security_headers: SecurityHeadersSettings,
+    pub metrics: MetricsSettings,
+    pub opentelemetry: OpenTelemetrySettings,
+    pub logging: LoggingSettings,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -56,6 +71,200 @@ pub struct RedisSettings {
     pub uri: Secret<String>,
 }
 
+#[derive(Clone, Debug, Deserialize)]
+pub struct SubscriptionSettings {
+    /// Submissions made sooner than this after the form was rendered are treated as bots.
+    pub form_token_min_age_seconds: u64,
+    /// Form tokens older than this are rejected and the visitor must reload the form.
+    pub form_token_max_age_seconds: u64,
+}
+
+impl SubscriptionSettings {
+    pub fn form_token_signer(&self, hmac_secret: Secret<String>) -> FormTokenSigner {
+        FormTokenSigner::new(
+            hmac_secret,
+            std::time::Duration::from_secs(self.form_token_min_age_seconds),
+            std::time::Duration::from_secs(self.form_token_max_age_seconds),
+        )
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct RateLimitSettings {
+    /// Prefix for the Redis keys holding the counters.
+    pub key_prefix: String,
+    pub login_per_ip: RateLimitPolicy,
+    pub subscriptions_per_ip: RateLimitPolicy,
+    pub subscriptions_per_email: RateLimitPolicy,
+    pub password_reset_per_ip: RateLimitPolicy,
+    pub password_reset_per_email: RateLimitPolicy,
+}
+
+/// Allow at most `max_requests` in every window of `window_seconds`.
+#[derive(Clone, Debug, Deserialize)]
+pub struct RateLimitPolicy {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_requests: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LoginProtectionSettings {
+    /// Prefix for the Redis keys holding failure counters and locks.
+    pub key_prefix: String,
+    /// How long a failed attempt is remembered.
+    pub failure_window_seconds: u64,
+    /// Failures tolerated before every further attempt is slowed down.
+    pub delay_after_failures: u64,
+    /// The first delay, doubled with every further failure.
+    pub base_delay_milliseconds: u64,
+    pub max_delay_milliseconds: u64,
+    /// Failures against one username before that account is locked.
+    pub lock_after_failures: u64,
+    /// Failures from one client IP, across all usernames, before that IP is locked.
+    pub ip_lock_after_failures: u64,
+    pub lock_seconds: u64,
+}
+
+/// Argon2id cost parameters for new password hashes.
+///
+/// Raising them is safe: existing hashes are upgraded as their owners log in.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PasswordHashingSettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub memory_kib: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub iterations: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub parallelism: u32,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct PasswordPolicySettings {
+    /// New passwords estimated to take fewer guesses than 2 to this power are rejected.
+    pub min_strength_bits: f64,
+    /// A directory of Have I Been Pwned range files (`<PREFIX>.txt` with `SUFFIX:COUNT`
+    /// lines) to reject breached passwords. The check is skipped when unset.
+    pub breached_passwords_directory: Option<String>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SessionSettings {
+    pub cookie_name: String,
+    /// Only send the cookie over HTTPS. Disable for local development over plain HTTP.
+    pub secure: bool,
+    pub same_site: SameSitePolicy,
+    /// Sessions expire after this long without a request.
+    pub idle_timeout_minutes: i64,
+    /// Sessions expire this long after the login, however active they are.
+    pub absolute_timeout_hours: i64,
+    /// Share the cookie with subdomains of this domain. Unset, it is sent to this host only.
+    pub domain: Option<String>,
+}
+
+impl SessionSettings {
+    pub fn session_config(&self) -> SessionConfig {
+        let config = SessionConfig::new()
+            .with_session_name(self.cookie_name.clone())
+            .with_secure(self.secure)
+            .with_http_only(true)
+            .with_cookie_same_site(self.same_site.into())
+            .with_lifetime(chrono::Duration::minutes(self.idle_timeout_minutes));
+        match &self.domain {
+            Some(domain) => config.with_cookie_domain(domain.clone()),
+            None => config,
+        }
+    }
+
+    pub fn idle_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.idle_timeout_minutes as u64 * 60)
+    }
+
+    pub fn absolute_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.absolute_timeout_hours as u64 * 60 * 60)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SameSitePolicy {
+    Strict,
+    Lax,
+    None,
+}
+
+impl From<SameSitePolicy> for SameSite {
+    fn from(value: SameSitePolicy) -> Self {
+        match value {
+            SameSitePolicy::Strict => SameSite::Strict,
+            SameSitePolicy::Lax => SameSite::Lax,
+            SameSitePolicy::None => SameSite::None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SecurityHeadersSettings {
+    /// The `Content-Security-Policy` of every page that does not set its own.
+    pub content_security_policy: String,
+    /// `max-age` of `Strict-Transport-Security`. Zero leaves the header out, e.g. when
+    /// the application is served over plain HTTP.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub hsts_max_age_seconds: u64,
+}
+
+/// The first owner of a new install, created on startup while there are no other users.
+#[derive(Clone, Debug, Deserialize)]
+pub struct BootstrapAdminSettings {
+    pub username: String,
+    pub email: String,
+    /// Keep it in a secret rather than a file. Nobody is created while it is unset.
+    pub password: Option<Secret<String>>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct MetricsSettings {
+    /// Serve `/metrics` on this port of the application host instead of the public one,
+    /// so it can be kept off the internet. It needs no token there.
+    ///
+    /// Processes that only run workers serve their metrics here too, or not at all.
+    #[serde(deserialize_with = "deserialize_optional_port")]
+    pub port: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct LoggingSettings {
+    pub format: LogFormat,
+    /// `EnvFilter` directives, e.g. `info,zero2prod=debug`. `RUST_LOG` takes precedence.
+    pub filter: String,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Bunyan records, for tools like the `bunyan` CLI.
+    Bunyan,
+    /// Plain JSON objects, one per line.
+    Json,
+    /// Multi-line and human readable, for local development.
+    Pretty,
+    /// One line per event.
+    Compact,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OpenTelemetrySettings {
+    /// The OTLP/gRPC endpoint of the collector to export spans to. Nothing is exported
+    /// when it is not set.
+    pub endpoint: Option<String>,
+    pub service_name: String,
+    /// The share of traces to export, between 0 and 1. Traces continued from an incoming
+    /// `traceparent` follow the sampling decision of the caller instead.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub sampling_ratio: f64,
+}
+
 #[derive(Clone, Debug, Deserialize)]
 pub struct ApplicationSettings {
     #[serde(deserialize_with = "deserialize_number_from_string")]
@@ -63,6 +272,18 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Take the client address from the last `X-Forwarded-For` entry. Only enable behind a
+    /// single reverse proxy that appends to the header.
+    pub trust_forwarded_for: bool,
+    /// How long in-flight requests and deliveries get to finish once asked to shut down.
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub drain_timeout_seconds: u64,
+}
+
+impl ApplicationSettings {
+    pub fn shutdown(&self) -> Shutdown {
+        Shutdown::new(std::time::Duration::from_secs(self.drain_timeout_seconds))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -159,3 +380,43 @@ impl TryFrom<String> for Environment {
         }
     }
 }
+
+/// A port from a file or an environment variable, which `serde_aux` cannot deserialize
+/// into an `Option` as the latter only comes as an owned string.
+fn deserialize_optional_port<'de, D>(deserializer: D) -> Result<Option<u16>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum PortOrString {
+        Port(u16),
+        String(String),
+    }
+
+    match Option::<PortOrString>::deserialize(deserializer)? {
+        Some(PortOrString::Port(port)) => Ok(Some(port)),
+        Some(PortOrString::String(s)) if s.is_empty() => Ok(None),
+        Some(PortOrString::String(s)) => s.parse().map(Some).map_err(serde::de::Error::custom),
+        None => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MetricsSettings;
+
+    fn metrics_settings(port: &str) -> Result<MetricsSettings, config::ConfigError> {
+        config::Config::builder()
+            .set_override("port", port)?
+            .build()?
+            .try_deserialize()
+    }
+
+    #[test]
+    fn the_metrics_port_can_come_from_an_environment_variable() {
+        assert_eq!(metrics_settings("9911").unwrap().port, Some(9911));
+        assert_eq!(metrics_settings("").unwrap().port, None);
+        assert!(metrics_settings("metrics").is_err());
+    }
+}
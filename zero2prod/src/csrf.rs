@@ -0,0 +1,125 @@
+use axum::{
+    async_trait,
+    body::{to_bytes, Body},
+    extract::FromRequestParts,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_flash::Flash;
+use axum_session::SessionRedisPool;
+use http::{request::Parts, Request, StatusCode};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{error::Problem, session_state::TypedSession};
+
+/// The form field forms submit their token in.
+pub const CSRF_FIELD: &str = "csrf_token";
+/// The header scripts can send the token in instead of a form field.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+/// Matches axum's default body limit, which applies to the handlers behind the check anyway.
+const MAX_FORM_BODY_BYTES: usize = 2 * 1024 * 1024;
+
+/// The synchronizer token of the current session, to be embedded in every form it renders.
+pub struct CsrfToken(String);
+
+impl CsrfToken {
+    /// A hidden input carrying the token, to be placed inside a `<form>`.
+    pub fn hidden_input(&self) -> String {
+        format!(
+            r#"<input type="hidden" name="{}" value="{}">"#,
+            CSRF_FIELD, self.0
+        )
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = <TypedSession<SessionRedisPool> as FromRequestParts<S>>::Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let session = TypedSession::<SessionRedisPool>::from_request_parts(parts, state).await?;
+        Ok(CsrfToken(session.csrf_token()))
+    }
+}
+
+#[derive(Deserialize)]
+struct CsrfForm {
+    csrf_token: Option<String>,
+}
+
+/// Reject state-changing requests that do not carry the token of their session.
+///
+/// The token is read from the [`CSRF_HEADER`] header or, failing that, from the
+/// [`CSRF_FIELD`] field of a form body, which is handed on untouched.
+pub async fn verify_csrf_token(
+    session: TypedSession<SessionRedisPool>,
+    flash: Flash,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    if request.method().is_safe() {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let header_token = parts
+        .headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+    let (submitted, body) = match header_token {
+        Some(token) => (Some(token), body),
+        None => {
+            let bytes = match to_bytes(body, MAX_FORM_BODY_BYTES).await {
+                Ok(bytes) => bytes,
+                Err(_) => return Problem::new(StatusCode::PAYLOAD_TOO_LARGE).into_response(),
+            };
+            let token = serde_urlencoded::from_bytes::<CsrfForm>(&bytes)
+                .ok()
+                .and_then(|form| form.csrf_token);
+            (token, Body::from(bytes))
+        }
+    };
+
+    match (session.get_csrf_token(), submitted) {
+        (Some(expected), Some(submitted)) if tokens_match(&expected, &submitted) => {
+            next.run(Request::from_parts(parts, body)).await
+        }
+        (_, submitted) => {
+            tracing::warn!(
+                token_submitted = submitted.is_some(),
+                "Rejected a {} {} with a missing or wrong CSRF token.",
+                parts.method,
+                parts.uri.path()
+            );
+            let message = "The form has expired. Please reload the page and try again.";
+            (
+                flash.error(message),
+                Problem::new(StatusCode::FORBIDDEN).detail(message),
+            )
+                .into_response()
+        }
+    }
+}
+
+/// Compare digests rather than the tokens, so the time taken reveals nothing about the token.
+fn tokens_match(expected: &str, submitted: &str) -> bool {
+    Sha256::digest(expected.as_bytes()) == Sha256::digest(submitted.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::tokens_match;
+
+    #[test]
+    fn only_identical_tokens_match() {
+        assert!(tokens_match("abc123", "abc123"));
+        assert!(!tokens_match("abc123", "abc124"));
+        assert!(!tokens_match("abc123", ""));
+    }
+}
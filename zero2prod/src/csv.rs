@@ -0,0 +1,53 @@
+/// Render one CSV row, terminated by CRLF as described in RFC 4180.
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| csv_field(field.as_ref()))
+        .collect();
+    format!("{}\r\n", fields.join(","))
+}
+
+/// Quote a field as described in RFC 4180.
+///
+/// Fields that spreadsheets would evaluate as formulas are prefixed with a `'`,
+/// since most of what ends up in exports was typed in by users.
+fn csv_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", field)
+    } else {
+        field.to_owned()
+    };
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv_field, csv_row};
+
+    #[test]
+    fn plain_fields_are_left_alone() {
+        assert_eq!(csv_field("user:ursula"), "user:ursula");
+    }
+
+    #[test]
+    fn fields_with_separators_and_quotes_are_quoted() {
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn formulas_are_neutralised() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+    }
+
+    #[test]
+    fn rows_are_comma_separated_and_crlf_terminated() {
+        assert_eq!(csv_row(&["a", "b,c", ""]), "a,\"b,c\",\r\n");
+    }
+}
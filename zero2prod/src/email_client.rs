@@ -1,8 +1,10 @@
+use std::time::Instant;
+
 use reqwest::Client;
 use secrecy::{ExposeSecret, Secret};
 use serde::Serialize;
 
-use crate::domain::SubscriberEmail;
+use crate::{domain::SubscriberEmail, metrics::METRICS};
 
 #[derive(Debug)]
 pub struct EmailClient {
@@ -47,7 +49,9 @@ impl EmailClient {
             text_body: text_content,
         };
 
-        self.http_client
+        let started = Instant::now();
+        let outcome = self
+            .http_client
             .post(&url)
             .header(
                 "X-Postmark-Server-Token",
@@ -55,10 +59,11 @@ impl EmailClient {
             )
             .json(&request_body)
             .send()
-            .await?
-            .error_for_status()?;
+            .await
+            .and_then(|response| response.error_for_status());
+        METRICS.observe_email_send(outcome.is_ok(), started);
 
-        Ok(())
+        outcome.map(|_| ())
     }
 }
 
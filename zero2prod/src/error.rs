@@ -1,30 +1,206 @@
-use axum::response::IntoResponse;
-use http::StatusCode;
+use axum::{
+    body::Body,
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{
+    header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, WWW_AUTHENTICATE},
+    HeaderValue, Request, StatusCode,
+};
+use serde::Serialize;
+use utoipa::ToSchema;
 
-use crate::error_chain_fmt;
+use crate::{error_chain_fmt, routes::html_escape};
+
+/// What went wrong with a request, as far as the client is allowed to know.
+///
+/// Error responses carry a `Problem` as an extension and an empty body.
+/// [`render_problems`] turns it into a body once it knows who is asking.
+#[derive(Clone, Debug)]
+pub struct Problem {
+    status: StatusCode,
+    code: Option<&'static str>,
+    detail: Option<String>,
+}
+
+impl Problem {
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            status,
+            code: None,
+            detail: None,
+        }
+    }
+
+    /// A stable, machine readable name for the kind of problem, e.g. `not_found`.
+    pub fn code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    /// An explanation of this occurrence of the problem. It is shown to the client,
+    /// so it must never contain internal causes.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        let mut response = self.status.into_response();
+        response.extensions_mut().insert(self);
+        response
+    }
+}
+
+/// A problem as serialized for API clients, following RFC 7807.
+#[derive(Serialize, ToSchema)]
+pub struct ProblemDetails {
+    /// Always `about:blank`: the status code and `code` identify the problem.
+    #[serde(rename = "type")]
+    problem_type: String,
+    /// The reason phrase of the status code.
+    title: String,
+    status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+    /// A stable, machine readable name for the kind of problem, e.g. `not_found`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<String>,
+    /// Quote it when reporting the problem, to find it in the logs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
+}
+
+/// Render the [`Problem`] of error responses as `application/problem+json`, or as an
+/// HTML page for browsers.
+pub async fn render_problems(request: Request<Body>, next: Next) -> Response {
+    let wants_html = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let request_id = request
+        .headers()
+        .get("x-request-id")
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let response = next.run(request).await;
+    let Some(problem) = response.extensions().get::<Problem>().cloned() else {
+        return response;
+    };
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(CONTENT_LENGTH);
+    let details = ProblemDetails {
+        problem_type: "about:blank".into(),
+        title: problem
+            .status
+            .canonical_reason()
+            .unwrap_or("Error")
+            .to_owned(),
+        status: problem.status.as_u16(),
+        detail: problem.detail,
+        code: problem.code.map(str::to_owned),
+        request_id,
+    };
+    let (content_type, body) = if wants_html {
+        ("text/html; charset=utf-8", problem_page(&details))
+    } else {
+        (
+            "application/problem+json",
+            serde_json::to_string(&details).expect("Problem details always serialize"),
+        )
+    };
+    parts
+        .headers
+        .insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn problem_page(details: &ProblemDetails) -> String {
+    let detail = match &details.detail {
+        Some(detail) => html_escape(detail),
+        None if details.status >= 500 => {
+            "Something went wrong on our side. Please try again later.".into()
+        }
+        None => "The request could not be completed.".into(),
+    };
+    let request_id = match &details.request_id {
+        Some(request_id) => format!(
+            "<p>If the problem persists, please mention request <code>{}</code>.</p>",
+            html_escape(request_id)
+        ),
+        None => String::new(),
+    };
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>{title}</title>
+</head>
+<body>
+    <h1>{status} {title}</h1>
+    <p>{detail}</p>
+    {request_id}
+    <p><a href="/">Home</a></p>
+</body>
+</html>"#,
+        status = details.status,
+        title = details.title,
+    )
+}
+
+/// The fallback for requests that match no route.
+pub async fn not_found() -> Problem {
+    Problem::new(StatusCode::NOT_FOUND)
+}
 
 pub struct ResponseError {
     status_code: StatusCode,
     internal_error: Box<dyn std::error::Error>,
+    /// What the client is told. The internal error only ever goes to the logs.
+    detail: Option<String>,
 }
 impl ResponseError {
     pub fn new(status_code: StatusCode, internal_error: Box<dyn std::error::Error>) -> Self {
         Self {
             status_code,
             internal_error,
+            detail: None,
         }
     }
 
+    /// A client error explained by `detail`, which is shown to the client as is.
+    pub fn client_error(status_code: StatusCode, detail: impl Into<String>) -> Self {
+        let detail = detail.into();
+        Self::new(status_code, detail.clone().into()).detail(detail)
+    }
+
     pub fn set_status(mut self, status_code: StatusCode) -> Self {
         self.status_code = status_code;
         self
     }
+
+    /// Explain a client error, e.g. an invalid filter. Server errors are never explained.
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
 }
 
 impl IntoResponse for ResponseError {
     fn into_response(self) -> axum::response::Response {
         tracing::error!("{:?}", self);
-        (self.status_code, self.internal_error.to_string()).into_response()
+        let problem = Problem::new(self.status_code);
+        match self.detail {
+            Some(detail) if self.status_code.is_client_error() => problem.detail(detail),
+            _ => problem,
+        }
+        .into_response()
     }
 }
 
@@ -33,11 +209,7 @@ where
     E: Into<Box<dyn std::error::Error>>,
 {
     fn from(value: E) -> Self {
-        let internal_error: Box<dyn std::error::Error> = value.into();
-        Self {
-            status_code: StatusCode::INTERNAL_SERVER_ERROR,
-            internal_error,
-        }
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, value.into())
     }
 }
 
@@ -52,3 +224,134 @@ impl std::fmt::Display for ResponseError {
         write!(f, "{}", &self.internal_error.to_string())
     }
 }
+
+/// An error returned by the JSON API, rendered as a [`Problem`] with a `code`.
+#[derive(thiserror::Error)]
+pub enum ApiError {
+    /// The request could not be understood, e.g. malformed JSON or query parameters.
+    #[error("{0}")]
+    BadRequest(String),
+    /// The request was understood, but its content is invalid.
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    NotFound(String),
+    /// The request conflicts with the current state, e.g. publishing an issue twice.
+    #[error("{0}")]
+    Conflict(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ApiError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::NotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::Conflict(_) => StatusCode::CONFLICT,
+            ApiError::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            ApiError::Forbidden(_) => StatusCode::FORBIDDEN,
+            ApiError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    /// A stable, machine readable name for the kind of error.
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::BadRequest(_) => "bad_request",
+            ApiError::ValidationError(_) => "validation_failed",
+            ApiError::NotFound(_) => "not_found",
+            ApiError::Conflict(_) => "conflict",
+            ApiError::Unauthorized(_) => "unauthorized",
+            ApiError::Forbidden(_) => "forbidden",
+            ApiError::UnexpectedError(_) => "internal_error",
+        }
+    }
+}
+
+// Extractor rejections are rendered as JSON too, via `WithRejection`.
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl std::fmt::Debug for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let problem = Problem::new(self.status_code()).code(self.code());
+        let problem = match &self {
+            ApiError::UnexpectedError(_) => {
+                tracing::error!("{:?}", self);
+                problem
+            }
+            e => {
+                tracing::warn!("{:?}", e);
+                problem.detail(e.to_string())
+            }
+        };
+        let mut response = problem.into_response();
+        if let ApiError::Unauthorized(_) = self {
+            response
+                .headers_mut()
+                .insert(WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        response
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::response::IntoResponse;
+    use http::StatusCode;
+
+    use super::{Problem, ResponseError};
+    use crate::{e400, e500};
+
+    fn detail(error: ResponseError) -> Option<String> {
+        let response = error.into_response();
+        response
+            .extensions()
+            .get::<Problem>()
+            .unwrap()
+            .detail
+            .clone()
+    }
+
+    #[test]
+    fn internal_errors_are_never_shown_to_the_client() {
+        let cause = || anyhow::anyhow!("relation \"users\" does not exist");
+        assert_eq!(detail(e400(cause())), None);
+        assert_eq!(detail(e500(cause()).detail("Explained anyway?")), None);
+    }
+
+    #[test]
+    fn client_errors_are_explained_by_their_detail() {
+        let error = e400(anyhow::anyhow!("invalid digit found in string")).detail("Bad page.");
+        assert_eq!(detail(error), Some("Bad page.".into()));
+        let error = ResponseError::client_error(StatusCode::BAD_REQUEST, "Bad filter.");
+        assert_eq!(detail(error), Some("Bad filter.".into()));
+    }
+}
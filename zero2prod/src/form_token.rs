@@ -0,0 +1,234 @@
+use std::time::Duration;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use chrono::{DateTime, TimeZone, Utc};
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng};
+use secrecy::{ExposeSecret, Secret};
+use sha2::Sha256;
+use sqlx::{PgPool, Postgres, Transaction};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Issues and verifies signed, time-limited tokens that are embedded in public forms.
+///
+/// A token has the shape `<issued_at>.<nonce>.<signature>`, where the signature is an
+/// HMAC-SHA256 over `<issued_at>.<nonce>` keyed with the application's `hmac_secret`.
+#[derive(Clone)]
+pub struct FormTokenSigner {
+    secret: Secret<String>,
+    min_age: Duration,
+    max_age: Duration,
+}
+
+impl FormTokenSigner {
+    pub fn new(secret: Secret<String>, min_age: Duration, max_age: Duration) -> Self {
+        Self {
+            secret,
+            min_age,
+            max_age,
+        }
+    }
+
+    /// Issue a new token stamped with the current time.
+    pub fn issue(&self) -> String {
+        self.issue_at(Utc::now())
+    }
+
+    pub fn issue_at(&self, issued_at: DateTime<Utc>) -> String {
+        let nonce: String = thread_rng()
+            .sample_iter(Alphanumeric)
+            .map(char::from)
+            .take(24)
+            .collect();
+        let payload = format!("{}.{}", issued_at.timestamp(), nonce);
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(&payload).finalize().into_bytes());
+        format!("{}.{}", payload, signature)
+    }
+
+    /// Verify a token against the current time.
+    pub fn verify(&self, token: &str) -> Result<FormToken, FormTokenError> {
+        self.verify_at(token, Utc::now())
+    }
+
+    pub fn verify_at(&self, token: &str, now: DateTime<Utc>) -> Result<FormToken, FormTokenError> {
+        let (payload, signature) = token.rsplit_once('.').ok_or(FormTokenError::Malformed)?;
+        let (issued_at, nonce) = payload.split_once('.').ok_or(FormTokenError::Malformed)?;
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| FormTokenError::Malformed)?;
+        self.mac(payload)
+            .verify_slice(&signature)
+            .map_err(|_| FormTokenError::InvalidSignature)?;
+
+        let issued_at = issued_at
+            .parse::<i64>()
+            .ok()
+            .and_then(|t| Utc.timestamp_opt(t, 0).single())
+            .ok_or(FormTokenError::Malformed)?;
+        let age = (now - issued_at).to_std().unwrap_or_default();
+        if age < self.min_age {
+            return Err(FormTokenError::SubmittedTooFast);
+        }
+        if age > self.max_age {
+            return Err(FormTokenError::Expired);
+        }
+
+        Ok(FormToken {
+            nonce: nonce.to_string(),
+        })
+    }
+
+    pub fn max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    fn mac(&self, payload: &str) -> HmacSha256 {
+        let mut mac = HmacSha256::new_from_slice(self.secret.expose_secret().as_bytes())
+            .expect("HMAC can take a key of any size");
+        mac.update(payload.as_bytes());
+        mac
+    }
+}
+
+/// A form token whose signature and age have been verified.
+#[derive(Debug)]
+pub struct FormToken {
+    nonce: String,
+}
+
+impl FormToken {
+    /// Record the token as used, returning `false` if it had already been redeemed.
+    #[tracing::instrument(name = "Redeem form token", skip_all)]
+    pub async fn redeem(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, sqlx::Error> {
+        let inserted = sqlx::query!(
+            r#"
+            INSERT INTO form_token_redemptions (nonce, redeemed_at)
+            VALUES ($1, now())
+            ON CONFLICT DO NOTHING
+            "#,
+            self.nonce
+        )
+        .execute(&mut **transaction)
+        .await?
+        .rows_affected();
+        Ok(inserted > 0)
+    }
+}
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub enum FormTokenError {
+    #[error("The form token is malformed.")]
+    Malformed,
+    #[error("The form token signature is invalid.")]
+    InvalidSignature,
+    #[error("The form was submitted too quickly.")]
+    SubmittedTooFast,
+    #[error("The form token has expired.")]
+    Expired,
+}
+
+/// Remove redeemed nonces whose tokens can no longer be verified anyway.
+#[tracing::instrument(skip_all)]
+pub async fn remove_expired_form_token_redemptions(
+    pool: &PgPool,
+    max_age: Duration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        DELETE FROM form_token_redemptions
+        WHERE
+            redeemed_at < now() - make_interval(secs => $1)
+        "#,
+        max_age.as_secs_f64()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+    use claims::{assert_err_eq, assert_ok};
+    use secrecy::Secret;
+
+    use super::{FormTokenError, FormTokenSigner};
+
+    fn signer() -> FormTokenSigner {
+        FormTokenSigner::new(
+            Secret::new("super-secret-key".to_string()),
+            Duration::from_secs(3),
+            Duration::from_secs(60 * 60),
+        )
+    }
+
+    #[test]
+    fn a_token_submitted_within_the_window_is_accepted() {
+        let signer = signer();
+        let now = Utc::now();
+        let token = signer.issue_at(now - chrono::Duration::seconds(10));
+        assert_ok!(signer.verify_at(&token, now));
+    }
+
+    #[test]
+    fn a_token_submitted_too_quickly_is_rejected() {
+        let signer = signer();
+        let now = Utc::now();
+        let token = signer.issue_at(now);
+        assert_err_eq!(
+            signer.verify_at(&token, now),
+            FormTokenError::SubmittedTooFast
+        );
+    }
+
+    #[test]
+    fn an_expired_token_is_rejected() {
+        let signer = signer();
+        let now = Utc::now();
+        let token = signer.issue_at(now - chrono::Duration::hours(2));
+        assert_err_eq!(signer.verify_at(&token, now), FormTokenError::Expired);
+    }
+
+    #[test]
+    fn a_tampered_token_is_rejected() {
+        let signer = signer();
+        let now = Utc::now();
+        let token = signer.issue_at(now - chrono::Duration::seconds(10));
+        let (_, rest) = token.split_once('.').unwrap();
+        let tampered = format!("{}.{}", now.timestamp(), rest);
+        assert_err_eq!(
+            signer.verify_at(&tampered, now),
+            FormTokenError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn a_token_signed_with_another_secret_is_rejected() {
+        let now = Utc::now();
+        let other = FormTokenSigner::new(
+            Secret::new("another-key".to_string()),
+            Duration::from_secs(3),
+            Duration::from_secs(60 * 60),
+        );
+        let token = other.issue_at(now - chrono::Duration::seconds(10));
+        assert_err_eq!(
+            signer().verify_at(&token, now),
+            FormTokenError::InvalidSignature
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert_err_eq!(
+            signer().verify_at("not-a-token", Utc::now()),
+            FormTokenError::Malformed
+        );
+    }
+}
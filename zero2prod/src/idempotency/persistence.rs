@@ -7,6 +7,7 @@ use sqlx::{postgres::PgHasArrayType, PgPool, Postgres, Transaction};
 use uuid::Uuid;
 
 use super::IdempotencyKey;
+use crate::metrics::METRICS;
 
 #[tracing::instrument(name = "Getting cached newsletter response", skip(pool))]
 pub async fn get_saved_response(
@@ -123,12 +124,13 @@ pub async fn try_processing(
     .await?
     .rows_affected();
 
+    METRICS.record_idempotency_lookup(n_inserted_rows == 0);
     if n_inserted_rows > 0 {
         tracing::debug!(
             "Starting a new transaction for idempotency key {:?}",
             idempotency_key
         );
-        Ok(NextAction::StartProcessing(transaction))
+        Ok(NextAction::StartProcessing(Box::new(transaction)))
     } else {
         tracing::debug!(
             "Retrieving response for idempotency key {:?}",
@@ -142,7 +144,7 @@ pub async fn try_processing(
 }
 
 pub enum NextAction {
-    StartProcessing(Transaction<'static, Postgres>),
+    StartProcessing(Box<Transaction<'static, Postgres>>),
     ReturnSavedResponse(Response),
 }
 
@@ -2,19 +2,49 @@ use std::time::Duration;
 
 use sqlx::PgPool;
 
-use crate::{configuration::Settings, startup::get_db_pool};
+use crate::{
+    configuration::Settings, form_token::remove_expired_form_token_redemptions, shutdown::Shutdown,
+    startup::get_db_pool, worker_heartbeat::Heartbeat,
+};
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Beats while waiting for the next clean up.
+pub const HEARTBEAT: Heartbeat = Heartbeat {
+    worker_name: "idempotency_cleanup",
+    interval: Duration::from_secs(60 * 60),
+};
+
+/// Clean up daily until `shutdown` is triggered.
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    shutdown: Shutdown,
+) -> Result<(), anyhow::Error> {
     // Set up the worker
     let connection_pool = get_db_pool(&configuration.database);
-    worker_loop(connection_pool).await
+    let form_token_max_age =
+        Duration::from_secs(configuration.subscriptions.form_token_max_age_seconds);
+    worker_loop(connection_pool, form_token_max_age, shutdown).await
 }
 
-async fn worker_loop(pool: PgPool) -> Result<(), anyhow::Error> {
-    loop {
+async fn worker_loop(
+    pool: PgPool,
+    form_token_max_age: Duration,
+    shutdown: Shutdown,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_triggered() {
         remove_old_idempotency_entries(&pool).await?;
-        tokio::time::sleep(Duration::from_secs(60 * 60 * 24)).await;
+        remove_expired_form_token_redemptions(&pool, form_token_max_age).await?;
+        for _ in 0..CLEANUP_INTERVAL.as_secs() / HEARTBEAT.interval.as_secs() {
+            if let Err(e) = HEARTBEAT.record(&pool).await {
+                tracing::warn!(error.cause_chain = ?e, error.message = %e, "Failed to record heartbeat.");
+            }
+            if !shutdown.sleep(HEARTBEAT.interval).await {
+                break;
+            }
+        }
     }
+    Ok(())
 }
 
 #[tracing::instrument(skip_all)]
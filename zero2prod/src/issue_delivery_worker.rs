@@ -1,18 +1,31 @@
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use anyhow::Context;
 
 use crate::{
-    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient,
-    startup::get_db_pool,
+    configuration::Settings, domain::SubscriberEmail, email_client::EmailClient, metrics::METRICS,
+    shutdown::Shutdown, startup::get_db_pool, telemetry::set_remote_parent,
+    worker_heartbeat::Heartbeat,
 };
 use sqlx::{PgPool, Postgres, Transaction};
-use tracing::{field::display, Span};
+use tracing::Instrument;
 use uuid::Uuid;
 
-pub async fn run_worker_until_stopped(configuration: Settings) -> Result<(), anyhow::Error> {
+/// Beats at least as often as an empty queue is polled.
+pub const HEARTBEAT: Heartbeat = Heartbeat {
+    worker_name: "issue_delivery",
+    interval: Duration::from_secs(10),
+};
+
+/// Deliver queued issues until `shutdown` is triggered, finishing the delivery in progress.
+pub async fn run_worker_until_stopped(
+    configuration: Settings,
+    shutdown: Shutdown,
+) -> Result<(), anyhow::Error> {
     // Set up the worker
     let connection_pool = get_db_pool(&configuration.database);
     let email_client = configuration.email_client.client();
-    worker_loop(connection_pool, email_client).await
+    worker_loop(connection_pool, email_client, shutdown).await
 }
 
 pub enum ExecutionOutcome {
@@ -21,40 +34,60 @@ pub enum ExecutionOutcome {
     EmptyQueue,
 }
 
-async fn worker_loop(pool: PgPool, email_client: EmailClient) -> Result<(), anyhow::Error> {
-    loop {
+async fn worker_loop(
+    pool: PgPool,
+    email_client: EmailClient,
+    shutdown: Shutdown,
+) -> Result<(), anyhow::Error> {
+    let mut last_beat: Option<Instant> = None;
+    while !shutdown.is_triggered() {
+        if last_beat.is_none_or(|at| at.elapsed() >= HEARTBEAT.interval) {
+            if let Err(e) = HEARTBEAT.record(&pool).await {
+                tracing::warn!(error.cause_chain = ?e, error.message = %e, "Failed to record heartbeat.");
+            }
+            last_beat = Some(Instant::now());
+        }
         match try_execute_task(&pool, &email_client).await {
             Ok(ExecutionOutcome::EmptyQueue) => {
-                tokio::time::sleep(Duration::from_secs(10)).await;
+                shutdown.sleep(Duration::from_secs(10)).await;
             }
             Ok(ExecutionOutcome::TaskQueuedForRetry) | Ok(ExecutionOutcome::TaskCompleted) => {}
             Err(_) => {
-                tokio::time::sleep(Duration::from_secs(1)).await;
+                shutdown.sleep(Duration::from_secs(1)).await;
             }
         }
     }
+    Ok(())
 }
 
-#[tracing::instrument(
-    skip_all,
-    fields(
-        newsletter_issue_id=tracing::field::Empty,
-        subscriber_email=tracing::field::Empty,
-     ),
-     err
-)]
+#[tracing::instrument(skip_all, err)]
 pub async fn try_execute_task(
     pool: &PgPool,
     email_client: &EmailClient,
 ) -> Result<ExecutionOutcome, anyhow::Error> {
-    let task = dequeue_task(pool).await?;
-    if task.is_none() {
+    let Some(task) = dequeue_task(pool).await? else {
         return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    // A fresh span, so the delivery is traced as part of the request that published the
+    // issue. Polling the queue stays in a trace of its own.
+    let span = tracing::info_span!(
+        "Deliver issue",
+        newsletter_issue_id = %task.issue_id,
+        subscriber_email = %task.email,
+    );
+    if let Some(traceparent) = &task.trace_context {
+        set_remote_parent(&span, traceparent);
     }
-    let task = task.unwrap();
-    Span::current()
-        .record("newsletter_issue_id", &display(task.issue_id))
-        .record("subscriber_email", &display(&task.email));
+    deliver_task(task, pool, email_client)
+        .instrument(span)
+        .await
+}
+
+async fn deliver_task(
+    task: EmailTask,
+    pool: &PgPool,
+    email_client: &EmailClient,
+) -> Result<ExecutionOutcome, anyhow::Error> {
     if task.retries < 100 {
         match SubscriberEmail::parse(task.email.clone()) {
             Ok(email) => {
@@ -75,6 +108,8 @@ pub async fn try_execute_task(
                     );
                     return queue_retry_task(task).await;
                 }
+                delete_task(task, DeliveryOutcome::Delivered).await?;
+                return Ok(ExecutionOutcome::TaskCompleted);
             }
             Err(e) => {
                 tracing::error!(
@@ -93,7 +128,7 @@ pub async fn try_execute_task(
             task.email
         );
     }
-    delete_task(task).await?;
+    delete_task(task, DeliveryOutcome::Failed).await?;
     Ok(ExecutionOutcome::TaskCompleted)
 }
 
@@ -103,7 +138,7 @@ async fn dequeue_task(pool: &PgPool) -> Result<Option<EmailTask>, anyhow::Error>
 
     let r = sqlx::query!(
         r#"
-        SELECT newsletter_issue_id, subscriber_email, retries
+        SELECT newsletter_issue_id, subscriber_email, retries, trace_context
         FROM issue_delivery_queue
         WHERE
             retry_after IS NULL OR now() > retry_after
@@ -121,6 +156,7 @@ async fn dequeue_task(pool: &PgPool) -> Result<Option<EmailTask>, anyhow::Error>
             issue_id: r.newsletter_issue_id,
             email: r.subscriber_email,
             retries: r.retries,
+            trace_context: r.trace_context,
         }))
     } else {
         Ok(None)
@@ -144,8 +180,11 @@ async fn get_issue(pool: &PgPool, issue_id: Uuid) -> Result<NewsletterIssue, any
     Ok(issue)
 }
 
+/// Remove a finished task from the queue, counting it towards the issue's delivery stats.
+///
+/// Failed tasks are kept as dead letters, see [`requeue_dead_letters`].
 #[tracing::instrument(skip_all)]
-async fn delete_task(mut task: EmailTask) -> Result<(), anyhow::Error> {
+async fn delete_task(mut task: EmailTask, outcome: DeliveryOutcome) -> Result<(), anyhow::Error> {
     sqlx::query!(
         r#"
         DELETE FROM issue_delivery_queue
@@ -158,7 +197,42 @@ async fn delete_task(mut task: EmailTask) -> Result<(), anyhow::Error> {
     )
     .execute(&mut *task.transaction)
     .await?;
+    let delivered = matches!(outcome, DeliveryOutcome::Delivered);
+    if !delivered {
+        sqlx::query!(
+            r#"
+            INSERT INTO issue_delivery_dead_letters (
+                newsletter_issue_id,
+                subscriber_email,
+                retries,
+                trace_context,
+                failed_at
+            )
+            VALUES ($1, $2, $3, $4, now())
+            "#,
+            task.issue_id,
+            task.email,
+            task.retries,
+            task.trace_context,
+        )
+        .execute(&mut *task.transaction)
+        .await?;
+    }
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET
+            delivered_count = delivered_count + CASE WHEN $2 THEN 1 ELSE 0 END,
+            failed_count = failed_count + CASE WHEN $2 THEN 0 ELSE 1 END
+        WHERE newsletter_issue_id = $1
+        "#,
+        task.issue_id,
+        delivered
+    )
+    .execute(&mut *task.transaction)
+    .await?;
     task.transaction.commit().await?;
+    METRICS.record_delivery(if delivered { "delivered" } else { "failed" });
     Ok(())
 }
 
@@ -180,15 +254,126 @@ async fn queue_retry_task(mut task: EmailTask) -> Result<ExecutionOutcome, anyho
     .execute(&mut *task.transaction)
     .await?;
     task.transaction.commit().await?;
+    METRICS.record_delivery("retried");
     Ok(ExecutionOutcome::TaskQueuedForRetry)
 }
 
+enum DeliveryOutcome {
+    Delivered,
+    Failed,
+}
+
+/// How many deliveries of an issue are left, as shown to operators.
+#[derive(Debug)]
+pub struct QueueDepth {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    /// Deliveries waiting for their first attempt.
+    pub pending: i64,
+    /// Deliveries that failed at least once and will be attempted again.
+    pub retrying: i64,
+    /// Deliveries that failed for good.
+    pub dead_letters: i64,
+}
+
+/// The issues with deliveries waiting in the queue or dead-lettered, oldest first.
+#[tracing::instrument(name = "Get queue depths", skip(pool))]
+pub async fn get_queue_depths(pool: &PgPool) -> Result<Vec<QueueDepth>, anyhow::Error> {
+    let depths = sqlx::query_as!(
+        QueueDepth,
+        r#"
+        SELECT
+            newsletter_issues.newsletter_issue_id,
+            newsletter_issues.title,
+            count(issue_delivery_queue.subscriber_email)
+                FILTER (WHERE issue_delivery_queue.retries = 0) AS "pending!",
+            count(issue_delivery_queue.subscriber_email)
+                FILTER (WHERE issue_delivery_queue.retries > 0) AS "retrying!",
+            (
+                SELECT count(*)
+                FROM issue_delivery_dead_letters
+                WHERE issue_delivery_dead_letters.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            ) AS "dead_letters!"
+        FROM newsletter_issues
+        LEFT JOIN issue_delivery_queue
+            ON issue_delivery_queue.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+        GROUP BY newsletter_issues.newsletter_issue_id
+        HAVING
+            count(issue_delivery_queue.subscriber_email) > 0 OR
+            EXISTS (
+                SELECT 1
+                FROM issue_delivery_dead_letters
+                WHERE issue_delivery_dead_letters.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            )
+        ORDER BY newsletter_issues.published_at
+        "#
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to retrieve queue depths.")?;
+    Ok(depths)
+}
+
+/// Queue dead-lettered deliveries again, of one issue or of every issue, returning how many.
+///
+/// They start over with no retries, and no longer count as failed in the issue's stats.
+#[tracing::instrument(name = "Requeue dead letters", skip(pool))]
+pub async fn requeue_dead_letters(
+    newsletter_issue_id: Option<Uuid>,
+    pool: &PgPool,
+) -> Result<u64, anyhow::Error> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let requeued = sqlx::query!(
+        r#"
+        WITH requeued AS (
+            DELETE FROM issue_delivery_dead_letters
+            WHERE $1::uuid IS NULL OR newsletter_issue_id = $1
+            RETURNING newsletter_issue_id, subscriber_email, trace_context
+        ), queued AS (
+            INSERT INTO issue_delivery_queue (
+                newsletter_issue_id,
+                subscriber_email,
+                trace_context
+            )
+            SELECT newsletter_issue_id, subscriber_email, trace_context
+            FROM requeued
+            RETURNING newsletter_issue_id
+        )
+        UPDATE newsletter_issues
+        SET failed_count = failed_count - counts.requeued
+        FROM (
+            SELECT newsletter_issue_id, count(*)::integer AS requeued
+            FROM queued
+            GROUP BY newsletter_issue_id
+        ) AS counts
+        WHERE newsletter_issues.newsletter_issue_id = counts.newsletter_issue_id
+        RETURNING counts.requeued AS "requeued!"
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_all(&mut *transaction)
+    .await
+    .context("Failed to move dead letters back into the queue.")?
+    .into_iter()
+    .map(|r| r.requeued as u64)
+    .sum();
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to requeue dead letters.")?;
+    Ok(requeued)
+}
+
 type PgTransaction = Transaction<'static, Postgres>;
 struct EmailTask {
     transaction: PgTransaction,
     issue_id: Uuid,
     email: String,
     retries: i32,
+    trace_context: Option<String>,
 }
 
 struct NewsletterIssue {
@@ -1,18 +1,32 @@
 use error::ResponseError;
 use http::StatusCode;
 
+pub mod audit;
 pub mod authentication;
+pub mod cli;
+pub mod client_ip;
 pub mod configuration;
+pub mod csrf;
+pub mod csv;
 pub mod domain;
 pub mod email_client;
 pub mod error;
+pub mod form_token;
 pub mod idempotency;
 pub mod idempotency_remover_worker;
 pub mod issue_delivery_worker;
+pub mod metrics;
+pub mod newsletter_issues;
+pub mod rate_limit;
+pub mod request_metadata;
 pub mod routes;
+pub mod security_headers;
 pub mod session_state;
+pub mod shutdown;
 pub mod startup;
+pub mod subscribers;
 pub mod telemetry;
+pub mod worker_heartbeat;
 
 pub fn error_chain_fmt(
     e: &impl std::error::Error,
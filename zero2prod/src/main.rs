@@ -1,38 +1,143 @@
 use std::fmt::{Debug, Display};
 
-use tokio::task::JoinError;
+use tokio::task::{JoinError, JoinHandle, JoinSet};
 use zero2prod::{
-    configuration::get_configuration, idempotency_remover_worker, issue_delivery_worker,
-    startup::Application, telemetry,
+    cli::{Command, Worker, USAGE},
+    configuration::get_configuration,
+    idempotency_remover_worker, issue_delivery_worker,
+    shutdown::{shutdown_signal, Shutdown},
+    startup::{get_db_pool, Application, MetricsApplication},
+    telemetry,
 };
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Set up tracing
-    let subscriber = telemetry::get_subscriber("zero2prod".into(), "info".into(), std::io::stdout);
-    telemetry::init_subscriber(subscriber);
+    let command = match Command::parse(std::env::args().skip(1)) {
+        Ok(Command::Help) => {
+            eprintln!("{}", USAGE);
+            return Ok(());
+        }
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("{}\n{}", e, USAGE);
+            std::process::exit(2);
+        }
+    };
 
     // Set up configuration
     let configuration = get_configuration().expect("failed to read configuration");
 
-    let app = Application::build(configuration.clone()).await?;
-    let app_task = tokio::spawn(app.run_until_stopped());
-    let email_delivery_worker_task = tokio::spawn(issue_delivery_worker::run_worker_until_stopped(
-        configuration.clone(),
-    ));
-    let idempotency_cleaner_task = tokio::spawn(
-        idempotency_remover_worker::run_worker_until_stopped(configuration),
+    if let Command::Operation(operation) = command {
+        // Log to stderr, leaving stdout to what the operation reports
+        let (subscriber, _) = telemetry::get_subscriber(
+            configuration.opentelemetry.service_name.clone(),
+            configuration.logging.filter.clone(),
+            configuration.logging.format,
+            std::io::stderr,
+            None,
+        );
+        telemetry::init_subscriber(subscriber);
+        operation.run(configuration).await?;
+        return Ok(());
+    }
+
+    // Set up tracing
+    let tracer_provider = telemetry::get_tracer_provider(&configuration.opentelemetry)?;
+    let (subscriber, log_filter) = telemetry::get_subscriber(
+        configuration.opentelemetry.service_name.clone(),
+        configuration.logging.filter.clone(),
+        configuration.logging.format,
+        std::io::stdout,
+        tracer_provider.as_ref(),
     );
+    telemetry::init_subscriber(subscriber);
 
+    // Stop on Ctrl+C or SIGTERM, e.g. when a deploy replaces this instance
+    let shutdown = configuration.application.shutdown();
+    tokio::spawn({
+        let shutdown = shutdown.clone();
+        async move {
+            shutdown_signal().await;
+            shutdown.trigger();
+        }
+    });
+
+    // Only start what the command asks for, so each part can be scaled on its own
+    let mut tasks = JoinSet::new();
+    if command.runs_server() {
+        let app = Application::build(configuration.clone(), log_filter).await?;
+        let app_task = tokio::spawn(app.run_until_stopped(shutdown.clone()));
+        tasks.spawn(stop_with("API", app_task, shutdown.clone()));
+    }
+    if !command.runs_server() {
+        // The server serves the metrics of its own process only
+        match MetricsApplication::build(&configuration, get_db_pool(&configuration.database))
+            .await?
+        {
+            Some(metrics) => {
+                let metrics_task = tokio::spawn(metrics.run_until_stopped(shutdown.clone()));
+                tasks.spawn(stop_with("Metrics", metrics_task, shutdown.clone()));
+            }
+            None => tracing::warn!(
+                "No metrics port is configured, so the metrics of this worker are not served."
+            ),
+        }
+    }
+    if command.runs_worker(Worker::Delivery) {
+        let email_delivery_worker_task =
+            tokio::spawn(issue_delivery_worker::run_worker_until_stopped(
+                configuration.clone(),
+                shutdown.clone(),
+            ));
+        tasks.spawn(stop_with(
+            "Email Delivery Worker",
+            email_delivery_worker_task,
+            shutdown.clone(),
+        ));
+    }
+    if command.runs_worker(Worker::IdempotencyCleanup) {
+        let idempotency_cleaner_task = tokio::spawn(
+            idempotency_remover_worker::run_worker_until_stopped(configuration, shutdown.clone()),
+        );
+        tasks.spawn(stop_with(
+            "Idempotency Cleaner Worker",
+            idempotency_cleaner_task,
+            shutdown.clone(),
+        ));
+    }
+
+    let stopped = async { while tasks.join_next().await.is_some() {} };
+    tokio::pin!(stopped);
+    // Once asked to stop, or once any task stopped on its own, give the rest time to drain
     tokio::select! {
-        o = app_task => report_exit("API", o),
-        o = email_delivery_worker_task => report_exit("Email Delivery Worker", o),
-        o = idempotency_cleaner_task => report_exit("Idempotency Cleaner Worker", o)
-    };
+        _ = &mut stopped => {}
+        _ = shutdown.clone().triggered() => {
+            if tokio::time::timeout(shutdown.drain_timeout(), &mut stopped).await.is_err() {
+                tracing::warn!(
+                    "Not everything stopped within {:?}, exiting anyway.",
+                    shutdown.drain_timeout()
+                );
+            }
+        }
+    }
+
+    // Flush the spans that have not been exported yet
+    if let Some(tracer_provider) = tracer_provider {
+        tracer_provider.shutdown()?;
+    }
 
     Ok(())
 }
 
+/// Wait for `task` to end, then tell the other tasks to stop too.
+async fn stop_with<E>(task_name: &'static str, task: JoinHandle<Result<(), E>>, shutdown: Shutdown)
+where
+    E: Debug + Display,
+{
+    report_exit(task_name, task.await);
+    shutdown.trigger();
+}
+
 fn report_exit(task_name: &str, outcome: Result<Result<(), impl Debug + Display>, JoinError>) {
     match outcome {
         Ok(Ok(())) => {
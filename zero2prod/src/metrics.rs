@@ -0,0 +1,170 @@
+use std::{sync::LazyLock, time::Instant};
+
+use anyhow::Context;
+use axum::{
+    body::Body,
+    extract::{MatchedPath, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::CONTENT_TYPE, Request};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use sqlx::PgPool;
+
+use crate::{e500, error::ResponseError};
+
+/// The metrics of this process, exposed in the Prometheus text format.
+pub struct Metrics {
+    registry: Registry,
+    http_requests: IntCounterVec,
+    http_request_duration: HistogramVec,
+    db_pool_connections: IntGaugeVec,
+    issue_delivery_queue_depth: IntGauge,
+    issue_deliveries: IntCounterVec,
+    email_send_duration: HistogramVec,
+    idempotency_lookups: IntCounterVec,
+}
+
+pub static METRICS: LazyLock<Metrics> =
+    LazyLock::new(|| Metrics::new().expect("Metric definitions are valid"));
+
+impl Metrics {
+    fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("zero2prod".into()), None)?;
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled."),
+            &["method", "route", "status"],
+        )?;
+        let http_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "Time taken to handle HTTP requests.",
+            ),
+            &["method", "route"],
+        )?;
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Connections of the database pool."),
+            &["state"],
+        )?;
+        let issue_delivery_queue_depth = IntGauge::new(
+            "issue_delivery_queue_depth",
+            "Newsletter issue deliveries waiting in the queue, including retries.",
+        )?;
+        let issue_deliveries = IntCounterVec::new(
+            Opts::new(
+                "issue_deliveries_total",
+                "Attempts to deliver a newsletter issue to a subscriber.",
+            ),
+            &["outcome"],
+        )?;
+        let email_send_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "email_send_duration_seconds",
+                "Time taken by the email provider to accept an email.",
+            ),
+            &["outcome"],
+        )?;
+        let idempotency_lookups = IntCounterVec::new(
+            Opts::new(
+                "idempotency_lookups_total",
+                "Idempotency keys looked up, by whether a saved response was found.",
+            ),
+            &["outcome"],
+        )?;
+
+        registry.register(Box::new(http_requests.clone()))?;
+        registry.register(Box::new(http_request_duration.clone()))?;
+        registry.register(Box::new(db_pool_connections.clone()))?;
+        registry.register(Box::new(issue_delivery_queue_depth.clone()))?;
+        registry.register(Box::new(issue_deliveries.clone()))?;
+        registry.register(Box::new(email_send_duration.clone()))?;
+        registry.register(Box::new(idempotency_lookups.clone()))?;
+
+        Ok(Self {
+            registry,
+            http_requests,
+            http_request_duration,
+            db_pool_connections,
+            issue_delivery_queue_depth,
+            issue_deliveries,
+            email_send_duration,
+            idempotency_lookups,
+        })
+    }
+
+    /// `outcome` is `delivered`, `retried` or `failed`.
+    pub fn record_delivery(&self, outcome: &str) {
+        self.issue_deliveries.with_label_values(&[outcome]).inc();
+    }
+
+    pub fn observe_email_send(&self, succeeded: bool, started: Instant) {
+        let outcome = if succeeded { "success" } else { "error" };
+        self.email_send_duration
+            .with_label_values(&[outcome])
+            .observe(started.elapsed().as_secs_f64());
+    }
+
+    pub fn record_idempotency_lookup(&self, hit: bool) {
+        let outcome = if hit { "hit" } else { "miss" };
+        self.idempotency_lookups.with_label_values(&[outcome]).inc();
+    }
+
+    /// Sample the gauges that are read from the database rather than counted as things happen.
+    async fn sample_gauges(&self, pool: &PgPool) -> Result<(), anyhow::Error> {
+        let idle = pool.num_idle() as i64;
+        self.db_pool_connections
+            .with_label_values(&["idle"])
+            .set(idle);
+        self.db_pool_connections
+            .with_label_values(&["active"])
+            .set(pool.size() as i64 - idle);
+        self.db_pool_connections
+            .with_label_values(&["max"])
+            .set(pool.options().get_max_connections() as i64);
+
+        let depth = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM issue_delivery_queue"#)
+            .fetch_one(pool)
+            .await
+            .context("Failed to count the queued deliveries.")?;
+        self.issue_delivery_queue_depth.set(depth);
+        Ok(())
+    }
+}
+
+/// Count and time every request, by the route it matched rather than its path, so ids in
+/// paths do not turn into a label value each.
+pub async fn track_http_requests(request: Request<Body>, next: Next) -> Response {
+    let method = request.method().to_string();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_else(|| "unmatched".into());
+    let started = Instant::now();
+
+    let response = next.run(request).await;
+
+    METRICS
+        .http_request_duration
+        .with_label_values(&[&method, &route])
+        .observe(started.elapsed().as_secs_f64());
+    METRICS
+        .http_requests
+        .with_label_values(&[&method, &route, response.status().as_str()])
+        .inc();
+    response
+}
+
+#[tracing::instrument(name = "Metrics", skip(pool))]
+pub async fn metrics_endpoint(State(pool): State<PgPool>) -> Result<Response, ResponseError> {
+    METRICS.sample_gauges(&pool).await.map_err(e500)?;
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    encoder
+        .encode(&METRICS.registry.gather(), &mut body)
+        .map_err(e500)?;
+    Ok(([(CONTENT_TYPE, encoder.format_type().to_owned())], body).into_response())
+}
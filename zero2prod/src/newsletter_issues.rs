@@ -0,0 +1,192 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::{PgPool, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::telemetry::current_traceparent;
+
+/// A newsletter issue, published or still a draft.
+#[derive(Debug)]
+pub struct NewsletterIssue {
+    pub newsletter_issue_id: Uuid,
+    pub title: String,
+    pub text_content: String,
+    pub html_content: String,
+    pub created_at: DateTime<Utc>,
+    pub published_at: Option<DateTime<Utc>>,
+}
+
+/// How far the delivery of a published issue has come.
+#[derive(Debug)]
+///
+/// The counts are `None` for issues published before they were kept.
+pub struct DeliveryStats {
+    /// Confirmed subscribers at the time the issue was published.
+    pub recipients: Option<i32>,
+    pub delivered: Option<i32>,
+    /// Given up on after too many retries, or skipped because of invalid contact details.
+    pub failed: Option<i32>,
+    /// Still in the delivery queue, including those waiting for a retry.
+    pub pending: i64,
+}
+
+/// Store a new issue as a draft, returning its id.
+#[tracing::instrument(skip_all)]
+pub async fn insert_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let newsletter_issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id,
+            title,
+            text_content,
+            html_content,
+            created_at
+        )
+        VALUES ($1, $2, $3, $4, now())
+        "#,
+        newsletter_issue_id,
+        title,
+        text_content,
+        html_content
+    )
+    .execute(&mut **transaction)
+    .await?;
+
+    Ok(newsletter_issue_id)
+}
+
+/// Publish a draft, queueing a delivery to every confirmed subscriber.
+///
+/// The deliveries carry the current trace, so they are traced as part of the publication.
+///
+/// Returns `false` if there is no such draft, e.g. because it has already been published.
+#[tracing::instrument(skip(transaction))]
+pub async fn publish_newsletter_issue(
+    transaction: &mut Transaction<'_, Postgres>,
+    newsletter_issue_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let published = sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET published_at = now()
+        WHERE newsletter_issue_id = $1 AND published_at IS NULL
+        "#,
+        newsletter_issue_id
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected()
+        > 0;
+    if !published {
+        return Ok(false);
+    }
+
+    let recipients = sqlx::query!(
+        r#"
+        INSERT INTO issue_delivery_queue (
+            newsletter_issue_id,
+            subscriber_email,
+            trace_context
+        )
+        SELECT $1, email, $2
+        FROM subscriptions
+        WHERE status = 'confirmed'
+        "#,
+        newsletter_issue_id,
+        current_traceparent()
+    )
+    .execute(&mut **transaction)
+    .await?
+    .rows_affected();
+    sqlx::query!(
+        r#"
+        UPDATE newsletter_issues
+        SET recipient_count = $2, delivered_count = 0, failed_count = 0
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id,
+        recipients as i32
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(true)
+}
+
+/// Issues, most recently created first.
+#[tracing::instrument(name = "List newsletter issues", skip(pool))]
+pub async fn list_newsletter_issues(
+    limit: i64,
+    offset: i64,
+    pool: &PgPool,
+) -> Result<Vec<NewsletterIssue>, anyhow::Error> {
+    let issues = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, created_at, published_at
+        FROM newsletter_issues
+        ORDER BY created_at DESC, newsletter_issue_id
+        LIMIT $1 OFFSET $2
+        "#,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list newsletter issues.")?;
+    Ok(issues)
+}
+
+#[tracing::instrument(name = "Get newsletter issue", skip(pool))]
+pub async fn get_newsletter_issue(
+    newsletter_issue_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<NewsletterIssue>, anyhow::Error> {
+    let issue = sqlx::query_as!(
+        NewsletterIssue,
+        r#"
+        SELECT newsletter_issue_id, title, text_content, html_content, created_at, published_at
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve a newsletter issue.")?;
+    Ok(issue)
+}
+
+/// The delivery stats of an issue, or `None` if there is no such issue.
+#[tracing::instrument(name = "Get delivery stats", skip(pool))]
+pub async fn get_delivery_stats(
+    newsletter_issue_id: Uuid,
+    pool: &PgPool,
+) -> Result<Option<DeliveryStats>, anyhow::Error> {
+    let stats = sqlx::query_as!(
+        DeliveryStats,
+        r#"
+        SELECT
+            recipient_count AS recipients,
+            delivered_count AS delivered,
+            failed_count AS failed,
+            (
+                SELECT count(*)
+                FROM issue_delivery_queue
+                WHERE issue_delivery_queue.newsletter_issue_id = newsletter_issues.newsletter_issue_id
+            ) AS "pending!"
+        FROM newsletter_issues
+        WHERE newsletter_issue_id = $1
+        "#,
+        newsletter_issue_id
+    )
+    .fetch_optional(pool)
+    .await
+    .context("Failed to perform a query to retrieve delivery stats.")?;
+    Ok(stats)
+}
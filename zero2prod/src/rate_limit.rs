@@ -0,0 +1,224 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use axum::{
+    body::{to_bytes, Body},
+    extract::State,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::{header::RETRY_AFTER, Request, StatusCode};
+use redis_pool::SingleRedisPool;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use crate::{
+    client_ip::ClientIp,
+    configuration::{RateLimitPolicy, RateLimitSettings},
+    error::Problem,
+};
+
+/// The largest form body the limiters will buffer to find the email address.
+const MAX_FORM_BODY_BYTES: usize = 64 * 1024;
+
+/// Fixed-window request counters stored in Redis, so limits hold across instances.
+#[derive(Clone)]
+pub struct RateLimiter {
+    redis_pool: SingleRedisPool,
+    settings: RateLimitSettings,
+}
+
+pub enum RateLimitDecision {
+    Allowed,
+    Limited { retry_after: Duration },
+}
+
+impl RateLimiter {
+    pub fn new(redis_pool: SingleRedisPool, settings: RateLimitSettings) -> Self {
+        Self {
+            redis_pool,
+            settings,
+        }
+    }
+
+    /// Count a request by `subject` against `policy`.
+    #[tracing::instrument(name = "Check rate limit", skip(self, policy))]
+    pub async fn check(
+        &self,
+        policy_name: &str,
+        policy: &RateLimitPolicy,
+        subject: &str,
+    ) -> Result<RateLimitDecision, anyhow::Error> {
+        let key = format!("{}:{}:{}", self.settings.key_prefix, policy_name, subject);
+        let mut connection = self
+            .redis_pool
+            .aquire()
+            .await
+            .context("Failed to acquire a Redis connection from the pool.")?;
+
+        // Start a new window if there is none, then count this request in it.
+        let (count, ttl): (u64, i64) = redis::pipe()
+            .atomic()
+            .cmd("SET")
+            .arg(&key)
+            .arg(0)
+            .arg("EX")
+            .arg(policy.window_seconds)
+            .arg("NX")
+            .ignore()
+            .cmd("INCR")
+            .arg(&key)
+            .cmd("TTL")
+            .arg(&key)
+            .query_async(&mut *connection)
+            .await
+            .context("Failed to update the rate limit counter.")?;
+
+        if count > policy.max_requests {
+            let retry_after = Duration::from_secs(ttl.max(1) as u64);
+            Ok(RateLimitDecision::Limited { retry_after })
+        } else {
+            Ok(RateLimitDecision::Allowed)
+        }
+    }
+
+    /// Check the `(policy, subject)` pairs in order, stopping at the first one exceeded.
+    ///
+    /// A rejected request is not counted against the later subjects, so whoever is over
+    /// the per-IP limit cannot use up somebody else's per-email allowance.
+    ///
+    /// Redis being unavailable must not take the site down with it, so errors are
+    /// logged and the request is let through.
+    async fn enforce(&self, checks: &[(&str, &RateLimitPolicy, String)]) -> Option<Response> {
+        for (policy_name, policy, subject) in checks {
+            match self.check(policy_name, policy, subject).await {
+                Ok(RateLimitDecision::Allowed) => {}
+                Ok(RateLimitDecision::Limited { retry_after }) => {
+                    tracing::warn!("Rate limit '{}' exceeded", policy_name);
+                    return Some(too_many_requests(retry_after));
+                }
+                Err(e) => {
+                    tracing::error!(
+                        error.cause_chain = ?e,
+                        error.message = %e,
+                        "Failed to check rate limit '{}'. Letting the request through.",
+                        policy_name
+                    );
+                }
+            }
+        }
+        None
+    }
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    (
+        [(RETRY_AFTER, retry_after.as_secs().to_string())],
+        Problem::new(StatusCode::TOO_MANY_REQUESTS)
+            .detail("Too many requests. Please try again later."),
+    )
+        .into_response()
+}
+
+/// Hash subjects that contain personal data so they do not end up in Redis in the clear.
+pub(crate) fn hashed(subject: &str) -> String {
+    format!("{:x}", Sha256::digest(subject.as_bytes()))
+}
+
+/// Limit login attempts per client IP.
+pub async fn limit_login_attempts(
+    State(rate_limiter): State<RateLimiter>,
+    client_ip: ClientIp,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let settings = &rate_limiter.settings;
+    let checks = [("login_ip", &settings.login_per_ip, client_ip.to_string())];
+    if let Some(response) = rate_limiter.enforce(&checks).await {
+        return response;
+    }
+    next.run(request).await
+}
+
+#[derive(Deserialize)]
+struct FormEmail {
+    email: String,
+}
+
+/// Buffer a form body to find the normalised email address it targets, then rebuild the
+/// request so the handler gets the body untouched.
+async fn form_email(request: Request<Body>) -> Result<(Request<Body>, Option<String>), Response> {
+    let (parts, body) = request.into_parts();
+    let bytes = match to_bytes(body, MAX_FORM_BODY_BYTES).await {
+        Ok(bytes) => bytes,
+        Err(_) => return Err(Problem::new(StatusCode::PAYLOAD_TOO_LARGE).into_response()),
+    };
+    let email = serde_urlencoded::from_bytes::<FormEmail>(&bytes)
+        .ok()
+        .map(|form| form.email.trim().to_lowercase())
+        .filter(|email| !email.is_empty());
+    Ok((Request::from_parts(parts, Body::from(bytes)), email))
+}
+
+/// Limit subscription attempts per client IP and per target email address.
+pub async fn limit_subscriptions(
+    State(rate_limiter): State<RateLimiter>,
+    client_ip: ClientIp,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let (request, email) = match form_email(request).await {
+        Ok(buffered) => buffered,
+        Err(response) => return response,
+    };
+
+    let settings = &rate_limiter.settings;
+    let mut checks = vec![(
+        "subscriptions_ip",
+        &settings.subscriptions_per_ip,
+        client_ip.to_string(),
+    )];
+    if let Some(email) = email {
+        checks.push((
+            "subscriptions_email",
+            &settings.subscriptions_per_email,
+            hashed(&email),
+        ));
+    }
+    if let Some(response) = rate_limiter.enforce(&checks).await {
+        return response;
+    }
+    next.run(request).await
+}
+
+/// Limit password reset requests per client IP and per email address, so nobody can
+/// flood an inbox with reset links by switching addresses.
+pub async fn limit_password_reset_requests(
+    State(rate_limiter): State<RateLimiter>,
+    client_ip: ClientIp,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let (request, email) = match form_email(request).await {
+        Ok(buffered) => buffered,
+        Err(response) => return response,
+    };
+
+    let settings = &rate_limiter.settings;
+    let mut checks = vec![(
+        "password_reset_ip",
+        &settings.password_reset_per_ip,
+        client_ip.to_string(),
+    )];
+    if let Some(email) = email {
+        checks.push((
+            "password_reset_email",
+            &settings.password_reset_per_email,
+            hashed(&email),
+        ));
+    }
+    if let Some(response) = rate_limiter.enforce(&checks).await {
+        return response;
+    }
+    next.run(request).await
+}
@@ -0,0 +1,43 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+};
+use http::{header::USER_AGENT, request::Parts};
+
+use crate::client_ip::{ClientIp, TrustForwardedFor};
+
+/// Who sent a request and how to find it in the logs.
+///
+/// Used to describe where a login came from and to attribute audit events.
+#[derive(Clone, Debug)]
+pub struct RequestMetadata {
+    pub ip: ClientIp,
+    pub user_agent: Option<String>,
+    /// The `x-request-id` set by the tracing layer, which also appears in every log line.
+    pub request_id: Option<String>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestMetadata
+where
+    TrustForwardedFor: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = <ClientIp as FromRequestParts<S>>::Rejection;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let ip = ClientIp::from_request_parts(parts, state).await?;
+        let header = |name| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_owned)
+        };
+        Ok(Self {
+            ip,
+            user_agent: header(USER_AGENT),
+            request_id: header(http::HeaderName::from_static("x-request-id")),
+        })
+    }
+}
@@ -1,13 +1,28 @@
+pub mod api;
+
 mod admin;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_form;
 
 pub use admin::*;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_form::*;
+
+/// Escape text that came from users before interpolating it into HTML.
+pub(crate) fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
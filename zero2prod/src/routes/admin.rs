@@ -1,9 +1,20 @@
 pub mod newsletters;
+pub mod users;
 
+mod api_tokens;
+mod audit;
 mod dashboard;
+mod log_filter;
 mod logout;
 mod password;
+mod security;
+mod sessions;
 
+pub use api_tokens::*;
+pub use audit::*;
 pub use dashboard::admin_dashboard;
+pub use log_filter::*;
 pub use logout::log_out;
 pub use password::*;
+pub use security::*;
+pub use sessions::*;
@@ -0,0 +1,125 @@
+use axum::{extract::State, response::IntoResponse, Extension};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{list_api_tokens, ApiScope, UserId},
+    csrf::CsrfToken,
+    e500,
+    error::ResponseError,
+    routes::html_escape,
+};
+
+#[tracing::instrument(name = "API tokens page", skip(flashes, pool, csrf))]
+pub async fn api_tokens_page(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let tokens = list_api_tokens(*user_id, &pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for token in tokens {
+        writeln!(
+            rows_html,
+            r#"        <tr>
+            <td>{name}</td>
+            <td>{scopes}</td>
+            <td>{created_at}</td>
+            <td>{expires_at}</td>
+            <td>{last_used_at}</td>
+            <td>
+                <form action="/admin/api-tokens/{id}/revoke" method="post">
+                    {csrf_input}
+                    <button type="submit">Revoke</button>
+                </form>
+            </td>
+        </tr>"#,
+            name = html_escape(&token.name),
+            scopes = html_escape(&token.scopes.join(", ")),
+            created_at = token.created_at.format("%Y-%m-%d %H:%M UTC"),
+            expires_at = token
+                .expires_at
+                .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "never".into()),
+            last_used_at = token
+                .last_used_at
+                .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+                .unwrap_or_else(|| "never".into()),
+            id = token.api_token_id,
+        )
+        .unwrap();
+    }
+
+    // Only offer the scopes the user's role allows
+    let mut scopes_html = String::new();
+    for scope in ApiScope::ALL
+        .into_iter()
+        .filter(|scope| user_id.role() >= scope.required_role())
+    {
+        writeln!(
+            scopes_html,
+            r#"        <label><input type="checkbox" name="scopes" value="{scope}"> <code>{scope}</code>: {description}</label>
+        <br>"#,
+            description = scope.description(),
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>API tokens</title>
+</head>
+<body>
+    {msg_html}
+    <p>API tokens let scripts use the API on your behalf, sending
+    <code>Authorization: Bearer &lt;token&gt;</code> with each request.</p>
+    <table>
+        <tr>
+            <th>Name</th>
+            <th>Scopes</th>
+            <th>Created</th>
+            <th>Expires</th>
+            <th>Last used</th>
+            <th></th>
+        </tr>
+{rows_html}    </table>
+    <form action="/admin/api-tokens" method="post">
+        {csrf_input}
+        <label>Name
+            <input type="text" placeholder="e.g. CMS integration" name="name">
+        </label>
+        <br>
+{scopes_html}        <label>Expires after
+            <select name="expires_in_days">
+                <option value="30">30 days</option>
+                <option value="90" selected>90 days</option>
+                <option value="365">a year</option>
+                <option value="">never</option>
+            </select>
+        </label>
+        <br>
+        <button type="submit">Create token</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+    );
+    Ok((flashes, Html(body)))
+}
@@ -0,0 +1,135 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect},
+    Extension,
+};
+use axum_extra::{extract::Form, response::Html};
+use axum_flash::Flash;
+use chrono::{Duration, Utc};
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{self, ApiScope, UserId},
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+    routes::html_escape,
+};
+
+/// Tokens can live for at most ten years, unless they never expire.
+const MAX_EXPIRY_DAYS: i64 = 3650;
+
+#[tracing::instrument(name = "Create an API token", skip(flash, pool, request, form))]
+pub async fn create_api_token(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    request: RequestMetadata,
+    Form(form): Form<CreateFormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let name = form.name.trim();
+    if name.is_empty() || name.chars().count() > 100 {
+        let flash = flash.error("The token needs a name of at most 100 characters.");
+        return Ok((flash, Redirect::to("/admin/api-tokens")).into_response());
+    }
+    if form.scopes.is_empty() {
+        let flash = flash.error("Select at least one scope for the token.");
+        return Ok((flash, Redirect::to("/admin/api-tokens")).into_response());
+    }
+    if let Some(scope) = form
+        .scopes
+        .iter()
+        .find(|scope| user_id.role() < scope.required_role())
+    {
+        let flash = flash.error(format!(
+            "Your role does not allow tokens with the {} scope.",
+            scope
+        ));
+        return Ok((flash, Redirect::to("/admin/api-tokens")).into_response());
+    }
+    let expires_at = match form.expires_in_days.trim() {
+        "" => None,
+        days => match days.parse::<i64>() {
+            Ok(days) if (1..=MAX_EXPIRY_DAYS).contains(&days) => {
+                Some(Utc::now() + Duration::days(days))
+            }
+            _ => {
+                let flash = flash.error("The expiry is invalid.");
+                return Ok((flash, Redirect::to("/admin/api-tokens")).into_response());
+            }
+        },
+    };
+
+    let scopes: Vec<ApiScope> = ApiScope::ALL
+        .into_iter()
+        .filter(|scope| form.scopes.contains(scope))
+        .collect();
+    let (api_token_id, token) =
+        authentication::create_api_token(*user_id, name, &scopes, expires_at, &pool)
+            .await
+            .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::ApiTokenCreated, &request)
+            .actor(*user_id)
+            .target(format!("api_token:{}", api_token_id)),
+    )
+    .await;
+
+    // The token is only ever shown here, so render it instead of redirecting.
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>New API token</title>
+</head>
+<body>
+    <p>Your new API token <i>{name}</i> is ready.</p>
+    <p>Copy it now and store it somewhere safe. It will not be shown again.</p>
+    <p><code>{token}</code></p>
+    <p><a href="/admin/api-tokens">Continue</a></p>
+</body>
+</html>"#,
+        name = html_escape(name),
+    );
+    Ok(Html(body).into_response())
+}
+
+#[tracing::instrument(name = "Revoke an API token", skip(flash, pool, request))]
+pub async fn revoke_api_token(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    Path(api_token_id): Path<Uuid>,
+    request: RequestMetadata,
+) -> Result<impl IntoResponse, ResponseError> {
+    if !authentication::revoke_api_token(*user_id, api_token_id, &pool)
+        .await
+        .map_err(e500)?
+    {
+        let flash = flash.error("The token does not exist or has already been revoked.");
+        return Ok((flash, Redirect::to("/admin/api-tokens")).into_response());
+    }
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::ApiTokenRevoked, &request)
+            .actor(*user_id)
+            .target(format!("api_token:{}", api_token_id)),
+    )
+    .await;
+    let flash = flash.info("The API token has been revoked.");
+    Ok((flash, Redirect::to("/admin/api-tokens")).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct CreateFormData {
+    name: String,
+    #[serde(default)]
+    scopes: Vec<ApiScope>,
+    #[serde(default)]
+    expires_in_days: String,
+}
@@ -0,0 +1,3 @@
+mod get;
+
+pub use get::{audit_page, export_audit_events};
@@ -0,0 +1,175 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use axum_extra::response::Html;
+use chrono::NaiveDate;
+use http::{
+    header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    audit::{audit_events_to_csv, list_audit_events, AuditAction, AuditFilter},
+    e500,
+    error::ResponseError,
+    routes::html_escape,
+};
+
+/// The page only shows the most recent events; the export has all of them.
+const AUDIT_PAGE_LIMIT: i64 = 200;
+
+#[tracing::instrument(name = "Audit log page", skip(pool))]
+pub async fn audit_page(
+    State(pool): State<PgPool>,
+    Query(params): Query<QueryParams>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let filter = params
+        .to_filter()
+        .map_err(|detail| ResponseError::client_error(StatusCode::BAD_REQUEST, detail))?;
+    let records = list_audit_events(&filter, Some(AUDIT_PAGE_LIMIT), &pool)
+        .await
+        .map_err(e500)?;
+
+    let mut action_options_html = String::new();
+    for action in AuditAction::ALL {
+        let selected = if filter.action == Some(action) {
+            " selected"
+        } else {
+            ""
+        };
+        writeln!(
+            action_options_html,
+            r#"                <option value="{action}"{selected}>{action}</option>"#
+        )
+        .unwrap();
+    }
+
+    let mut rows_html = String::new();
+    for record in &records {
+        writeln!(
+            rows_html,
+            r#"        <tr>
+            <td>{occurred_at}</td>
+            <td>{actor}</td>
+            <td>{action}</td>
+            <td>{target}</td>
+            <td>{ip}</td>
+            <td>{request_id}</td>
+        </tr>"#,
+            occurred_at = record.occurred_at.format("%Y-%m-%d %H:%M:%S UTC"),
+            actor = html_escape(record.actor_username.as_deref().unwrap_or("-")),
+            action = html_escape(&record.action),
+            target = html_escape(record.target.as_deref().unwrap_or("-")),
+            ip = html_escape(record.ip_address.as_deref().unwrap_or("-")),
+            request_id = html_escape(record.request_id.as_deref().unwrap_or("-")),
+        )
+        .unwrap();
+    }
+
+    let export_url = format!(
+        "/admin/audit.csv?{}",
+        html_escape(&serde_urlencoded::to_string(&params).map_err(e500)?)
+    );
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Audit log</title>
+</head>
+<body>
+    <form action="/admin/audit" method="get">
+        <label>Actor
+            <input type="text" name="actor" value="{actor}">
+        </label>
+        <label>Action
+            <select name="action">
+                <option value="">any</option>
+{action_options_html}            </select>
+        </label>
+        <label>From
+            <input type="date" name="from" value="{from}">
+        </label>
+        <label>To
+            <input type="date" name="to" value="{to}">
+        </label>
+        <button type="submit">Filter</button>
+    </form>
+    <p>Showing the {limit} most recent matching events. <a href="{export_url}">Export all as CSV</a></p>
+    <table>
+        <tr>
+            <th>Time</th>
+            <th>Actor</th>
+            <th>Action</th>
+            <th>Target</th>
+            <th>IP address</th>
+            <th>Request ID</th>
+        </tr>
+{rows_html}    </table>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        actor = html_escape(&params.actor),
+        from = html_escape(&params.from),
+        to = html_escape(&params.to),
+        limit = AUDIT_PAGE_LIMIT,
+    );
+    Ok(Html(body))
+}
+
+#[tracing::instrument(name = "Export audit log", skip(pool))]
+pub async fn export_audit_events(
+    State(pool): State<PgPool>,
+    Query(params): Query<QueryParams>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let filter = params
+        .to_filter()
+        .map_err(|detail| ResponseError::client_error(StatusCode::BAD_REQUEST, detail))?;
+    let records = list_audit_events(&filter, None, &pool)
+        .await
+        .map_err(e500)?;
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (CONTENT_DISPOSITION, r#"attachment; filename="audit.csv""#),
+        ],
+        audit_events_to_csv(&records),
+    ))
+}
+
+/// The filter form. Fields left empty match everything.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct QueryParams {
+    actor: String,
+    action: String,
+    from: String,
+    to: String,
+}
+
+impl QueryParams {
+    fn to_filter(&self) -> Result<AuditFilter, String> {
+        let date = |value: &str| {
+            non_empty(value)
+                .map(|v| {
+                    NaiveDate::parse_from_str(v, "%Y-%m-%d")
+                        .map_err(|_| format!("{} is not a valid date", v))
+                })
+                .transpose()
+        };
+        Ok(AuditFilter {
+            actor: non_empty(&self.actor).map(str::to_owned),
+            action: non_empty(&self.action).map(str::parse).transpose()?,
+            from: date(&self.from)?,
+            to: date(&self.to)?,
+        })
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|v| !v.is_empty())
+}
@@ -5,18 +5,35 @@ use http::StatusCode;
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{get_username, UserId},
+    authentication::{get_username, Role, UserId},
+    csrf::CsrfToken,
     e500,
     error::ResponseError,
 };
 
 #[debug_handler]
-#[tracing::instrument(name = "Admin Dashboard", skip(pool, user_id))]
+#[tracing::instrument(name = "Admin Dashboard", skip(pool, user_id, csrf))]
 pub async fn admin_dashboard(
+    csrf: CsrfToken,
     Extension(user_id): Extension<UserId>,
     State(pool): State<PgPool>,
 ) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
     let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let role = user_id.role();
+    // Only offer the actions the user's role allows
+    let newsletters_link = if role >= Role::Editor {
+        r#"<li><a href="/admin/newsletters">Send a newsletter issue</a></li>"#
+    } else {
+        ""
+    };
+    let users_link = if role >= Role::Owner {
+        r#"<li><a href="/admin/users">Manage users</a></li>
+        <li><a href="/admin/audit">Audit log</a></li>
+        <li><a href="/admin/log-filter">Log filter</a></li>"#
+    } else {
+        ""
+    };
 
     let response = Html((
         StatusCode::OK,
@@ -28,13 +45,18 @@ pub async fn admin_dashboard(
     <title>Admin dashboard</title>
 </head>
 <body>
-    <p>Welcome {username}!</p>
+    <p>Welcome {username}! You are signed in as {role}.</p>
     <p>Available actions:</p>
     <ol>
-        <li><a href="/admin/newsletters">Send a newsletter issue</li>
+        {newsletters_link}
         <li><a href="/admin/password">Change password</a></li>
+        <li><a href="/admin/security">Two-factor authentication</a></li>
+        <li><a href="/admin/sessions">Signed in devices</a></li>
+        <li><a href="/admin/api-tokens">API tokens</a></li>
+        {users_link}
         <li>
             <form name="logoutForm" action="/admin/logout" method="post">
+                {csrf_input}
                 <input type="submit" value="Logout">
             </form>
         </li>
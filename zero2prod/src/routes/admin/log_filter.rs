@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::log_filter_page;
+pub use post::change_log_filter;
@@ -0,0 +1,52 @@
+use axum::{extract::State, response::IntoResponse};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use std::fmt::Write;
+
+use crate::{
+    csrf::CsrfToken, e500, error::ResponseError, routes::html_escape, telemetry::LogFilter,
+};
+
+#[tracing::instrument(name = "Log filter page", skip(flashes, log_filter, csrf))]
+pub async fn log_filter_page(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    State(log_filter): State<LogFilter>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+    let current = html_escape(&log_filter.current().map_err(e500)?);
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Log filter</title>
+</head>
+<body>
+    {msg_html}
+    <p>Events are currently logged according to <code>{current}</code>.</p>
+    <p>A change applies to this instance until it restarts.</p>
+    <form action="/admin/log-filter" method="post">
+        {csrf_input}
+        <label>Filter
+            <input type="text" placeholder="info,zero2prod=debug" name="filter" value="{current}">
+        </label>
+        <br>
+        <button type="submit">Change log filter</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+    );
+    Ok((flashes, Html(body)))
+}
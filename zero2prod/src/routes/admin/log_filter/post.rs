@@ -0,0 +1,57 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::UserId,
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+    telemetry::{LogFilter, LogFilterError},
+};
+
+#[derive(Deserialize)]
+pub struct FormData {
+    filter: String,
+}
+
+#[tracing::instrument(
+    name = "Change the log filter",
+    skip(flash, log_filter, pool, request, form),
+    fields(filter=%form.filter)
+)]
+pub async fn change_log_filter(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(log_filter): State<LogFilter>,
+    State(pool): State<PgPool>,
+    request: RequestMetadata,
+    Form(form): Form<FormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let filter = form.filter.trim();
+    match log_filter.set(filter) {
+        Ok(()) => {}
+        Err(e @ LogFilterError::InvalidDirectives(_)) => {
+            let flash = flash.error(format!("{} Use directives like 'info,zero2prod=debug'.", e));
+            return Ok((flash, Redirect::to("/admin/log-filter")).into_response());
+        }
+        Err(e) => return Err(e500(e)),
+    }
+    tracing::warn!("The log filter has been changed to '{}'.", filter);
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::LogFilterChanged, &request)
+            .actor(*user_id)
+            .target(format!("log_filter:{}", filter)),
+    )
+    .await;
+
+    let flash = flash.info("The log filter has been changed.");
+    Ok((flash, Redirect::to("/admin/log-filter")).into_response())
+}
@@ -1,13 +1,38 @@
-use axum::response::{IntoResponse, Redirect};
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Extension,
+};
 use axum_flash::Flash;
 use axum_session::SessionRedisPool;
+use sqlx::PgPool;
 
-use crate::{error::ResponseError, session_state::TypedSession};
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{revoke_user_session, UserId},
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+    session_state::TypedSession,
+};
 
 pub async fn log_out(
     flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    request: RequestMetadata,
     session: TypedSession<SessionRedisPool>,
 ) -> Result<impl IntoResponse, ResponseError> {
+    if let Some(session_id) = session.get_session_id() {
+        revoke_user_session(*user_id, session_id, &pool)
+            .await
+            .map_err(e500)?;
+    }
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::LoggedOut, &request).actor(*user_id),
+    )
+    .await;
     session.log_out();
     let flash = flash.info("You have successfully logged out.");
     Ok((flash, Redirect::to("/login")).into_response())
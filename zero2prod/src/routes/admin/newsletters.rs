@@ -1,6 +1,8 @@
 mod get;
 mod post;
+mod preview;
 
 pub use get::newsletters_publish_form;
 pub use post::publish_newsletter;
 pub use post::PUBLISH_SUCCESS_INFO_MESSAGE;
+pub use preview::preview_newsletter_issue;
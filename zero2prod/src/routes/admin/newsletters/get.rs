@@ -4,13 +4,15 @@ use axum_flash::IncomingFlashes;
 use axum_macros::debug_handler;
 use std::fmt::Write;
 
-use crate::error::ResponseError;
+use crate::{csrf::CsrfToken, error::ResponseError};
 
 #[debug_handler(state = axum_flash::Config)]
-#[tracing::instrument(name = "Publish newsletter issue", skip(flashes))]
+#[tracing::instrument(name = "Publish newsletter issue", skip(flashes, csrf))]
 pub async fn newsletters_publish_form(
+    csrf: CsrfToken,
     flashes: IncomingFlashes,
 ) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
     let mut msg_html = String::new();
     for (level, text) in flashes.iter() {
         writeln!(
@@ -33,6 +35,7 @@ pub async fn newsletters_publish_form(
 <body>
     {msg_html}
     <form action="/admin/newsletters" method="post" enctype="application/x-www-form-urlencoded">
+        {csrf_input}
         <label>Newsletter Title
             <input type="text" placeholder="Enter newsletter title" name="title">
         </label>
@@ -0,0 +1,45 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use axum_extra::response::Html;
+use http::{
+    header::{CONTENT_SECURITY_POLICY, X_FRAME_OPTIONS},
+    StatusCode,
+};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    e500,
+    error::{Problem, ResponseError},
+    newsletter_issues::get_newsletter_issue,
+};
+
+/// Issues are written for email clients, so they may pull in images, fonts and styles from
+/// anywhere. The sandbox still keeps scripts and forms in them from running.
+const PREVIEW_CONTENT_SECURITY_POLICY: &str = "default-src 'none'; img-src https: data:; \
+    style-src 'unsafe-inline' https:; font-src https: data:; sandbox";
+
+/// Show the HTML content of an issue as its recipients will see it.
+#[tracing::instrument(name = "Preview newsletter issue", skip(pool))]
+pub async fn preview_newsletter_issue(
+    State(pool): State<PgPool>,
+    Path(newsletter_issue_id): Path<Uuid>,
+) -> Result<Response, ResponseError> {
+    let Some(issue) = get_newsletter_issue(newsletter_issue_id, &pool)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(Problem::new(StatusCode::NOT_FOUND).into_response());
+    };
+    Ok((
+        [
+            (CONTENT_SECURITY_POLICY, PREVIEW_CONTENT_SECURITY_POLICY),
+            // Let our own pages embed the preview.
+            (X_FRAME_OPTIONS, "SAMEORIGIN"),
+        ],
+        Html(issue.html_content),
+    )
+        .into_response())
+}
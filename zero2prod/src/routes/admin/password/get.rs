@@ -5,12 +5,14 @@ use axum_flash::{IncomingFlashes, Level};
 use http::StatusCode;
 use std::fmt::Write;
 
-use crate::error::ResponseError;
+use crate::{csrf::CsrfToken, error::ResponseError};
 
-#[tracing::instrument("Change password form")]
+#[tracing::instrument("Change password form", skip(csrf))]
 pub async fn change_password_form(
+    csrf: CsrfToken,
     flashes: IncomingFlashes,
 ) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
     let mut msg_html = String::new();
     for (level, text) in flashes.iter().filter(|m| m.0 == Level::Error) {
         writeln!(
@@ -30,6 +32,7 @@ pub async fn change_password_form(
 <body>
     {msg_html}
     <form action="/admin/password" method="post">
+        {csrf_input}
         <label>Current password
             <input type="password" placeholder="Enter current password" name="current_password">
         </label>
@@ -4,46 +4,63 @@ use axum::{
     Extension, Form,
 };
 use axum_flash::Flash;
-use secrecy::{ExposeSecret, Secret};
+use axum_session::SessionRedisPool;
+use secrecy::Secret;
 use serde::Deserialize;
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{get_username, validate_credentials, AuthError, Credentials, UserId},
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        get_user_email, get_username, revoke_all_user_sessions, validate_credentials, AuthError,
+        Credentials, NewPasswordError, PasswordHashing, PasswordPolicy, UserId,
+    },
     e500,
     error::ResponseError,
+    request_metadata::RequestMetadata,
+    session_state::TypedSession,
 };
 
-#[tracing::instrument(name = "Change password", skip(user_id, form))]
+#[tracing::instrument(
+    name = "Change password",
+    skip(user_id, hashing, policy, session, request, form)
+)]
+// Each of the password, session and audit concerns brings its own extractor.
+#[allow(clippy::too_many_arguments)]
 pub async fn change_password(
     flash: Flash,
     Extension(user_id): Extension<UserId>,
     State(pool): State<PgPool>,
+    State(hashing): State<PasswordHashing>,
+    State(policy): State<PasswordPolicy>,
+    session: TypedSession<SessionRedisPool>,
+    request: RequestMetadata,
     Form(form): Form<FormData>,
 ) -> Result<impl IntoResponse, ResponseError> {
-    // Ensure the new password is the correct length
-    if form.new_password.expose_secret().len() < 12 || form.new_password.expose_secret().len() > 128
-    {
-        let flash = flash.error("The new password should be between 12 and 128 characters long.");
-        return Ok((flash, Redirect::to("/admin/password")).into_response());
-    }
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let email = get_user_email(&username, &pool).await.map_err(e500)?;
 
-    // Ensure the new password and confirmation match
-    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
-        let flash =
-            flash.error("You entered two different new passwords - the field values must match.");
+    // Ensure the new password follows the password policy and matches its confirmation
+    let mut personal_info = vec![username.as_str()];
+    personal_info.extend(email.as_deref());
+    if let Err(e) = policy
+        .check(&form.new_password, &form.new_password_check, &personal_info)
+        .await
+    {
+        if let NewPasswordError::UnexpectedError(_) = e {
+            return Err(e500(e));
+        }
+        let flash = flash.error(e.to_string());
         return Ok((flash, Redirect::to("/admin/password")).into_response());
     }
 
     // Ensure the old/current password is valid
-    let username = get_username(*user_id, &pool).await.map_err(e500)?;
-
     let credentials = Credentials {
         username,
         password: form.current_password,
     };
 
-    if let Err(e) = validate_credentials(credentials, &pool).await {
+    if let Err(e) = validate_credentials(credentials, &hashing, &pool).await {
         return match e {
             AuthError::InvalidCredentials(_) => {
                 let flash = flash.error("The current password is incorrect.");
@@ -53,9 +70,18 @@ pub async fn change_password(
         };
     }
 
-    crate::authentication::change_password(*user_id, form.new_password, &pool)
+    crate::authentication::change_password(*user_id, form.new_password, &hashing, &pool)
+        .await
+        .map_err(e500)?;
+    // Sign out everywhere else, in case someone else knew the old password.
+    revoke_all_user_sessions(*user_id, session.get_session_id(), &pool)
         .await
         .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::PasswordChanged, &request).actor(*user_id),
+    )
+    .await;
 
     let flash = flash.error("Your password has been changed.");
 
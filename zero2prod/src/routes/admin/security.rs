@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::security_page;
+pub use post::{disable_two_factor, enable_two_factor};
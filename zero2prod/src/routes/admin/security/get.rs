@@ -0,0 +1,103 @@
+use axum::{extract::State, response::IntoResponse, Extension};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use axum_session::SessionRedisPool;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{
+        count_unused_recovery_codes, generate_totp_secret, get_totp_secret, get_username,
+        TotpEnrollment, UserId,
+    },
+    csrf::CsrfToken,
+    e500,
+    error::ResponseError,
+    session_state::TypedSession,
+};
+
+#[tracing::instrument(name = "Security page", skip(flashes, pool, session, csrf))]
+pub async fn security_page(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    session: TypedSession<SessionRedisPool>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let two_factor_html = if get_totp_secret(*user_id, &pool)
+        .await
+        .map_err(e500)?
+        .is_some()
+    {
+        let remaining = count_unused_recovery_codes(*user_id, &pool)
+            .await
+            .map_err(e500)?;
+        format!(
+            r#"<p>Two-factor authentication is enabled. You have {remaining} unused recovery codes left.</p>
+    <form action="/admin/security/2fa/disable" method="post">
+        {csrf_input}
+        <label>Current password
+            <input type="password" placeholder="Enter current password" name="current_password">
+        </label>
+        <br>
+        <button type="submit">Disable two-factor authentication</button>
+    </form>"#
+        )
+    } else {
+        // Reuse the secret across reloads, so a scanned QR code stays valid until confirmed.
+        let secret = match session.get_totp_enrollment_secret() {
+            Some(secret) => secret,
+            None => {
+                let secret = generate_totp_secret();
+                session.insert_totp_enrollment_secret(&secret);
+                secret
+            }
+        };
+        let username = get_username(*user_id, &pool).await.map_err(e500)?;
+        let enrollment = TotpEnrollment::new(&secret, &username).map_err(e500)?;
+        format!(
+            r#"<p>Two-factor authentication is disabled.</p>
+    <p>Scan this QR code with your authenticator app:</p>
+    {qr_code}
+    <p>Or enter this key manually: <code>{secret}</code></p>
+    <p><a href="{otpauth_uri}">{otpauth_uri}</a></p>
+    <form action="/admin/security/2fa/enable" method="post">
+        {csrf_input}
+        <label>Code from your app
+            <input type="text" inputmode="numeric" autocomplete="one-time-code" placeholder="123456" name="code">
+        </label>
+        <br>
+        <button type="submit">Enable two-factor authentication</button>
+    </form>"#,
+            qr_code = enrollment.qr_code_svg().map_err(e500)?,
+            otpauth_uri = enrollment.otpauth_uri(),
+        )
+    };
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Security</title>
+</head>
+<body>
+    {msg_html}
+    {two_factor_html}
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+    );
+    Ok((flashes, Html(body)))
+}
@@ -0,0 +1,131 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_extra::response::Html;
+use axum_flash::Flash;
+use axum_session::SessionRedisPool;
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        self, get_username, validate_credentials, AuthError, Credentials, PasswordHashing,
+        TotpEnrollment, UserId,
+    },
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+    session_state::TypedSession,
+};
+
+#[tracing::instrument(
+    name = "Enable two-factor authentication",
+    skip(flash, pool, session, request, form)
+)]
+pub async fn enable_two_factor(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    session: TypedSession<SessionRedisPool>,
+    request: RequestMetadata,
+    Form(form): Form<EnableFormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let Some(secret) = session.get_totp_enrollment_secret() else {
+        let flash = flash.error("Your setup session has expired. Please scan the new QR code.");
+        return Ok((flash, Redirect::to("/admin/security")).into_response());
+    };
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let enrollment = TotpEnrollment::new(&secret, &username).map_err(e500)?;
+    let Some(step) = enrollment.verify(form.code.trim(), None) else {
+        let flash = flash.error("The code is invalid. Please try again.");
+        return Ok((flash, Redirect::to("/admin/security")).into_response());
+    };
+
+    let recovery_codes = authentication::enable_two_factor(*user_id, &secret, step, &pool)
+        .await
+        .map_err(e500)?;
+    session.remove_totp_enrollment_secret();
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::TwoFactorEnabled, &request).actor(*user_id),
+    )
+    .await;
+
+    // The codes are only ever shown here, so render them instead of redirecting.
+    let mut codes_html = String::new();
+    for code in recovery_codes {
+        writeln!(codes_html, "        <li><code>{}</code></li>", code).unwrap();
+    }
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Recovery codes</title>
+</head>
+<body>
+    <p>Two-factor authentication is now enabled.</p>
+    <p>Store these recovery codes somewhere safe. Each of them can be used once to log in
+    if you lose access to your authenticator app. They will not be shown again.</p>
+    <ul>
+{codes_html}    </ul>
+    <p><a href="/admin/security">Continue</a></p>
+</body>
+</html>"#
+    );
+    Ok(Html(body).into_response())
+}
+
+#[tracing::instrument(
+    name = "Disable two-factor authentication",
+    skip(flash, pool, hashing, request, form)
+)]
+pub async fn disable_two_factor(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    State(hashing): State<PasswordHashing>,
+    request: RequestMetadata,
+    Form(form): Form<DisableFormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let username = get_username(*user_id, &pool).await.map_err(e500)?;
+    let credentials = Credentials {
+        username,
+        password: form.current_password,
+    };
+    if let Err(e) = validate_credentials(credentials, &hashing, &pool).await {
+        return match e {
+            AuthError::InvalidCredentials(_) => {
+                let flash = flash.error("The current password is incorrect.");
+                Ok((flash, Redirect::to("/admin/security")).into_response())
+            }
+            AuthError::UnexpectedError(_) => Err(e500(e)),
+        };
+    }
+
+    authentication::disable_two_factor(*user_id, &pool)
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::TwoFactorDisabled, &request).actor(*user_id),
+    )
+    .await;
+    let flash = flash.info("Two-factor authentication has been disabled.");
+    Ok((flash, Redirect::to("/admin/security")).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct EnableFormData {
+    code: String,
+}
+
+#[derive(Deserialize)]
+pub struct DisableFormData {
+    current_password: Secret<String>,
+}
@@ -0,0 +1,103 @@
+use axum::{extract::State, response::IntoResponse, Extension};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use axum_session::SessionRedisPool;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{list_active_user_sessions, SessionIdleTimeout, SessionMaxLifetime, UserId},
+    csrf::CsrfToken,
+    e500,
+    error::ResponseError,
+    routes::html_escape,
+    session_state::TypedSession,
+};
+
+#[tracing::instrument(
+    name = "Sessions page",
+    skip(flashes, max_lifetime, idle_timeout, pool, session, csrf)
+)]
+pub async fn sessions_page(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    Extension(user_id): Extension<UserId>,
+    State(max_lifetime): State<SessionMaxLifetime>,
+    State(idle_timeout): State<SessionIdleTimeout>,
+    State(pool): State<PgPool>,
+    session: TypedSession<SessionRedisPool>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let current_session_id = session.get_session_id();
+    let sessions = list_active_user_sessions(*user_id, max_lifetime, idle_timeout, &pool)
+        .await
+        .map_err(e500)?;
+    let mut rows_html = String::new();
+    for user_session in sessions {
+        let current = if Some(user_session.session_id) == current_session_id {
+            " (this session)"
+        } else {
+            ""
+        };
+        writeln!(
+            rows_html,
+            r#"        <tr>
+            <td>{ip}{current}</td>
+            <td>{user_agent}</td>
+            <td>{created_at}</td>
+            <td>{last_seen_at}</td>
+            <td>
+                <form action="/admin/sessions/{id}/revoke" method="post">
+                    {csrf_input}
+                    <button type="submit">Revoke</button>
+                </form>
+            </td>
+        </tr>"#,
+            ip = user_session.ip_address.as_deref().unwrap_or("-"),
+            user_agent = html_escape(user_session.user_agent.as_deref().unwrap_or("-")),
+            created_at = user_session.created_at.format("%Y-%m-%d %H:%M UTC"),
+            last_seen_at = user_session.last_seen_at.format("%Y-%m-%d %H:%M UTC"),
+            id = user_session.session_id,
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Sessions</title>
+</head>
+<body>
+    {msg_html}
+    <p>These devices are signed in to your account:</p>
+    <table>
+        <tr>
+            <th>IP address</th>
+            <th>Browser</th>
+            <th>Signed in</th>
+            <th>Last seen</th>
+            <th></th>
+        </tr>
+{rows_html}    </table>
+    <form action="/admin/sessions/revoke-others" method="post">
+        {csrf_input}
+        <button type="submit">Sign out everywhere else</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#
+    );
+    Ok((flashes, Html(body)))
+}
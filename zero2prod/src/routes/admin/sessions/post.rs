@@ -0,0 +1,74 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect},
+    Extension,
+};
+use axum_flash::Flash;
+use axum_session::SessionRedisPool;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{revoke_all_user_sessions, revoke_user_session, UserId},
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+    session_state::TypedSession,
+};
+
+#[tracing::instrument(name = "Revoke a session", skip(flash, pool, session, request))]
+pub async fn revoke_session(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    session: TypedSession<SessionRedisPool>,
+    Path(session_id): Path<Uuid>,
+    request: RequestMetadata,
+) -> Result<impl IntoResponse, ResponseError> {
+    if !revoke_user_session(*user_id, session_id, &pool)
+        .await
+        .map_err(e500)?
+    {
+        let flash = flash.error("The session does not exist or has already been revoked.");
+        return Ok((flash, Redirect::to("/admin/sessions")).into_response());
+    }
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::SessionRevoked, &request)
+            .actor(*user_id)
+            .target(format!("session:{}", session_id)),
+    )
+    .await;
+
+    if session.get_session_id() == Some(session_id) {
+        session.log_out();
+        let flash = flash.info("You have successfully logged out.");
+        return Ok((flash, Redirect::to("/login")).into_response());
+    }
+    let flash = flash.info("The session has been revoked.");
+    Ok((flash, Redirect::to("/admin/sessions")).into_response())
+}
+
+#[tracing::instrument(
+    name = "Revoke all other sessions",
+    skip(flash, pool, session, request)
+)]
+pub async fn revoke_other_sessions(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    session: TypedSession<SessionRedisPool>,
+    request: RequestMetadata,
+) -> Result<impl IntoResponse, ResponseError> {
+    revoke_all_user_sessions(*user_id, session.get_session_id(), &pool)
+        .await
+        .map_err(e500)?;
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::OtherSessionsRevoked, &request).actor(*user_id),
+    )
+    .await;
+    let flash = flash.info("You have been signed out everywhere else.");
+    Ok((flash, Redirect::to("/admin/sessions")).into_response())
+}
@@ -0,0 +1,136 @@
+use axum::{extract::State, response::IntoResponse, Extension};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::{list_users, Role, UserId},
+    csrf::CsrfToken,
+    e500,
+    error::ResponseError,
+};
+
+#[tracing::instrument(name = "Users page", skip(flashes, pool, csrf))]
+pub async fn users_page(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong> - <i>{}</i></p>",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let users = list_users(&pool).await.map_err(e500)?;
+    let mut rows_html = String::new();
+    for user in users {
+        let status = if user.disabled { "Disabled" } else { "Active" };
+        let (toggle_action, toggle_label) = if user.disabled {
+            ("enable", "Enable")
+        } else {
+            ("disable", "Disable")
+        };
+        let you = if user.user_id == *user_id {
+            " (you)"
+        } else {
+            ""
+        };
+        writeln!(
+            rows_html,
+            r#"        <tr>
+            <td>{username}{you}</td>
+            <td>{email}</td>
+            <td>
+                <form action="/admin/users/{id}/role" method="post">
+                    {csrf_input}
+                    <select name="role">
+{role_options}                    </select>
+                    <button type="submit">Change role</button>
+                </form>
+            </td>
+            <td>{status}</td>
+            <td>
+                <form action="/admin/users/{id}/{toggle_action}" method="post">
+                    {csrf_input}
+                    <button type="submit">{toggle_label}</button>
+                </form>
+                <form action="/admin/users/{id}/delete" method="post">
+                    {csrf_input}
+                    <button type="submit">Delete</button>
+                </form>
+            </td>
+        </tr>"#,
+            username = user.username,
+            email = user.email.as_deref().unwrap_or("-"),
+            id = user.user_id,
+            role_options = role_options(Some(user.role)),
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Users</title>
+</head>
+<body>
+    {msg_html}
+    <table>
+        <tr>
+            <th>Username</th>
+            <th>Email</th>
+            <th>Role</th>
+            <th>Status</th>
+            <th>Actions</th>
+        </tr>
+{rows_html}    </table>
+    <h2>Invite a user</h2>
+    <form action="/admin/users/invite" method="post">
+        {csrf_input}
+        <label>Username
+            <input type="text" placeholder="Enter username" name="username">
+        </label>
+        <br>
+        <label>Email
+            <input type="email" placeholder="Enter email address" name="email">
+        </label>
+        <br>
+        <label>Role
+            <select name="role">
+{invite_role_options}            </select>
+        </label>
+        <br>
+        <button type="submit">Send invitation</button>
+    </form>
+    <p><a href="/admin/dashboard">&lt;- Back</a></p>
+</body>
+</html>"#,
+        invite_role_options = role_options(None),
+    );
+    Ok((flashes, Html(body)))
+}
+
+/// `<option>`s for every role, with `selected` preselected (editors by default).
+fn role_options(selected: Option<Role>) -> String {
+    let selected = selected.unwrap_or(Role::Editor);
+    let mut html = String::new();
+    for role in Role::ALL {
+        writeln!(
+            html,
+            r#"                        <option value="{role}"{selected}>{role}</option>"#,
+            selected = if role == selected { " selected" } else { "" },
+        )
+        .unwrap();
+    }
+    html
+}
@@ -0,0 +1,208 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect},
+    Extension, Form,
+};
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        self, create_invitation, is_valid_username, InvitationError, Role, UserId,
+        UserManagementError,
+    },
+    domain::SubscriberEmail,
+    e500,
+    email_client::EmailClient,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+    startup::ApplicationBaseUrl,
+};
+
+#[tracing::instrument(
+    name = "Invite a user",
+    skip(flash, pool, email_client, base_url, request, form),
+    fields(invitee=%form.username)
+)]
+pub async fn invite_user(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    State(email_client): State<Arc<EmailClient>>,
+    State(base_url): State<ApplicationBaseUrl>,
+    request: RequestMetadata,
+    Form(form): Form<InviteFormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    if !is_valid_username(&form.username) {
+        let flash = flash.error(
+            "Usernames must be 1 to 64 characters long and only contain letters, \
+            digits, '.', '-' or '_'.",
+        );
+        return Ok((flash, Redirect::to("/admin/users")).into_response());
+    }
+    let email = match SubscriberEmail::parse(form.email) {
+        Ok(email) => email,
+        Err(_) => {
+            let flash = flash.error("The email address is invalid.");
+            return Ok((flash, Redirect::to("/admin/users")).into_response());
+        }
+    };
+
+    let token =
+        match create_invitation(&pool, &form.username, email.as_ref(), form.role, *user_id).await {
+            Ok(token) => token,
+            Err(e @ (InvitationError::UsernameTaken | InvitationError::EmailTaken)) => {
+                let flash = flash.error(e.to_string());
+                return Ok((flash, Redirect::to("/admin/users")).into_response());
+            }
+            Err(e) => return Err(e500(e)),
+        };
+
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::UserInvited, &request)
+            .actor(*user_id)
+            .target(format!("invitation:{} ({})", form.username, form.role)),
+    )
+    .await;
+
+    let invitation_link = format!("{}/invitations/accept?token={}", base_url.0, token);
+    let html_body = format!(
+        "You have been invited to help run our newsletter as '{}', with the {} role.<br />\
+        Click <a href=\"{}\">here</a> to choose a password.",
+        form.username, form.role, invitation_link
+    );
+    let plain_body = format!(
+        "You have been invited to help run our newsletter as '{}', with the {} role.\n\
+        Visit {} to choose a password.",
+        form.username, form.role, invitation_link
+    );
+    email_client
+        .send_email(&email, "You have been invited", &html_body, &plain_body)
+        .await
+        .map_err(e500)?;
+
+    let flash = flash.info(format!("An invitation has been sent to {}.", email));
+    Ok((flash, Redirect::to("/admin/users")).into_response())
+}
+
+#[tracing::instrument(
+    name = "Change a user's role",
+    skip(flash, pool, request, form),
+    fields(role=%form.role)
+)]
+pub async fn change_user_role(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    Path(target_user_id): Path<Uuid>,
+    request: RequestMetadata,
+    Form(form): Form<RoleFormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let outcome = authentication::set_user_role(target_user_id, form.role, &pool).await;
+    if outcome.is_ok() {
+        record_audit_event(
+            &pool,
+            AuditEvent::new(AuditAction::UserRoleChanged, &request)
+                .actor(*user_id)
+                .target(format!("user:{} ({})", target_user_id, form.role)),
+        )
+        .await;
+    }
+    respond(flash, outcome, "The user's role has been changed.")
+}
+
+#[tracing::instrument(name = "Disable a user", skip(flash, pool, request))]
+pub async fn disable_user(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    Path(target_user_id): Path<Uuid>,
+    request: RequestMetadata,
+) -> Result<impl IntoResponse, ResponseError> {
+    let outcome = authentication::set_user_disabled(target_user_id, true, &pool).await;
+    if outcome.is_ok() {
+        record_audit_event(
+            &pool,
+            AuditEvent::new(AuditAction::UserDisabled, &request)
+                .actor(*user_id)
+                .target(format!("user:{}", target_user_id)),
+        )
+        .await;
+    }
+    respond(flash, outcome, "The user has been disabled.")
+}
+
+#[tracing::instrument(name = "Enable a user", skip(flash, pool, request))]
+pub async fn enable_user(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    Path(target_user_id): Path<Uuid>,
+    request: RequestMetadata,
+) -> Result<impl IntoResponse, ResponseError> {
+    let outcome = authentication::set_user_disabled(target_user_id, false, &pool).await;
+    if outcome.is_ok() {
+        record_audit_event(
+            &pool,
+            AuditEvent::new(AuditAction::UserEnabled, &request)
+                .actor(*user_id)
+                .target(format!("user:{}", target_user_id)),
+        )
+        .await;
+    }
+    respond(flash, outcome, "The user has been enabled.")
+}
+
+#[tracing::instrument(name = "Delete a user", skip(flash, pool, request))]
+pub async fn delete_user(
+    flash: Flash,
+    Extension(user_id): Extension<UserId>,
+    State(pool): State<PgPool>,
+    Path(target_user_id): Path<Uuid>,
+    request: RequestMetadata,
+) -> Result<impl IntoResponse, ResponseError> {
+    let outcome = authentication::delete_user(target_user_id, &pool).await;
+    if outcome.is_ok() {
+        record_audit_event(
+            &pool,
+            AuditEvent::new(AuditAction::UserDeleted, &request)
+                .actor(*user_id)
+                .target(format!("user:{}", target_user_id)),
+        )
+        .await;
+    }
+    respond(flash, outcome, "The user has been deleted.")
+}
+
+fn respond(
+    flash: Flash,
+    outcome: Result<(), UserManagementError>,
+    success_message: &'static str,
+) -> Result<axum::response::Response, ResponseError> {
+    let flash = match outcome {
+        Ok(()) => flash.info(success_message),
+        Err(e @ (UserManagementError::UnknownUser | UserManagementError::LastActiveOwner)) => {
+            flash.error(e.to_string())
+        }
+        Err(e) => return Err(e500(e)),
+    };
+    Ok((flash, Redirect::to("/admin/users")).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct InviteFormData {
+    username: String,
+    email: String,
+    role: Role,
+}
+
+#[derive(Deserialize)]
+pub struct RoleFormData {
+    role: Role,
+}
@@ -0,0 +1,5 @@
+//! The JSON API, authenticated with API tokens rather than sessions.
+mod docs;
+pub mod v1;
+
+pub use docs::*;
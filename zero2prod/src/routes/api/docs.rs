@@ -0,0 +1,142 @@
+use std::sync::Arc;
+
+use axum::{extract::Path, response::IntoResponse, Json};
+use axum_extra::response::Html;
+use http::header::{CONTENT_SECURITY_POLICY, CONTENT_TYPE};
+use utoipa::{
+    openapi::{
+        security::{HttpAuthScheme, HttpBuilder, SecurityScheme},
+        Response,
+    },
+    Modify, OpenApi,
+};
+
+use utoipa_swagger_ui::Config;
+
+use crate::{
+    authentication::ApiScope,
+    e500,
+    error::{not_found, ProblemDetails, ResponseError},
+    routes::api::v1,
+};
+
+const OPENAPI_URL: &str = "/api/openapi.json";
+
+/// The OpenAPI document of the JSON API, generated from its handlers.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "zero2prod",
+        description = "Manage newsletter issues and subscribers. \
+            Create an API token in the admin section and send it as a bearer token."
+    ),
+    paths(
+        v1::current_token,
+        v1::list_issues,
+        v1::create_issue,
+        v1::get_issue,
+        v1::publish_issue,
+        v1::get_issue_stats,
+        v1::list_subscribers,
+        v1::export_subscribers,
+    ),
+    components(schemas(ApiScope, ProblemDetails)),
+    modifiers(&ApiTokenAuth),
+    tags(
+        (name = "issues", description = "Newsletter issues and their delivery"),
+        (name = "subscribers", description = "People signed up for the newsletter"),
+        (name = "tokens", description = "The API token used to make the request"),
+    )
+)]
+pub struct ApiDoc;
+
+/// Declares the bearer token scheme and the response every endpoint gives without a valid token.
+struct ApiTokenAuth;
+
+impl Modify for ApiTokenAuth {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        if let Some(components) = openapi.components.as_mut() {
+            components.add_security_scheme(
+                "api_token",
+                SecurityScheme::Http(
+                    HttpBuilder::new()
+                        .scheme(HttpAuthScheme::Bearer)
+                        .description(Some(format!(
+                            "A personal API token. Its scopes ({}) decide which endpoints it may use.",
+                            ApiScope::ALL.map(|scope| scope.as_str()).join(", ")
+                        )))
+                        .build(),
+                ),
+            );
+        }
+        let error = |description: &str| {
+            Response::builder()
+                .description(description)
+                .content(
+                    "application/problem+json",
+                    utoipa::openapi::Content::new(Some(utoipa::openapi::Ref::from_schema_name(
+                        "ProblemDetails",
+                    ))),
+                )
+                .build()
+        };
+        for path in openapi.paths.paths.values_mut() {
+            for operation in [&mut path.get, &mut path.post].into_iter().flatten() {
+                operation.responses.responses.insert(
+                    "401".into(),
+                    error("The API token is missing, invalid, expired or revoked").into(),
+                );
+            }
+        }
+    }
+}
+
+#[tracing::instrument(name = "OpenAPI document")]
+pub async fn openapi_json() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+#[tracing::instrument(name = "API documentation page")]
+pub async fn api_docs() -> impl IntoResponse {
+    // Swagger UI is served from `/api/docs/`, so scripts only ever come from this origin.
+    // Its stylesheet embeds images as data URIs.
+    let content_security_policy = "default-src 'self'; style-src 'self' 'unsafe-inline'; \
+        img-src 'self' data:; object-src 'none'; base-uri 'none'; frame-ancestors 'none'";
+    (
+        [(CONTENT_SECURITY_POLICY, content_security_policy)],
+        Html(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>API documentation</title>
+    <link rel="stylesheet" type="text/css" href="/api/docs/swagger-ui.css">
+</head>
+<body>
+    <noscript>
+        <p>The documentation needs JavaScript. The raw specification is at
+        <a href="/api/openapi.json">/api/openapi.json</a>.</p>
+    </noscript>
+    <div id="swagger-ui"></div>
+    <script src="/api/docs/swagger-ui-bundle.js"></script>
+    <script src="/api/docs/swagger-ui-standalone-preset.js"></script>
+    <script src="/api/docs/swagger-initializer.js"></script>
+</body>
+</html>"#,
+        ),
+    )
+}
+
+/// The files of the documentation viewer, bundled into the binary at build time.
+///
+/// `swagger-initializer.js` is generated to load `/api/openapi.json`.
+#[tracing::instrument(name = "API documentation asset")]
+pub async fn api_docs_asset(
+    Path(file): Path<String>,
+) -> Result<axum::response::Response, ResponseError> {
+    let config = Arc::new(Config::from(OPENAPI_URL));
+    let Some(file) = utoipa_swagger_ui::serve(&file, config).map_err(e500)? else {
+        return Ok(not_found().await.into_response());
+    };
+    Ok(([(CONTENT_TYPE, file.content_type)], file.bytes.into_owned()).into_response())
+}
@@ -0,0 +1,44 @@
+mod issues;
+mod subscribers;
+mod token;
+
+pub use issues::*;
+pub use subscribers::*;
+pub use token::*;
+
+use serde::Deserialize;
+use utoipa::IntoParams;
+
+use crate::error::ApiError;
+
+const DEFAULT_PAGE_SIZE: i64 = 50;
+const MAX_PAGE_SIZE: i64 = 200;
+
+/// `?limit=&offset=` on list endpoints.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct Pagination {
+    /// How many items to return, from 1 to 200. Defaults to 50.
+    limit: Option<i64>,
+    /// How many items to skip.
+    offset: Option<i64>,
+}
+
+impl Pagination {
+    /// The validated limit and offset.
+    fn parse(&self) -> Result<(i64, i64), ApiError> {
+        let limit = self.limit.unwrap_or(DEFAULT_PAGE_SIZE);
+        if !(1..=MAX_PAGE_SIZE).contains(&limit) {
+            return Err(ApiError::BadRequest(format!(
+                "limit must be between 1 and {}.",
+                MAX_PAGE_SIZE
+            )));
+        }
+        let offset = self.offset.unwrap_or(0);
+        if offset < 0 {
+            return Err(ApiError::BadRequest("offset must not be negative.".into()));
+        }
+        Ok((limit, offset))
+    }
+}
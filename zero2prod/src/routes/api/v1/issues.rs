@@ -0,0 +1,37 @@
+mod get;
+mod post;
+
+pub use get::*;
+pub use post::*;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::newsletter_issues::NewsletterIssue;
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Issue)]
+pub struct IssueResponse {
+    id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    created_at: DateTime<Utc>,
+    /// `null` while the issue is a draft.
+    published_at: Option<DateTime<Utc>>,
+}
+
+impl From<NewsletterIssue> for IssueResponse {
+    fn from(issue: NewsletterIssue) -> Self {
+        Self {
+            id: issue.newsletter_issue_id,
+            title: issue.title,
+            text_content: issue.text_content,
+            html_content: issue.html_content,
+            created_at: issue.created_at,
+            published_at: issue.published_at,
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use axum_extra::extract::WithRejection;
+use serde::Serialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::IssueResponse;
+use crate::{
+    error::{ApiError, ProblemDetails},
+    newsletter_issues::{get_delivery_stats, get_newsletter_issue, list_newsletter_issues},
+    routes::api::v1::Pagination,
+};
+
+/// List issues, drafts included, most recently created first.
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues",
+    tag = "issues",
+    params(Pagination),
+    responses(
+        (status = 200, description = "The issues", body = Vec<IssueResponse>),
+        (status = 400, description = "Invalid pagination", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the newsletters:read scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["newsletters:read"]))
+)]
+#[tracing::instrument(name = "API: list issues", skip(pool))]
+pub async fn list_issues(
+    State(pool): State<PgPool>,
+    WithRejection(Query(pagination), _): WithRejection<Query<Pagination>, ApiError>,
+) -> Result<Json<Vec<IssueResponse>>, ApiError> {
+    let (limit, offset) = pagination.parse()?;
+    let issues = list_newsletter_issues(limit, offset, &pool).await?;
+    Ok(Json(issues.into_iter().map(IssueResponse::from).collect()))
+}
+
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path, description = "The id of the issue")),
+    responses(
+        (status = 200, description = "The issue", body = IssueResponse),
+        (status = 404, description = "There is no such issue", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the newsletters:read scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["newsletters:read"]))
+)]
+#[tracing::instrument(name = "API: get issue", skip(pool))]
+pub async fn get_issue(
+    State(pool): State<PgPool>,
+    WithRejection(Path(issue_id), _): WithRejection<Path<Uuid>, ApiError>,
+) -> Result<Json<IssueResponse>, ApiError> {
+    let issue = get_newsletter_issue(issue_id, &pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("There is no such issue.".into()))?;
+    Ok(Json(issue.into()))
+}
+
+/// How far the delivery of an issue has come.
+#[utoipa::path(
+    get,
+    path = "/api/v1/issues/{issue_id}/stats",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path, description = "The id of the issue")),
+    responses(
+        (status = 200, description = "The delivery stats", body = DeliveryStatsResponse),
+        (status = 404, description = "There is no such issue", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the newsletters:read scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["newsletters:read"]))
+)]
+#[tracing::instrument(name = "API: get issue delivery stats", skip(pool))]
+pub async fn get_issue_stats(
+    State(pool): State<PgPool>,
+    WithRejection(Path(issue_id), _): WithRejection<Path<Uuid>, ApiError>,
+) -> Result<Json<DeliveryStatsResponse>, ApiError> {
+    let stats = get_delivery_stats(issue_id, &pool)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("There is no such issue.".into()))?;
+    Ok(Json(DeliveryStatsResponse {
+        recipients: stats.recipients,
+        delivered: stats.delivered,
+        failed: stats.failed,
+        pending: stats.pending,
+    }))
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = DeliveryStats)]
+pub struct DeliveryStatsResponse {
+    /// Confirmed subscribers at the time the issue was published. The counts are null
+    /// for issues published before they were kept.
+    recipients: Option<i32>,
+    delivered: Option<i32>,
+    /// Given up on after too many retries, or skipped because of invalid contact details.
+    failed: Option<i32>,
+    /// Still waiting to be sent, including retries.
+    pending: i64,
+}
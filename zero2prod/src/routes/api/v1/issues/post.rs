@@ -0,0 +1,139 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    Extension, Json,
+};
+use axum_extra::extract::WithRejection;
+use serde::Deserialize;
+use sqlx::PgPool;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use super::IssueResponse;
+use crate::{
+    audit::{insert_audit_event, AuditAction, AuditEvent},
+    authentication::ApiToken,
+    error::{ApiError, ProblemDetails},
+    newsletter_issues::{get_newsletter_issue, insert_newsletter_issue, publish_newsletter_issue},
+    request_metadata::RequestMetadata,
+};
+
+/// Store a new issue as a draft, to be published separately.
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues",
+    tag = "issues",
+    request_body = NewIssue,
+    responses(
+        (status = 201, description = "The new draft", body = IssueResponse),
+        (status = 400, description = "The body is not valid JSON", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 422, description = "The issue is incomplete", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the newsletters:write scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["newsletters:write"]))
+)]
+#[tracing::instrument(name = "API: create issue", skip(pool, body))]
+pub async fn create_issue(
+    State(pool): State<PgPool>,
+    WithRejection(Json(body), _): WithRejection<Json<NewIssue>, ApiError>,
+) -> Result<(StatusCode, Json<IssueResponse>), ApiError> {
+    body.validate()?;
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    let issue_id = insert_newsletter_issue(
+        &mut transaction,
+        body.title.trim(),
+        &body.text_content,
+        &body.html_content,
+    )
+    .await
+    .context("Failed to store newsletter issue details")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to store a newsletter issue.")?;
+
+    let issue = get_newsletter_issue(issue_id, &pool)
+        .await?
+        .context("The new issue is missing.")?;
+    Ok((StatusCode::CREATED, Json(issue.into())))
+}
+
+/// Publish a draft, sending it to every confirmed subscriber.
+#[utoipa::path(
+    post,
+    path = "/api/v1/issues/{issue_id}/publish",
+    tag = "issues",
+    params(("issue_id" = Uuid, Path, description = "The id of the draft")),
+    responses(
+        (status = 200, description = "The published issue", body = IssueResponse),
+        (status = 404, description = "There is no such issue", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 409, description = "The issue has already been published", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the newsletters:write scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["newsletters:write"]))
+)]
+#[tracing::instrument(name = "API: publish issue", skip(pool, api_token, request))]
+pub async fn publish_issue(
+    State(pool): State<PgPool>,
+    Extension(api_token): Extension<ApiToken>,
+    request: RequestMetadata,
+    WithRejection(Path(issue_id), _): WithRejection<Path<Uuid>, ApiError>,
+) -> Result<Json<IssueResponse>, ApiError> {
+    let mut transaction = pool
+        .begin()
+        .await
+        .context("Failed to acquire a Postgres connection from the pool.")?;
+    if !publish_newsletter_issue(&mut transaction, issue_id)
+        .await
+        .context("Failed to enqueue delivery tasks")?
+    {
+        return Err(match get_newsletter_issue(issue_id, &pool).await? {
+            Some(_) => ApiError::Conflict("The issue has already been published.".into()),
+            None => ApiError::NotFound("There is no such issue.".into()),
+        });
+    }
+    insert_audit_event(
+        &mut *transaction,
+        AuditEvent::new(AuditAction::NewsletterPublished, &request)
+            .actor(api_token.user_id)
+            .target(format!("issue:{}", issue_id)),
+    )
+    .await
+    .context("Failed to record the audit event")?;
+    transaction
+        .commit()
+        .await
+        .context("Failed to commit SQL transaction to publish a newsletter issue.")?;
+
+    let issue = get_newsletter_issue(issue_id, &pool)
+        .await?
+        .context("The published issue is missing.")?;
+    Ok(Json(issue.into()))
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NewIssue {
+    title: String,
+    text_content: String,
+    html_content: String,
+}
+
+impl NewIssue {
+    fn validate(&self) -> Result<(), ApiError> {
+        if self.title.trim().is_empty() {
+            return Err(ApiError::ValidationError(
+                "The title must not be empty.".into(),
+            ));
+        }
+        if self.text_content.trim().is_empty() || self.html_content.trim().is_empty() {
+            return Err(ApiError::ValidationError(
+                "Both the text and the HTML content are required.".into(),
+            ));
+        }
+        Ok(())
+    }
+}
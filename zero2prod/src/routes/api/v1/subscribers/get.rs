@@ -0,0 +1,137 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+    Json,
+};
+use axum_extra::extract::WithRejection;
+use chrono::{DateTime, Utc};
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use serde::{Deserialize, Serialize};
+use sqlx::PgPool;
+use utoipa::{IntoParams, ToSchema};
+use uuid::Uuid;
+
+use crate::{
+    error::{ApiError, ProblemDetails},
+    routes::api::v1::Pagination,
+    subscribers::{self, subscribers_to_csv, SubscriberFilter},
+};
+
+const STATUSES: [&str; 2] = ["confirmed", "pending_confirmation"];
+
+/// List subscribers, most recent first.
+///
+/// Subscribers whose stored details no longer pass the checks of the subscription form
+/// are left out, so a page can hold fewer than `limit` of them.
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers",
+    tag = "subscribers",
+    params(QueryParams, Pagination),
+    responses(
+        (status = 200, description = "The matching subscribers", body = Vec<SubscriberResponse>),
+        (status = 400, description = "Invalid filter or pagination", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the subscribers:read scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["subscribers:read"]))
+)]
+#[tracing::instrument(name = "API: list subscribers", skip(pool))]
+pub async fn list_subscribers(
+    State(pool): State<PgPool>,
+    WithRejection(Query(params), _): WithRejection<Query<QueryParams>, ApiError>,
+    WithRejection(Query(pagination), _): WithRejection<Query<Pagination>, ApiError>,
+) -> Result<Json<Vec<SubscriberResponse>>, ApiError> {
+    let filter = params.to_filter()?;
+    let (limit, offset) = pagination.parse()?;
+    let subscribers = subscribers::list_subscribers(&filter, Some(limit), offset, &pool).await?;
+    Ok(Json(
+        subscribers
+            .into_iter()
+            .filter_map(|subscriber| {
+                let validated = subscriber.validated()?;
+                Some(SubscriberResponse {
+                    id: subscriber.id,
+                    email: validated.email.as_ref().to_owned(),
+                    name: validated.name.as_ref().to_owned(),
+                    status: subscriber.status,
+                    subscribed_at: subscriber.subscribed_at,
+                })
+            })
+            .collect(),
+    ))
+}
+
+/// Every matching subscriber as CSV, for spreadsheets and mailing tools.
+#[utoipa::path(
+    get,
+    path = "/api/v1/subscribers/export",
+    tag = "subscribers",
+    params(QueryParams),
+    responses(
+        (status = 200, description = "The matching subscribers", content_type = "text/csv", body = String),
+        (status = 400, description = "Invalid filter", body = ProblemDetails, content_type = "application/problem+json"),
+        (status = 403, description = "The API token lacks the subscribers:read scope", body = ProblemDetails, content_type = "application/problem+json"),
+    ),
+    security(("api_token" = ["subscribers:read"]))
+)]
+#[tracing::instrument(name = "API: export subscribers", skip(pool))]
+pub async fn export_subscribers(
+    State(pool): State<PgPool>,
+    WithRejection(Query(params), _): WithRejection<Query<QueryParams>, ApiError>,
+) -> Result<impl IntoResponse, ApiError> {
+    let filter = params.to_filter()?;
+    let subscribers = subscribers::list_subscribers(&filter, None, 0, &pool).await?;
+    Ok((
+        [
+            (CONTENT_TYPE, "text/csv; charset=utf-8"),
+            (
+                CONTENT_DISPOSITION,
+                r#"attachment; filename="subscribers.csv""#,
+            ),
+        ],
+        subscribers_to_csv(&subscribers),
+    ))
+}
+
+/// `?status=&q=` to narrow down the subscribers. Fields left empty match everything.
+#[derive(Debug, Default, Deserialize, IntoParams)]
+#[serde(default)]
+#[into_params(parameter_in = Query)]
+pub struct QueryParams {
+    /// `confirmed` or `pending_confirmation`.
+    status: String,
+    /// Matched case-insensitively against any part of the email address or name.
+    q: String,
+}
+
+impl QueryParams {
+    fn to_filter(&self) -> Result<SubscriberFilter, ApiError> {
+        let status = non_empty(&self.status);
+        if let Some(status) = status {
+            if !STATUSES.contains(&status) {
+                return Err(ApiError::BadRequest(format!(
+                    "status must be one of {}.",
+                    STATUSES.join(", ")
+                )));
+            }
+        }
+        Ok(SubscriberFilter {
+            status: status.map(str::to_owned),
+            search: non_empty(&self.q).map(str::to_owned),
+        })
+    }
+}
+
+fn non_empty(value: &str) -> Option<&str> {
+    Some(value.trim()).filter(|v| !v.is_empty())
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Subscriber)]
+pub struct SubscriberResponse {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: String,
+    subscribed_at: DateTime<Utc>,
+}
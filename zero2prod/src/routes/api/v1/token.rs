@@ -0,0 +1,35 @@
+use axum::{Extension, Json};
+use serde::Serialize;
+use utoipa::ToSchema;
+use uuid::Uuid;
+
+use crate::authentication::{ApiScope, ApiToken};
+
+/// Describe the API token the request was made with, e.g. to check it works.
+#[utoipa::path(
+    get,
+    path = "/api/v1/token",
+    tag = "tokens",
+    responses((status = 200, description = "The API token", body = TokenResponse)),
+    security(("api_token" = []))
+)]
+#[tracing::instrument(name = "Describe current API token", skip(api_token))]
+pub async fn current_token(Extension(api_token): Extension<ApiToken>) -> Json<TokenResponse> {
+    Json(TokenResponse {
+        id: api_token.api_token_id,
+        name: api_token.name,
+        user_id: api_token.user_id,
+        role: api_token.role.to_string(),
+        scopes: api_token.scopes,
+    })
+}
+
+#[derive(Serialize, ToSchema)]
+#[schema(as = Token)]
+pub struct TokenResponse {
+    id: Uuid,
+    name: String,
+    user_id: Uuid,
+    role: String,
+    scopes: Vec<ApiScope>,
+}
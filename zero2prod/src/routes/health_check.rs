@@ -1,4 +1,23 @@
-use axum::{http::StatusCode, response::IntoResponse};
+use std::{
+    collections::{BTreeMap, HashSet},
+    future::Future,
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use axum::{extract::State, http::StatusCode, response::IntoResponse, Json};
+use chrono::{DateTime, Utc};
+use redis_pool::SingleRedisPool;
+use serde::Serialize;
+use sqlx::{migrate::Migrate, PgPool};
+
+use crate::{
+    idempotency_remover_worker, issue_delivery_worker, startup::MIGRATOR,
+    worker_heartbeat::Heartbeat,
+};
+
+/// How long a dependency may take to answer before it counts as down.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[allow(clippy::let_with_type_underscore)]
 /// Returns HTTP status code OK (200) to act as a health check
@@ -6,3 +25,186 @@ use axum::{http::StatusCode, response::IntoResponse};
 pub async fn health_check() -> impl IntoResponse {
     StatusCode::OK
 }
+
+#[derive(Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+enum Status {
+    Ok,
+    Failing,
+}
+
+#[derive(Serialize)]
+struct ComponentReport {
+    status: Status,
+    latency_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<String>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum WorkerStatus {
+    Ok,
+    /// Checked in before, but not recently.
+    Stale,
+    /// Never checked in.
+    Missing,
+    /// Its heartbeat could not be read.
+    Unknown,
+}
+
+#[derive(Serialize)]
+struct WorkerReport {
+    status: WorkerStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    last_beat_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct ReadinessReport {
+    status: Status,
+    /// Critical dependencies: the application is not ready unless all of them are ok.
+    components: BTreeMap<&'static str, ComponentReport>,
+    /// Background workers run separately, so they are reported but do not affect readiness.
+    workers: BTreeMap<&'static str, WorkerReport>,
+}
+
+/// Report whether the dependencies of the application are reachable and up to date,
+/// returning 503 Service Unavailable if any of them is not.
+#[tracing::instrument(name = "[Readiness Check]", skip_all)]
+pub async fn ready(
+    State(pool): State<PgPool>,
+    State(redis_pool): State<SingleRedisPool>,
+) -> impl IntoResponse {
+    let (database, redis, migrations) = tokio::join!(
+        probe("database", check_database(&pool)),
+        probe("redis", check_redis(&redis_pool)),
+        probe("migrations", check_migrations(&pool)),
+    );
+    let components = BTreeMap::from([
+        ("database", database),
+        ("redis", redis),
+        ("migrations", migrations),
+    ]);
+
+    let mut workers = BTreeMap::new();
+    for heartbeat in [
+        issue_delivery_worker::HEARTBEAT,
+        idempotency_remover_worker::HEARTBEAT,
+    ] {
+        workers.insert(heartbeat.worker_name, worker_report(heartbeat, &pool).await);
+    }
+
+    let status = if components.values().all(|c| c.status == Status::Ok) {
+        Status::Ok
+    } else {
+        Status::Failing
+    };
+    let status_code = match status {
+        Status::Ok => StatusCode::OK,
+        Status::Failing => StatusCode::SERVICE_UNAVAILABLE,
+    };
+    (
+        status_code,
+        Json(ReadinessReport {
+            status,
+            components,
+            workers,
+        }),
+    )
+}
+
+/// Time `check`, giving up after [`PROBE_TIMEOUT`].
+///
+/// Only the outermost error message is reported, the causes are logged.
+async fn probe<F>(component: &str, check: F) -> ComponentReport
+where
+    F: Future<Output = Result<Option<String>, anyhow::Error>>,
+{
+    let started = Instant::now();
+    let outcome = tokio::time::timeout(PROBE_TIMEOUT, check).await;
+    let latency_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let (status, detail) = match outcome {
+        Ok(Ok(detail)) => (Status::Ok, detail),
+        Ok(Err(e)) => {
+            tracing::error!(error.cause_chain = ?e, error.message = %e, "Readiness check of {} failed.", component);
+            (Status::Failing, Some(e.to_string()))
+        }
+        Err(_) => {
+            tracing::error!("Readiness check of {} timed out.", component);
+            (Status::Failing, Some("Timed out.".into()))
+        }
+    };
+    ComponentReport {
+        status,
+        latency_ms,
+        detail,
+    }
+}
+
+async fn check_database(pool: &PgPool) -> Result<Option<String>, anyhow::Error> {
+    sqlx::query!("SELECT 1 AS one")
+        .fetch_one(pool)
+        .await
+        .context("The database is unreachable.")?;
+    Ok(None)
+}
+
+async fn check_redis(redis_pool: &SingleRedisPool) -> Result<Option<String>, anyhow::Error> {
+    let mut connection = redis_pool.aquire().await.context("Redis is unreachable.")?;
+    redis::cmd("PING")
+        .query_async::<_, String>(&mut *connection)
+        .await
+        .context("Redis did not answer.")?;
+    Ok(None)
+}
+
+/// Check that every migration this build ships with has been applied, reporting the latest.
+async fn check_migrations(pool: &PgPool) -> Result<Option<String>, anyhow::Error> {
+    let mut connection = pool
+        .acquire()
+        .await
+        .context("The database is unreachable.")?;
+    let applied: HashSet<i64> = connection
+        .list_applied_migrations()
+        .await
+        .context("The applied migrations could not be listed.")?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .count();
+    if pending > 0 {
+        anyhow::bail!("Migrations not applied yet: {}.", pending);
+    }
+    Ok(applied
+        .iter()
+        .max()
+        .map(|version| format!("At version {}.", version)))
+}
+
+async fn worker_report(heartbeat: Heartbeat, pool: &PgPool) -> WorkerReport {
+    match heartbeat.last_beat_at(pool).await {
+        Ok(Some(last_beat_at)) => WorkerReport {
+            status: if heartbeat.is_stale(last_beat_at, Utc::now()) {
+                WorkerStatus::Stale
+            } else {
+                WorkerStatus::Ok
+            },
+            last_beat_at: Some(last_beat_at),
+        },
+        Ok(None) => WorkerReport {
+            status: WorkerStatus::Missing,
+            last_beat_at: None,
+        },
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to read the heartbeat of {}.", heartbeat.worker_name);
+            WorkerReport {
+                status: WorkerStatus::Unknown,
+                last_beat_at: None,
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use http::StatusCode;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{authentication::get_pending_invitation, csrf::CsrfToken, e500, error::ResponseError};
+
+#[tracing::instrument(name = "Accept invitation form", skip(flashes, pool, parameters, csrf))]
+pub async fn accept_invitation_form(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    State(pool): State<PgPool>,
+    Query(parameters): Query<InvitationParameters>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    let Some(invitation) = get_pending_invitation(&pool, &parameters.token)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok((
+            flashes,
+            Html((
+                StatusCode::NOT_FOUND,
+                "This invitation is invalid or has expired.".to_string(),
+            )),
+        ));
+    };
+
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Accept invitation</title>
+</head>
+<body>
+    {msg_html}
+    <p>Welcome {username}! Choose a password to finish setting up your account.</p>
+    <form action="/invitations/accept" method="post">
+        {csrf_input}
+        <input hidden type="text" name="token" value="{token}">
+        <label>Password
+            <input type="password" placeholder="Enter password" name="password">
+        </label>
+        <br>
+        <label>
+            <input type="password" placeholder="Type the password again" name="password_check">
+        </label>
+        <br>
+        <button type="submit">Set password</button>
+    </form>
+</body>
+</html>"#,
+        username = invitation.username,
+        token = parameters.token,
+    );
+    Ok((flashes, Html((StatusCode::OK, body))))
+}
+
+#[derive(Deserialize)]
+pub struct InvitationParameters {
+    token: String,
+}
@@ -0,0 +1,83 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Form,
+};
+use axum_flash::Flash;
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        self, get_pending_invitation, InvitationError, NewPasswordError, PasswordHashing,
+        PasswordPolicy,
+    },
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+};
+
+#[tracing::instrument(
+    name = "Accept invitation",
+    skip(flash, pool, hashing, policy, request, form)
+)]
+pub async fn accept_invitation(
+    flash: Flash,
+    State(pool): State<PgPool>,
+    State(hashing): State<PasswordHashing>,
+    State(policy): State<PasswordPolicy>,
+    request: RequestMetadata,
+    Form(form): Form<FormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let retry_url = format!(
+        "/invitations/accept?{}",
+        serde_urlencoded::to_string([("token", &form.token)]).map_err(e500)?
+    );
+    let Some(invitation) = get_pending_invitation(&pool, &form.token)
+        .await
+        .map_err(e500)?
+    else {
+        let flash = flash.error(InvitationError::InvalidInvitation.to_string());
+        return Ok((flash, Redirect::to("/login")).into_response());
+    };
+    if let Err(e) = policy
+        .check(
+            &form.password,
+            &form.password_check,
+            &[&invitation.username, &invitation.email],
+        )
+        .await
+    {
+        if let NewPasswordError::UnexpectedError(_) = e {
+            return Err(e500(e));
+        }
+        let flash = flash.error(e.to_string());
+        return Ok((flash, Redirect::to(&retry_url)).into_response());
+    }
+
+    match authentication::accept_invitation(&pool, &form.token, form.password, &hashing).await {
+        Ok(user_id) => {
+            record_audit_event(
+                &pool,
+                AuditEvent::new(AuditAction::InvitationAccepted, &request).actor(user_id),
+            )
+            .await;
+            let flash = flash.info("Your account is ready. You can now log in.");
+            Ok((flash, Redirect::to("/login")).into_response())
+        }
+        Err(e @ InvitationError::InvalidInvitation) => {
+            let flash = flash.error(e.to_string());
+            Ok((flash, Redirect::to("/login")).into_response())
+        }
+        Err(e) => Err(e500(e)),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    token: String,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
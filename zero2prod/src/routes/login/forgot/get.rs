@@ -0,0 +1,44 @@
+use axum::response::IntoResponse;
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use http::StatusCode;
+use std::fmt::Write;
+
+use crate::csrf::CsrfToken;
+
+#[tracing::instrument(name = "Forgot password form", skip(flashes, csrf))]
+pub async fn forgot_password_form(csrf: CsrfToken, flashes: IncomingFlashes) -> impl IntoResponse {
+    let csrf_input = csrf.hidden_input();
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Forgot password</title>
+</head>
+<body>
+    {msg_html}
+    <p>Enter the email address of your account and we will send you a link to reset your password.</p>
+    <form action="/login/forgot" method="post">
+        {csrf_input}
+        <label>Email
+            <input type="email" placeholder="Enter email address" name="email">
+        </label>
+        <button type="submit">Send reset link</button>
+    </form>
+    <p><a href="/login">&lt;- Back to login</a></p>
+</body>
+</html>"#
+    );
+    (flashes, Html((StatusCode::OK, body)))
+}
@@ -0,0 +1,89 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Form,
+};
+use axum_flash::Flash;
+use serde::Deserialize;
+use sqlx::PgPool;
+use tracing::Instrument;
+
+use crate::{
+    authentication::create_password_reset_token, domain::SubscriberEmail, e500,
+    email_client::EmailClient, error::ResponseError, startup::ApplicationBaseUrl,
+};
+
+#[tracing::instrument(
+    name = "Request a password reset",
+    skip(flash, pool, email_client, base_url, form)
+)]
+pub async fn forgot_password(
+    flash: Flash,
+    State(pool): State<PgPool>,
+    State(email_client): State<Arc<EmailClient>>,
+    State(base_url): State<ApplicationBaseUrl>,
+    Form(form): Form<FormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    // The same message whether or not the account exists, so the form can't be used
+    // to find out which email addresses have an account.
+    let flash = flash.info(
+        "If an account with that email address exists, we have sent it a link to reset the password.",
+    );
+
+    let Ok(email) = SubscriberEmail::parse(form.email) else {
+        return Ok((flash, Redirect::to("/login/forgot")).into_response());
+    };
+    let Some(request) = create_password_reset_token(email.as_ref(), &pool)
+        .await
+        .map_err(e500)?
+    else {
+        tracing::info!("No active account with that email address.");
+        return Ok((flash, Redirect::to("/login/forgot")).into_response());
+    };
+
+    // Sent in the background: neither how long the provider takes nor whether it fails
+    // may tell the response for a registered address apart from the one for any other.
+    let reset_link = format!("{}/login/reset?token={}", base_url.0, request.token);
+    tokio::spawn(
+        send_reset_email(email_client, email, request.username, reset_link).in_current_span(),
+    );
+
+    Ok((flash, Redirect::to("/login/forgot")).into_response())
+}
+
+async fn send_reset_email(
+    email_client: Arc<EmailClient>,
+    recipient: SubscriberEmail,
+    username: String,
+    reset_link: String,
+) {
+    let html_body = format!(
+        "Hi {},<br />\
+        Click <a href=\"{}\">here</a> to choose a new password. The link expires in an hour.<br />\
+        If you did not ask for this, you can ignore this email.",
+        username, reset_link
+    );
+    let plain_body = format!(
+        "Hi {},\n\
+        Visit {} to choose a new password. The link expires in an hour.\n\
+        If you did not ask for this, you can ignore this email.",
+        username, reset_link
+    );
+    if let Err(e) = email_client
+        .send_email(&recipient, "Reset your password", &html_body, &plain_body)
+        .await
+    {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a password reset email"
+        );
+    }
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    email: String,
+}
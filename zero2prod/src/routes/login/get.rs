@@ -5,10 +5,13 @@ use axum_macros::debug_handler;
 use http::StatusCode;
 use std::fmt::Write;
 
+use crate::csrf::CsrfToken;
+
 #[allow(clippy::let_with_type_underscore)]
 #[debug_handler(state = axum_flash::Config)]
-#[tracing::instrument(name = "Login form", skip(flashes))]
-pub async fn login_form(flashes: IncomingFlashes) -> impl IntoResponse {
+#[tracing::instrument(name = "Login form", skip(flashes, csrf))]
+pub async fn login_form(csrf: CsrfToken, flashes: IncomingFlashes) -> impl IntoResponse {
+    let csrf_input = csrf.hidden_input();
     let mut error_html = String::new();
 
     for (level, text) in flashes.iter() {
@@ -35,6 +38,7 @@ pub async fn login_form(flashes: IncomingFlashes) -> impl IntoResponse {
             <body>
                 {error_html}
                 <form action="/login" method="post">
+                    {csrf_input}
                     <label>Username
                         <input type="text" placeholder="Enter Username" name="username">
                     </label>
@@ -43,6 +47,7 @@ pub async fn login_form(flashes: IncomingFlashes) -> impl IntoResponse {
                     </label>
                     <button type="submit">Login</button>
                 </form>
+                <p><a href="/login/forgot">Forgot your password?</a></p>
             </body>
             
             </html>
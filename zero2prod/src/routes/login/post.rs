@@ -1,3 +1,5 @@
+use std::{sync::Arc, time::Duration};
+
 use axum::{
     extract::State,
     response::{IntoResponse, Redirect},
@@ -13,19 +15,34 @@ use serde::Deserialize;
 use sqlx::PgPool;
 
 use crate::{
-    authentication::{validate_credentials, AuthError, Credentials},
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        create_user_session, get_totp_secret, get_user_email, validate_credentials, AuthError,
+        Credentials, FailureOutcome, LoginGate, LoginGuard, PasswordHashing,
+    },
+    client_ip::ClientIp,
+    domain::SubscriberEmail,
+    email_client::EmailClient,
+    error::Problem,
     error_chain_fmt,
+    request_metadata::RequestMetadata,
     session_state::TypedSession,
 };
 
 #[debug_handler(state = crate::startup::AppState)]
 #[tracing::instrument(
     name = "Login posted"
-    skip(form, flash, session, pool),
+    skip(form, flash, session, pool, hashing, login_guard, email_client, request),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
+// Login touches the database, Redis, email and the session, each its own extractor.
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
     State(pool): State<PgPool>,
+    State(hashing): State<PasswordHashing>,
+    State(login_guard): State<LoginGuard>,
+    State(email_client): State<Arc<EmailClient>>,
+    request: RequestMetadata,
     flash: Flash,
     session: TypedSession<SessionRedisPool>,
     Form(form): Form<FormData>,
@@ -34,18 +51,73 @@ pub async fn login(
         username: form.username,
         password: form.password,
     };
+    let username = credentials.username.clone();
+    let client_ip = request.ip;
+
+    tracing::Span::current().record("username", tracing::field::display(&username));
 
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    match login_guard.check(&username, client_ip).await {
+        Ok(LoginGate::Locked) => {
+            // Same message as a wrong password, so the lock reveals nothing about the account.
+            let e = LoginError::AuthError(anyhow::anyhow!("Locked out after repeated failures."));
+            tracing::warn!("{:?}", &e);
+            let flash = flash.error(e.to_string());
+            return Ok((flash, Redirect::to("/login")).into_response());
+        }
+        Ok(LoginGate::Open { delay }) => {
+            if !delay.is_zero() {
+                tracing::info!("Delaying login attempt by {:?}", delay);
+                tokio::time::sleep(delay).await;
+            }
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to check the login guard. Letting the attempt through."
+            );
+        }
+    }
 
-    let response = match validate_credentials(credentials, &pool).await {
+    let response = match validate_credentials(credentials, &hashing, &pool).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::display(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+            let two_factor_enabled = get_totp_secret(user_id, &pool)
+                .await
+                .map_err(LoginError::UnexpectedError)?
+                .is_some();
             // In actix_web, it would be necessary to handle serialization failure here. Somehow axum gets around that.
             session.renew();
-            session.insert_user_id(user_id);
-            Redirect::to("/admin/dashboard").into_response()
+            if two_factor_enabled {
+                session.insert_pending_two_factor(user_id);
+                Redirect::to("/login/2fa").into_response()
+            } else {
+                // With two-factor authentication on, this waits for the second factor
+                record_login_success(&login_guard, &username).await;
+                let session_id = create_user_session(user_id, &request, &pool)
+                    .await
+                    .map_err(LoginError::UnexpectedError)?;
+                session.insert_user_id(user_id);
+                session.insert_session_id(session_id);
+                record_audit_event(
+                    &pool,
+                    AuditEvent::new(AuditAction::LoginSucceeded, &request).actor(user_id),
+                )
+                .await;
+                Redirect::to("/admin/dashboard").into_response()
+            }
         }
         Err(e) => {
+            if let AuthError::InvalidCredentials(_) = e {
+                record_audit_event(
+                    &pool,
+                    AuditEvent::new(AuditAction::LoginFailed, &request)
+                        .target(format!("username:{}", username)),
+                )
+                .await;
+                record_login_failure(&login_guard, &pool, &email_client, &username, client_ip)
+                    .await;
+            }
             let e = match e {
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
@@ -63,6 +135,96 @@ pub async fn login(
     Ok(response)
 }
 
+/// Forget the failed attempts against `username` once they are fully logged in.
+pub(super) async fn record_login_success(login_guard: &LoginGuard, username: &str) {
+    if let Err(e) = login_guard.record_success(username).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to reset failed login attempts."
+        );
+    }
+}
+
+/// Count a wrong password or second factor against `username` and the client IP,
+/// returning whether the account just got locked.
+pub(super) async fn record_login_failure(
+    login_guard: &LoginGuard,
+    pool: &PgPool,
+    email_client: &EmailClient,
+    username: &str,
+    client_ip: ClientIp,
+) -> bool {
+    match login_guard.record_failure(username, client_ip).await {
+        Ok(FailureOutcome::AccountLocked { lock_duration }) => {
+            notify_account_locked(pool, email_client, username, lock_duration).await;
+            true
+        }
+        Ok(FailureOutcome::Recorded) => false,
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "Failed to record a failed login attempt."
+            );
+            false
+        }
+    }
+}
+
+/// Let the account owner know that someone has been guessing their password.
+///
+/// The login response does not depend on this, so failures are only logged.
+#[tracing::instrument(name = "Notify account owner of lockout", skip(pool, email_client))]
+async fn notify_account_locked(
+    pool: &PgPool,
+    email_client: &EmailClient,
+    username: &str,
+    lock_duration: Duration,
+) {
+    let email = match get_user_email(username, pool).await {
+        Ok(Some(email)) => email,
+        Ok(None) => {
+            tracing::info!("No email address on file. Skipping lockout notification.");
+            return;
+        }
+        Err(e) => {
+            tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to look up user email.");
+            return;
+        }
+    };
+    let recipient = match SubscriberEmail::parse(email) {
+        Ok(recipient) => recipient,
+        Err(e) => {
+            tracing::error!(error.message = %e, "Stored user email is invalid.");
+            return;
+        }
+    };
+
+    let minutes = lock_duration.as_secs().div_ceil(60);
+    let html_body = format!(
+        "Hi {username},<br />\
+        Your account has been locked for {minutes} minutes after repeated failed login attempts.<br />\
+        If this was not you, consider changing your password once the lock expires."
+    );
+    let plain_body = format!(
+        "Hi {username},\n\
+        Your account has been locked for {minutes} minutes after repeated failed login attempts.\n\
+        If this was not you, consider changing your password once the lock expires."
+    );
+    if let Err(e) = email_client
+        .send_email(
+            &recipient,
+            "Your account has been temporarily locked",
+            &html_body,
+            &plain_body,
+        )
+        .await
+    {
+        tracing::error!(error.cause_chain = ?e, error.message = %e, "Failed to send lockout notification.");
+    }
+}
+
 #[derive(Deserialize)]
 pub struct FormData {
     username: String,
@@ -80,8 +242,10 @@ impl IntoResponse for LoginError {
     fn into_response(self) -> axum::response::Response {
         tracing::error!("{:?}", self);
         match self {
-            LoginError::AuthError(_) => StatusCode::UNAUTHORIZED.into_response(),
-            LoginError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            LoginError::AuthError(_) => Problem::new(StatusCode::UNAUTHORIZED).into_response(),
+            LoginError::UnexpectedError(_) => {
+                Problem::new(StatusCode::INTERNAL_SERVER_ERROR).into_response()
+            }
         }
     }
 }
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::reset_password_form;
+pub use post::reset_password;
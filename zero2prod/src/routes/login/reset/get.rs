@@ -0,0 +1,80 @@
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use http::StatusCode;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::fmt::Write;
+
+use crate::{
+    authentication::get_password_reset_account, csrf::CsrfToken, e500, error::ResponseError,
+};
+
+#[tracing::instrument(name = "Reset password form", skip(flashes, pool, parameters, csrf))]
+pub async fn reset_password_form(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    State(pool): State<PgPool>,
+    Query(parameters): Query<ResetParameters>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let csrf_input = csrf.hidden_input();
+    if get_password_reset_account(&parameters.token, &pool)
+        .await
+        .map_err(e500)?
+        .is_none()
+    {
+        return Ok((
+            flashes,
+            Html((
+                StatusCode::NOT_FOUND,
+                "This password reset link is invalid or has expired.".to_string(),
+            )),
+        ));
+    }
+
+    let mut msg_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            msg_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Reset password</title>
+</head>
+<body>
+    {msg_html}
+    <form action="/login/reset" method="post">
+        {csrf_input}
+        <input hidden type="text" name="token" value="{token}">
+        <label>New password
+            <input type="password" placeholder="New password" name="new_password">
+        </label>
+        <br>
+        <label>
+            <input type="password" placeholder="Type the new password again" name="new_password_check">
+        </label>
+        <br>
+        <button type="submit">Reset password</button>
+    </form>
+</body>
+</html>"#,
+        token = parameters.token,
+    );
+    Ok((flashes, Html((StatusCode::OK, body))))
+}
+
+#[derive(Deserialize)]
+pub struct ResetParameters {
+    token: String,
+}
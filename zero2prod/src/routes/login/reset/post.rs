@@ -0,0 +1,93 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Form,
+};
+use axum_flash::Flash;
+use secrecy::Secret;
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        change_password, get_password_reset_account, redeem_password_reset_token,
+        revoke_all_user_sessions, NewPasswordError, PasswordHashing, PasswordPolicy,
+    },
+    e500,
+    error::ResponseError,
+    request_metadata::RequestMetadata,
+};
+
+#[tracing::instrument(
+    name = "Reset password",
+    skip(flash, pool, hashing, policy, request, form),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn reset_password(
+    flash: Flash,
+    State(pool): State<PgPool>,
+    State(hashing): State<PasswordHashing>,
+    State(policy): State<PasswordPolicy>,
+    request: RequestMetadata,
+    Form(form): Form<FormData>,
+) -> Result<impl IntoResponse, ResponseError> {
+    let Some(account) = get_password_reset_account(&form.token, &pool)
+        .await
+        .map_err(e500)?
+    else {
+        let flash = flash.error("This password reset link is invalid or has expired.");
+        return Ok((flash, Redirect::to("/login/forgot")).into_response());
+    };
+    let mut personal_info = vec![account.username.as_str()];
+    personal_info.extend(account.email.as_deref());
+    if let Err(e) = policy
+        .check(&form.new_password, &form.new_password_check, &personal_info)
+        .await
+    {
+        if let NewPasswordError::UnexpectedError(_) = e {
+            return Err(e500(e));
+        }
+        let flash = flash.error(e.to_string());
+        let retry_url = format!(
+            "/login/reset?{}",
+            serde_urlencoded::to_string([("token", &form.token)]).map_err(e500)?
+        );
+        return Ok((flash, Redirect::to(&retry_url)).into_response());
+    }
+
+    // Only use up the token if the password is changed along with it
+    let mut transaction = pool.begin().await.map_err(e500)?;
+    let Some(user_id) = redeem_password_reset_token(&form.token, &mut *transaction)
+        .await
+        .map_err(e500)?
+    else {
+        let flash = flash.error("This password reset link is invalid or has expired.");
+        return Ok((flash, Redirect::to("/login/forgot")).into_response());
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&user_id));
+
+    change_password(user_id, form.new_password, &hashing, &mut *transaction)
+        .await
+        .map_err(e500)?;
+    // Whoever knew the old password may still be logged in.
+    revoke_all_user_sessions(user_id, None, &mut *transaction)
+        .await
+        .map_err(e500)?;
+    transaction.commit().await.map_err(e500)?;
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::PasswordReset, &request).actor(user_id),
+    )
+    .await;
+
+    let flash = flash.info("Your password has been reset. You can now log in.");
+    Ok((flash, Redirect::to("/login")).into_response())
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
@@ -0,0 +1,52 @@
+use axum::response::{IntoResponse, Redirect};
+use axum_extra::response::Html;
+use axum_flash::IncomingFlashes;
+use axum_session::SessionRedisPool;
+use http::StatusCode;
+use std::fmt::Write;
+
+use crate::{csrf::CsrfToken, session_state::TypedSession};
+
+#[tracing::instrument(name = "Two-factor login form", skip(flashes, session, csrf))]
+pub async fn two_factor_form(
+    csrf: CsrfToken,
+    flashes: IncomingFlashes,
+    session: TypedSession<SessionRedisPool>,
+) -> impl IntoResponse {
+    let csrf_input = csrf.hidden_input();
+    if session.get_pending_two_factor().is_none() {
+        return (flashes, Redirect::to("/login")).into_response();
+    }
+
+    let mut error_html = String::new();
+    for (level, text) in flashes.iter() {
+        writeln!(
+            error_html,
+            "<p><strong>{:?}</strong>: <i>{}</i></p>\n",
+            level, text
+        )
+        .unwrap();
+    }
+
+    let body = format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Two-factor authentication</title>
+</head>
+<body>
+    {error_html}
+    <p>Enter the code from your authenticator app, or one of your recovery codes.</p>
+    <form action="/login/2fa" method="post">
+        {csrf_input}
+        <label>Code
+            <input type="text" inputmode="numeric" autocomplete="one-time-code" placeholder="123456" name="code">
+        </label>
+        <button type="submit">Verify</button>
+    </form>
+</body>
+</html>"#
+    );
+    (flashes, Html((StatusCode::OK, body))).into_response()
+}
@@ -0,0 +1,102 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect},
+    Form,
+};
+use axum_flash::Flash;
+use axum_session::SessionRedisPool;
+use secrecy::{ExposeSecret, Secret};
+use serde::Deserialize;
+use sqlx::PgPool;
+
+use crate::{
+    audit::{record_audit_event, AuditAction, AuditEvent},
+    authentication::{
+        create_user_session, get_username, verify_second_factor, LoginGate, LoginGuard,
+    },
+    email_client::EmailClient,
+    request_metadata::RequestMetadata,
+    routes::login::post::{record_login_failure, record_login_success, LoginError},
+    session_state::TypedSession,
+};
+
+#[tracing::instrument(
+    name = "Two-factor login posted",
+    skip(form, flash, session, pool, login_guard, email_client, request),
+    fields(user_id=tracing::field::Empty)
+)]
+pub async fn verify_two_factor(
+    State(pool): State<PgPool>,
+    State(login_guard): State<LoginGuard>,
+    State(email_client): State<Arc<EmailClient>>,
+    request: RequestMetadata,
+    flash: Flash,
+    session: TypedSession<SessionRedisPool>,
+    Form(form): Form<FormData>,
+) -> Result<impl IntoResponse, LoginError> {
+    let Some(pending) = session.get_pending_two_factor() else {
+        let flash = flash.error("Your login has expired. Please try again.");
+        return Ok((flash, Redirect::to("/login")).into_response());
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&pending.user_id));
+
+    // Codes are guessed against the same counters as passwords
+    let username = get_username(pending.user_id, &pool)
+        .await
+        .map_err(LoginError::UnexpectedError)?;
+    if let Ok(LoginGate::Locked) = login_guard.check(&username, request.ip).await {
+        tracing::warn!("Locked out after repeated failures.");
+        session.remove_pending_two_factor();
+        let flash = flash.error("Too many failed attempts. Please try again later.");
+        return Ok((flash, Redirect::to("/login")).into_response());
+    }
+
+    let accepted = verify_second_factor(pending.user_id, form.code.expose_secret(), &pool)
+        .await
+        .map_err(LoginError::UnexpectedError)?;
+    if !accepted {
+        tracing::warn!("Invalid second factor.");
+        record_audit_event(
+            &pool,
+            AuditEvent::new(AuditAction::LoginFailed, &request)
+                .actor(pending.user_id)
+                .target("second_factor"),
+        )
+        .await;
+        let locked =
+            record_login_failure(&login_guard, &pool, &email_client, &username, request.ip).await;
+        if locked {
+            session.remove_pending_two_factor();
+            let flash = flash.error("Too many failed attempts. Please try again later.");
+            return Ok((flash, Redirect::to("/login")).into_response());
+        }
+        if session.record_two_factor_failure(pending) {
+            let flash = flash.error("The code is invalid.");
+            return Ok((flash, Redirect::to("/login/2fa")).into_response());
+        }
+        let flash = flash.error("Too many invalid codes. Please log in again.");
+        return Ok((flash, Redirect::to("/login")).into_response());
+    }
+
+    record_login_success(&login_guard, &username).await;
+    let session_id = create_user_session(pending.user_id, &request, &pool)
+        .await
+        .map_err(LoginError::UnexpectedError)?;
+    session.renew();
+    session.remove_pending_two_factor();
+    session.insert_user_id(pending.user_id);
+    session.insert_session_id(session_id);
+    record_audit_event(
+        &pool,
+        AuditEvent::new(AuditAction::LoginSucceeded, &request).actor(pending.user_id),
+    )
+    .await;
+    Ok(Redirect::to("/admin/dashboard").into_response())
+}
+
+#[derive(Deserialize)]
+pub struct FormData {
+    code: Secret<String>,
+}
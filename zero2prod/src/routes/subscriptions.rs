@@ -17,12 +17,14 @@ use sqlx::{types::Uuid, PgPool, Postgres, Transaction};
 use crate::{
     domain::NewSubscriber,
     email_client::EmailClient,
+    error::Problem,
+    form_token::{FormTokenError, FormTokenSigner},
     startup::{AppState, ApplicationBaseUrl},
 };
 
 #[tracing::instrument(
     name="[Adding a new subscriber]",
-    skip(db, email_client, base_url, form),
+    skip(db, email_client, base_url, form_token_signer, form),
     fields(
         subscriber_email=%form.email,
         subscriber_name=%form.name
@@ -33,8 +35,24 @@ pub async fn subscribe(
     State(db): State<PgPool>,
     State(email_client): State<Arc<EmailClient>>,
     State(base_url): State<ApplicationBaseUrl>,
+    State(form_token_signer): State<FormTokenSigner>,
     WithRejection(Form(form), _): WithRejection<Form<FormData>, SubscribeError>,
 ) -> Result<impl IntoResponse, SubscribeError> {
+    // Humans never see the honeypot field, so anything filled in there came from a bot.
+    // Pretend everything went fine so the bot has no signal to adapt to.
+    if !form.website.is_empty() {
+        tracing::warn!("Honeypot field was filled in. Dropping the subscription silently.");
+        return Ok(StatusCode::OK);
+    }
+
+    let form_token = form_token_signer
+        .verify(
+            form.form_token
+                .as_deref()
+                .ok_or(SubscribeError::MissingFormToken)?,
+        )
+        .map_err(SubscribeError::InvalidFormToken)?;
+
     tracing::info!(
         "Adding '{}' '{}' as a new subscriber.",
         form.email,
@@ -48,6 +66,14 @@ pub async fn subscribe(
 
     let new_subscriber = form.try_into().map_err(SubscribeError::ValidationError)?;
 
+    if !form_token
+        .redeem(&mut transaction)
+        .await
+        .context("Failed to redeem the subscription form token.")?
+    {
+        return Err(SubscribeError::ReplayedFormToken);
+    }
+
     let subscriber_id = insert_subscriber(&mut transaction, &new_subscriber)
         .await
         .context("Failed to insert a new subscriber in the database.")?;
@@ -165,6 +191,11 @@ fn generate_subscription_token() -> String {
 pub struct FormData {
     pub email: String,
     pub name: String,
+    /// The signed token rendered into the subscription form.
+    pub form_token: Option<String>,
+    /// Honeypot field hidden from humans by the subscription form.
+    #[serde(default)]
+    pub website: String,
 }
 
 pub struct StoreTokenError(sqlx::Error);
@@ -195,6 +226,12 @@ impl std::error::Error for StoreTokenError {
 pub enum SubscribeError {
     #[error("{0}")]
     ValidationError(String),
+    #[error("The subscription form token is missing.")]
+    MissingFormToken,
+    #[error("The subscription form token was rejected.")]
+    InvalidFormToken(#[source] FormTokenError),
+    #[error("The subscription form token has already been used.")]
+    ReplayedFormToken,
     // Transparent delegates both `Display`'s and `source`'s implementation
     // to the type wrapped by `UnexpectedError`.
     #[error(transparent)]
@@ -212,10 +249,20 @@ impl std::fmt::Debug for SubscribeError {
 impl IntoResponse for SubscribeError {
     fn into_response(self) -> axum::response::Response {
         tracing::error!("{:?}", self);
-        match self {
-            SubscribeError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
-            SubscribeError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            SubscribeError::FormExtractionError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        match &self {
+            SubscribeError::ValidationError(_) => {
+                Problem::new(StatusCode::UNPROCESSABLE_ENTITY).detail(self.to_string())
+            }
+            SubscribeError::MissingFormToken
+            | SubscribeError::InvalidFormToken(_)
+            | SubscribeError::ReplayedFormToken => {
+                Problem::new(StatusCode::BAD_REQUEST).detail(self.to_string())
+            }
+            SubscribeError::UnexpectedError(_) => Problem::new(StatusCode::INTERNAL_SERVER_ERROR),
+            SubscribeError::FormExtractionError(_) => {
+                Problem::new(StatusCode::UNPROCESSABLE_ENTITY)
+                    .detail("Please fill in both your name and email address.")
+            }
         }
         .into_response()
     }
@@ -8,6 +8,8 @@ use serde::Deserialize;
 use sqlx::PgPool;
 use uuid::Uuid;
 
+use crate::error::Problem;
+
 #[tracing::instrument(name = "Confirm a pending subscription", skip(db_pool, parameters))]
 pub async fn confirm(
     State(db_pool): State<PgPool>,
@@ -18,12 +20,14 @@ pub async fn confirm(
         .context("Failed to get subscriber id from token.")?;
 
     match id {
-        None => Ok(StatusCode::UNAUTHORIZED),
+        None => Ok(Problem::new(StatusCode::UNAUTHORIZED)
+            .detail("This confirmation link is invalid.")
+            .into_response()),
         Some(subscriber_id) => {
             confirm_subscriber(&db_pool, subscriber_id)
                 .await
                 .context("Failed to set subscriber to 'confirmed' status.")?;
-            Ok(StatusCode::OK)
+            Ok(StatusCode::OK.into_response())
         }
     }
 }
@@ -78,7 +82,7 @@ impl IntoResponse for ConfirmError {
     fn into_response(self) -> axum::response::Response {
         tracing::error!("{:?}", self);
         match self {
-            ConfirmError::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ConfirmError::UnexpectedError(_) => Problem::new(StatusCode::INTERNAL_SERVER_ERROR),
         }
         .into_response()
     }
@@ -0,0 +1,42 @@
+use axum::{extract::State, response::IntoResponse};
+use axum_extra::response::Html;
+use http::StatusCode;
+
+use crate::form_token::FormTokenSigner;
+
+#[tracing::instrument(name = "Subscription form", skip(form_token_signer))]
+pub async fn subscribe_form(State(form_token_signer): State<FormTokenSigner>) -> impl IntoResponse {
+    let form_token = form_token_signer.issue();
+
+    Html((
+        StatusCode::OK,
+        format!(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta http-equiv="content-type" content="text/html; charset=utf-8">
+    <title>Subscribe</title>
+</head>
+<body>
+    <form action="/subscriptions" method="post">
+        <label>Name
+            <input type="text" placeholder="Enter your name" name="name">
+        </label>
+        <br>
+        <label>Email
+            <input type="email" placeholder="Enter your email address" name="email">
+        </label>
+        <br>
+        <div style="position: absolute; left: -10000px;" aria-hidden="true">
+            <label>Leave this field empty
+                <input type="text" name="website" tabindex="-1" autocomplete="off">
+            </label>
+        </div>
+        <input hidden type="text" name="form_token" value="{form_token}">
+        <button type="submit">Subscribe</button>
+    </form>
+</body>
+</html>"#
+        ),
+    ))
+}
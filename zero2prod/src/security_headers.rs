@@ -0,0 +1,66 @@
+use axum::{body::Body, extract::State, middleware::Next, response::Response};
+use http::{
+    header::{
+        InvalidHeaderValue, CONTENT_SECURITY_POLICY, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+        X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+    },
+    HeaderName, HeaderValue, Request,
+};
+
+use crate::configuration::SecurityHeadersSettings;
+
+const PERMISSIONS_POLICY: HeaderName = HeaderName::from_static("permissions-policy");
+
+/// The headers every response carries to limit what browsers let pages do.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    headers: Vec<(HeaderName, HeaderValue)>,
+}
+
+impl SecurityHeaders {
+    pub fn new(settings: &SecurityHeadersSettings) -> Result<Self, InvalidHeaderValue> {
+        let mut headers = vec![
+            (
+                CONTENT_SECURITY_POLICY,
+                HeaderValue::from_str(&settings.content_security_policy)?,
+            ),
+            (X_FRAME_OPTIONS, HeaderValue::from_static("DENY")),
+            (X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff")),
+            // Password reset and invitation links carry their token in the URL.
+            (REFERRER_POLICY, HeaderValue::from_static("no-referrer")),
+            (
+                PERMISSIONS_POLICY,
+                HeaderValue::from_static("camera=(), microphone=(), geolocation=(), payment=()"),
+            ),
+        ];
+        if settings.hsts_max_age_seconds > 0 {
+            headers.push((
+                STRICT_TRANSPORT_SECURITY,
+                HeaderValue::from_str(&format!(
+                    "max-age={}; includeSubDomains",
+                    settings.hsts_max_age_seconds
+                ))?,
+            ));
+        }
+        Ok(Self { headers })
+    }
+}
+
+/// Add the [`SecurityHeaders`] to every response.
+///
+/// A handler overrides one for its route by setting the header itself, e.g. a page that
+/// needs a more permissive `Content-Security-Policy`.
+pub async fn set_security_headers(
+    State(security_headers): State<SecurityHeaders>,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    for (name, value) in &security_headers.headers {
+        if !headers.contains_key(name) {
+            headers.insert(name.clone(), value.clone());
+        }
+    }
+    response
+}
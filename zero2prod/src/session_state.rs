@@ -1,7 +1,27 @@
 use axum::{async_trait, extract::FromRequestParts};
 use axum_session::{DatabasePool, Session};
+use chrono::{Duration, Utc};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use crate::authentication::generate_token;
+
+/// How long a user has to enter their second factor after their password was accepted.
+const PENDING_TWO_FACTOR_VALIDITY_MINUTES: i64 = 5;
+/// Wrong codes allowed before the user has to start over with their password.
+const PENDING_TWO_FACTOR_MAX_ATTEMPTS: u32 = 5;
+
+/// A user who got their password right but has not entered their second factor yet.
+///
+/// Deliberately kept apart from the user id, so the admin section stays locked.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct PendingTwoFactor {
+    pub user_id: Uuid,
+    /// Unix timestamp in seconds.
+    expires_at: i64,
+    failed_attempts: u32,
+}
+
 pub struct TypedSession<T>(Session<T>)
 where
     T: DatabasePool + Clone + std::fmt::Debug + Sync + Send + 'static;
@@ -10,6 +30,10 @@ where
     T: DatabasePool + Clone + std::fmt::Debug + Sync + Send + 'static,
 {
     const USER_ID_KEY: &'static str = "user_id";
+    const SESSION_ID_KEY: &'static str = "session_id";
+    const PENDING_TWO_FACTOR_KEY: &'static str = "pending_two_factor";
+    const TOTP_ENROLLMENT_SECRET_KEY: &'static str = "totp_enrollment_secret";
+    const CSRF_TOKEN_KEY: &'static str = "csrf_token";
 
     pub fn get_user_id(&self) -> Option<Uuid> {
         self.0.get(Self::USER_ID_KEY)
@@ -19,6 +43,76 @@ where
         self.0.set(Self::USER_ID_KEY, user_id)
     }
 
+    /// The id of the `user_sessions` row recorded when the user logged in.
+    pub fn get_session_id(&self) -> Option<Uuid> {
+        self.0.get(Self::SESSION_ID_KEY)
+    }
+
+    pub fn insert_session_id(&self, session_id: Uuid) {
+        self.0.set(Self::SESSION_ID_KEY, session_id)
+    }
+
+    pub fn insert_pending_two_factor(&self, user_id: Uuid) {
+        let pending = PendingTwoFactor {
+            user_id,
+            expires_at: (Utc::now() + Duration::minutes(PENDING_TWO_FACTOR_VALIDITY_MINUTES))
+                .timestamp(),
+            failed_attempts: 0,
+        };
+        self.0.set(Self::PENDING_TWO_FACTOR_KEY, pending)
+    }
+
+    /// The pending second factor check, unless it has expired.
+    pub fn get_pending_two_factor(&self) -> Option<PendingTwoFactor> {
+        self.0
+            .get::<PendingTwoFactor>(Self::PENDING_TWO_FACTOR_KEY)
+            .filter(|p| p.expires_at > Utc::now().timestamp())
+    }
+
+    /// Count a wrong code, dropping the pending check once too many have been entered.
+    ///
+    /// Returns whether the user may try again.
+    pub fn record_two_factor_failure(&self, mut pending: PendingTwoFactor) -> bool {
+        pending.failed_attempts += 1;
+        if pending.failed_attempts >= PENDING_TWO_FACTOR_MAX_ATTEMPTS {
+            self.remove_pending_two_factor();
+            false
+        } else {
+            self.0.set(Self::PENDING_TWO_FACTOR_KEY, pending);
+            true
+        }
+    }
+
+    pub fn remove_pending_two_factor(&self) {
+        self.0.remove(Self::PENDING_TWO_FACTOR_KEY)
+    }
+
+    pub fn get_totp_enrollment_secret(&self) -> Option<String> {
+        self.0.get(Self::TOTP_ENROLLMENT_SECRET_KEY)
+    }
+
+    /// Keep the secret shown on the enrollment page until the user confirms it with a code.
+    pub fn insert_totp_enrollment_secret(&self, secret: &str) {
+        self.0.set(Self::TOTP_ENROLLMENT_SECRET_KEY, secret)
+    }
+
+    pub fn remove_totp_enrollment_secret(&self) {
+        self.0.remove(Self::TOTP_ENROLLMENT_SECRET_KEY)
+    }
+
+    pub fn get_csrf_token(&self) -> Option<String> {
+        self.0.get(Self::CSRF_TOKEN_KEY)
+    }
+
+    /// The token forms rendered for this session must carry, created on first use.
+    pub fn csrf_token(&self) -> String {
+        self.get_csrf_token().unwrap_or_else(|| {
+            let token = generate_token();
+            self.0.set(Self::CSRF_TOKEN_KEY, &token);
+            token
+        })
+    }
+
     pub fn log_out(self) {
         self.0.destroy();
     }
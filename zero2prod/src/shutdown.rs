@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use tokio_util::sync::CancellationToken;
+
+/// Tells the server and the workers to stop taking on new work and finish what they are doing.
+///
+/// Clones share the same signal.
+#[derive(Clone, Debug)]
+pub struct Shutdown {
+    token: CancellationToken,
+    /// How long they are given to finish before the process exits anyway.
+    drain_timeout: Duration,
+}
+
+impl Shutdown {
+    pub fn new(drain_timeout: Duration) -> Self {
+        Self {
+            token: CancellationToken::new(),
+            drain_timeout,
+        }
+    }
+
+    pub fn drain_timeout(&self) -> Duration {
+        self.drain_timeout
+    }
+
+    pub fn trigger(&self) {
+        self.token.cancel();
+    }
+
+    pub fn is_triggered(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Completes once the shutdown has been triggered.
+    pub async fn triggered(self) {
+        self.token.cancelled_owned().await
+    }
+
+    /// Sleep for `duration`, waking up early if the shutdown is triggered meanwhile.
+    ///
+    /// Returns `false` if it was woken up early.
+    pub async fn sleep(&self, duration: Duration) -> bool {
+        tokio::select! {
+            _ = tokio::time::sleep(duration) => true,
+            _ = self.token.cancelled() => false,
+        }
+    }
+}
+
+/// Completes when the process is asked to stop, by Ctrl+C or `SIGTERM`.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received Ctrl+C, shutting down."),
+        _ = terminate => tracing::info!("Received SIGTERM, shutting down."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::Shutdown;
+
+    #[tokio::test]
+    async fn triggering_wakes_up_sleepers_in_every_clone() {
+        let shutdown = Shutdown::new(Duration::from_secs(1));
+        let sleeper = tokio::spawn({
+            let shutdown = shutdown.clone();
+            async move { shutdown.sleep(Duration::from_secs(60)).await }
+        });
+
+        shutdown.trigger();
+
+        let slept_in_full = tokio::time::timeout(Duration::from_secs(1), sleeper)
+            .await
+            .expect("The sleeper was not woken up")
+            .unwrap();
+        assert!(!slept_in_full);
+        assert!(shutdown.is_triggered());
+    }
+}
@@ -1,43 +1,72 @@
-use std::sync::Arc;
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc};
 
 use axum::{
-    extract::FromRef,
-    middleware,
-    routing::{get, post, IntoMakeService},
+    extract::{connect_info::IntoMakeServiceWithConnectInfo, ConnectInfo, FromRef},
+    middleware::{self, AddExtension},
+    routing::{get, post},
     serve::Serve,
     Router,
 };
 use axum_flash::Key;
-use axum_session::{SessionConfig, SessionLayer, SessionRedisPool, SessionStore};
-use redis_pool::RedisPool;
+use axum_session::{SessionLayer, SessionRedisPool, SessionStore};
+use redis_pool::{RedisPool, SingleRedisPool};
 use secrecy::{ExposeSecret, Secret};
-use sqlx::{postgres::PgPoolOptions, PgPool};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions, PgPool};
 use tokio::net::TcpListener;
 
 use crate::{
-    authentication::reject_anonymous_users,
+    authentication::{
+        bootstrap_admin, reject_anonymous_users, reject_invalid_api_tokens, require_role,
+        require_scope, ApiScope, LoginGuard, PasswordHashing, PasswordPolicy, Role,
+        SessionIdleTimeout, SessionMaxLifetime,
+    },
     configuration::{DatabaseSettings, Settings},
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, home, log_out, login,
-        login_form,
-        newsletters::{newsletters_publish_form, publish_newsletter},
+        accept_invitation, accept_invitation_form, admin_dashboard, api, api_tokens_page,
+        audit_page, change_log_filter, change_password, change_password_form, confirm,
+        create_api_token, disable_two_factor, enable_two_factor, export_audit_events,
+        forgot_password, forgot_password_form, home, log_filter_page, log_out, login, login_form,
+        newsletters::{newsletters_publish_form, preview_newsletter_issue, publish_newsletter},
+        reset_password, reset_password_form, revoke_api_token, revoke_other_sessions,
+        revoke_session, security_page, sessions_page, subscribe_form, two_factor_form, users,
+        verify_two_factor,
     },
-    telemetry::RouterExt,
+    telemetry::{LogFilter, RouterExt},
 };
 use crate::{
+    client_ip::TrustForwardedFor,
+    csrf::verify_csrf_token,
     email_client::EmailClient,
-    routes::{health_check, subscribe},
+    error::{not_found, render_problems},
+    form_token::FormTokenSigner,
+    metrics::{metrics_endpoint, track_http_requests},
+    rate_limit::{
+        limit_login_attempts, limit_password_reset_requests, limit_subscriptions, RateLimiter,
+    },
+    routes::{health_check, ready, subscribe},
+    security_headers::{set_security_headers, SecurityHeaders},
+    shutdown::Shutdown,
 };
 
-pub type AppServer = Serve<IntoMakeService<Router>, Router>;
+pub type AppServer = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
+
+pub type MetricsServer = Serve<Router, Router>;
 
 pub struct Application {
     port: u16,
     server: AppServer,
+    /// Serves `/metrics` when it is kept off the application port.
+    metrics: Option<(u16, MetricsServer)>,
 }
 
 impl Application {
-    pub async fn build(configuration: Settings) -> Result<Self, anyhow::Error> {
+    pub async fn build(
+        configuration: Settings,
+        log_filter: LogFilter,
+    ) -> Result<Self, anyhow::Error> {
         // Get database pool
         let db_pool = get_db_pool(&configuration.database);
 
@@ -45,42 +74,141 @@ impl Application {
         let redis = redis::Client::open(configuration.redis.uri.expose_secret().as_str())?;
         let redis_pool = RedisPool::from(redis);
         // Create a session store
-        let session_config = SessionConfig::new();
+        let session_config = configuration.session.session_config();
         let session_store =
-            SessionStore::<SessionRedisPool>::new(Some(redis_pool.into()), session_config).await?;
-
-        // Build an email client
-        let email_client = configuration.email_client.client();
+            SessionStore::<SessionRedisPool>::new(Some(redis_pool.clone().into()), session_config)
+                .await?;
 
         let address = format!(
             "{}:{}",
             configuration.application.host, configuration.application.port
         );
-        let listener = TcpListener::bind(address.to_string()).await.map_err(|e| {
-            tracing::error!("failed to bind port {}", address);
-            e
-        })?;
+        let listener = TcpListener::bind(address.to_string())
+            .await
+            .inspect_err(|_| {
+                tracing::error!("failed to bind port {}", address);
+            })?;
         let port = listener.local_addr().unwrap().port();
-        let server = run(
-            listener,
+
+        let metrics = MetricsApplication::build(&configuration, db_pool.clone())
+            .await?
+            .map(|metrics| (metrics.port, metrics.server));
+
+        // Build an email client
+        let email_client = configuration.email_client.client();
+
+        // Let the first owner in on a new install
+        let password_hashing = PasswordHashing::new(&configuration.password_hashing)?;
+        let password_policy = PasswordPolicy::new(configuration.password_policy);
+        bootstrap_admin(
+            &configuration.bootstrap_admin,
+            &password_policy,
+            &password_hashing,
+            &db_pool,
+        )
+        .await?;
+
+        // Build app state
+        let hmac_secret = configuration.application.hmac_secret;
+        let app_state = AppState {
             db_pool,
-            email_client,
-            configuration.application.base_url,
-            configuration.application.hmac_secret,
-            session_store,
-        );
-        Ok(Self { port, server })
+            email_client: Arc::new(email_client),
+            base_url: ApplicationBaseUrl(configuration.application.base_url),
+            flash_config: axum_flash::Config::new(Key::from(
+                hmac_secret.expose_secret().as_bytes(),
+            )),
+            form_token_signer: configuration
+                .subscriptions
+                .form_token_signer(hmac_secret.clone()),
+            rate_limiter: RateLimiter::new(redis_pool.clone(), configuration.rate_limit),
+            login_guard: LoginGuard::new(redis_pool.clone(), configuration.login_protection),
+            password_hashing,
+            password_policy,
+            trust_forwarded_for: TrustForwardedFor(configuration.application.trust_forwarded_for),
+            session_max_lifetime: SessionMaxLifetime(configuration.session.absolute_timeout()),
+            session_idle_timeout: SessionIdleTimeout(configuration.session.idle_timeout()),
+            security_headers: SecurityHeaders::new(&configuration.security_headers)?,
+            redis_pool,
+            log_filter,
+        };
+
+        let server = run(listener, app_state, session_store, metrics.is_none());
+        Ok(Self {
+            port,
+            server,
+            metrics,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
-    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+    /// The port `/metrics` is served on, if not the application port.
+    pub fn metrics_port(&self) -> Option<u16> {
+        self.metrics.as_ref().map(|(port, _)| *port)
     }
+
+    /// Serve requests until `shutdown` is triggered, then wait for those in flight.
+    pub async fn run_until_stopped(self, shutdown: Shutdown) -> Result<(), std::io::Error> {
+        let server = self
+            .server
+            .with_graceful_shutdown(shutdown.clone().triggered());
+        match self.metrics {
+            Some((_, metrics_server)) => {
+                let metrics_server = metrics_server.with_graceful_shutdown(shutdown.triggered());
+                tokio::try_join!(server.into_future(), metrics_server.into_future())?;
+                Ok(())
+            }
+            None => server.await,
+        }
+    }
+}
+
+/// Serves `/metrics` on `metrics.port`, e.g. for a process that only runs workers.
+///
+/// Metrics are kept in the process that records them, so each process has to serve its
+/// own: the delivery worker's are never seen by the server of another process.
+pub struct MetricsApplication {
+    port: u16,
+    server: MetricsServer,
 }
 
+impl MetricsApplication {
+    /// `None` if no metrics port is configured.
+    pub async fn build(
+        configuration: &Settings,
+        db_pool: PgPool,
+    ) -> Result<Option<Self>, anyhow::Error> {
+        let Some(port) = configuration.metrics.port else {
+            return Ok(None);
+        };
+        let address = format!("{}:{}", configuration.application.host, port);
+        let listener = TcpListener::bind(&address).await.inspect_err(|_| {
+            tracing::error!("failed to bind metrics port {}", address);
+        })?;
+        let port = listener.local_addr().unwrap().port();
+        Ok(Some(Self {
+            port,
+            server: run_metrics(listener, db_pool),
+        }))
+    }
+
+    pub fn port(&self) -> u16 {
+        self.port
+    }
+
+    /// Serve `/metrics` until `shutdown` is triggered.
+    pub async fn run_until_stopped(self, shutdown: Shutdown) -> Result<(), std::io::Error> {
+        self.server
+            .with_graceful_shutdown(shutdown.triggered())
+            .await
+    }
+}
+
+/// The migrations this build expects the database to be at.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
 /// Get a database connection pool.
 pub fn get_db_pool(configuration: &DatabaseSettings) -> PgPool {
     PgPoolOptions::new()
@@ -90,52 +218,200 @@ pub fn get_db_pool(configuration: &DatabaseSettings) -> PgPool {
 
 pub fn run(
     listener: TcpListener,
-    db_pool: PgPool,
-    email_client: EmailClient,
-    base_url: String,
-    hmac_secret: Secret<String>,
+    app_state: AppState,
     session_store: SessionStore<SessionRedisPool>,
+    serve_metrics: bool,
 ) -> AppServer {
-    // Build app state
-    let app_state = AppState {
-        db_pool,
-        email_client: Arc::new(email_client),
-        base_url: ApplicationBaseUrl(base_url),
-        flash_config: axum_flash::Config::new(Key::from(hmac_secret.expose_secret().as_bytes())),
-    };
-
     // Routes that need to not have a session applied
-    let router_no_session = Router::new().route("/health_check", get(health_check));
+    let router_no_session = Router::new()
+        .route("/health_check", get(health_check))
+        .route("/ready", get(ready))
+        .route("/api/openapi.json", get(api::openapi_json))
+        .route("/api/docs", get(api::api_docs))
+        .route("/api/docs/:file", get(api::api_docs_asset));
 
-    // All admin section routes
-    let router_for_admin_section = Router::new()
-        .route("/admin/dashboard", get(admin_dashboard))
+    // Admin section routes that change what subscribers receive
+    let router_for_editors = Router::new()
         .route("/admin/newsletters", get(newsletters_publish_form))
         .route("/admin/newsletters", post(publish_newsletter))
+        .route_layer(middleware::from_fn_with_state(Role::Editor, require_role));
+
+    // Admin section routes that manage other users
+    let router_for_owners = Router::new()
+        .route("/admin/users", get(users::users_page))
+        .route("/admin/users/invite", post(users::invite_user))
+        .route("/admin/users/:user_id/role", post(users::change_user_role))
+        .route("/admin/users/:user_id/disable", post(users::disable_user))
+        .route("/admin/users/:user_id/enable", post(users::enable_user))
+        .route("/admin/users/:user_id/delete", post(users::delete_user))
+        .route("/admin/audit", get(audit_page))
+        .route("/admin/audit.csv", get(export_audit_events))
+        .route("/admin/log-filter", get(log_filter_page))
+        .route("/admin/log-filter", post(change_log_filter))
+        .route_layer(middleware::from_fn_with_state(Role::Owner, require_role));
+
+    // All admin section routes, open to every role unless stated otherwise above
+    let router_for_admin_section = Router::new()
+        .route("/admin/dashboard", get(admin_dashboard))
         .route("/admin/password", get(change_password_form))
         .route("/admin/password", post(change_password))
         .route("/admin/logout", post(log_out))
-        .layer(middleware::from_fn(reject_anonymous_users));
+        .route("/admin/security", get(security_page))
+        .route("/admin/security/2fa/enable", post(enable_two_factor))
+        .route("/admin/security/2fa/disable", post(disable_two_factor))
+        .route("/admin/sessions", get(sessions_page))
+        .route("/admin/sessions/revoke-others", post(revoke_other_sessions))
+        .route("/admin/sessions/:session_id/revoke", post(revoke_session))
+        .route(
+            "/admin/newsletters/:newsletter_issue_id/preview",
+            get(preview_newsletter_issue),
+        )
+        .route("/admin/api-tokens", get(api_tokens_page))
+        .route("/admin/api-tokens", post(create_api_token))
+        .route(
+            "/admin/api-tokens/:api_token_id/revoke",
+            post(revoke_api_token),
+        )
+        .merge(router_for_editors)
+        .merge(router_for_owners)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            reject_anonymous_users,
+        ));
 
     // All routes that should be a care about session
     let router_with_session = Router::new()
         .route("/", get(home))
         .route("/login", get(login_form))
-        .route("/login", post(login))
-        .route("/subscriptions", post(subscribe))
-        .route("/subscriptions/confirm", get(confirm))
+        .route(
+            "/login",
+            post(login).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                limit_login_attempts,
+            )),
+        )
+        .route("/login/2fa", get(two_factor_form))
+        .route(
+            "/login/2fa",
+            post(verify_two_factor).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                limit_login_attempts,
+            )),
+        )
+        .route("/login/forgot", get(forgot_password_form))
+        .route(
+            "/login/forgot",
+            post(forgot_password).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                limit_password_reset_requests,
+            )),
+        )
+        .route("/login/reset", get(reset_password_form))
+        .route("/login/reset", post(reset_password))
+        .route("/invitations/accept", get(accept_invitation_form))
+        .route("/invitations/accept", post(accept_invitation))
         .merge(router_for_admin_section)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            verify_csrf_token,
+        ))
+        // The public subscription form is protected by its signed form token instead
+        .route("/subscriptions", get(subscribe_form))
+        .route(
+            "/subscriptions",
+            post(subscribe).layer(middleware::from_fn_with_state(
+                app_state.clone(),
+                limit_subscriptions,
+            )),
+        )
+        .route("/subscriptions/confirm", get(confirm))
         .layer(SessionLayer::new(session_store));
 
+    // JSON API routes, each guarded by the API token scope it needs
+    let api_for_newsletter_readers = Router::new()
+        .route("/api/v1/issues", get(api::v1::list_issues))
+        .route("/api/v1/issues/:issue_id", get(api::v1::get_issue))
+        .route(
+            "/api/v1/issues/:issue_id/stats",
+            get(api::v1::get_issue_stats),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            ApiScope::NewslettersRead,
+            require_scope,
+        ));
+    let api_for_newsletter_writers = Router::new()
+        .route("/api/v1/issues", post(api::v1::create_issue))
+        .route(
+            "/api/v1/issues/:issue_id/publish",
+            post(api::v1::publish_issue),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            ApiScope::NewslettersWrite,
+            require_scope,
+        ));
+    let api_for_subscriber_readers = Router::new()
+        .route("/api/v1/subscribers", get(api::v1::list_subscribers))
+        .route(
+            "/api/v1/subscribers/export",
+            get(api::v1::export_subscribers),
+        )
+        .route_layer(middleware::from_fn_with_state(
+            ApiScope::SubscribersRead,
+            require_scope,
+        ));
+
+    // Without a port of its own, `/metrics` is public and needs a token like the API
+    let mut metrics_for_scrapers = Router::new();
+    if serve_metrics {
+        metrics_for_scrapers = metrics_for_scrapers
+            .route("/metrics", get(metrics_endpoint))
+            .route_layer(middleware::from_fn_with_state(
+                ApiScope::MetricsRead,
+                require_scope,
+            ));
+    }
+
+    // The JSON API authenticates each request with an API token instead of a session
+    let router_for_api = Router::new()
+        .route("/api/v1/token", get(api::v1::current_token))
+        .merge(api_for_newsletter_readers)
+        .merge(api_for_newsletter_writers)
+        .merge(api_for_subscriber_readers)
+        .merge(metrics_for_scrapers)
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            reject_invalid_api_tokens,
+        ));
+
     // Create a router that will contain and match all routes for the application
     let app = Router::new()
         .merge(router_no_session)
+        .merge(router_for_api)
         .merge(router_with_session)
+        .fallback(not_found)
+        .layer(middleware::from_fn(render_problems))
+        .layer(middleware::from_fn_with_state(
+            app_state.clone(),
+            set_security_headers,
+        ))
+        .layer(middleware::from_fn(track_http_requests))
         .add_axum_tracing_layer()
         .with_state(app_state);
 
-    // Start the axum server and set up to use supplied listener
-    axum::serve(listener, app.into_make_service())
+    // Start the axum server and set up to use supplied listener.
+    // The peer address is needed to key rate limits by client IP.
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+}
+
+/// Serve only `/metrics`, on a listener of its own.
+pub fn run_metrics(listener: TcpListener, db_pool: PgPool) -> MetricsServer {
+    let app = Router::new()
+        .route("/metrics", get(metrics_endpoint))
+        .with_state(db_pool);
+    axum::serve(listener, app)
 }
 
 #[derive(Clone)]
@@ -144,6 +420,17 @@ pub struct AppState {
     email_client: Arc<EmailClient>,
     base_url: ApplicationBaseUrl,
     flash_config: axum_flash::Config,
+    form_token_signer: FormTokenSigner,
+    rate_limiter: RateLimiter,
+    login_guard: LoginGuard,
+    password_hashing: PasswordHashing,
+    password_policy: PasswordPolicy,
+    trust_forwarded_for: TrustForwardedFor,
+    session_max_lifetime: SessionMaxLifetime,
+    session_idle_timeout: SessionIdleTimeout,
+    security_headers: SecurityHeaders,
+    redis_pool: SingleRedisPool,
+    log_filter: LogFilter,
 }
 
 impl FromRef<AppState> for PgPool {
@@ -170,6 +457,72 @@ impl FromRef<AppState> for axum_flash::Config {
     }
 }
 
+impl FromRef<AppState> for FormTokenSigner {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.form_token_signer.clone()
+    }
+}
+
+impl FromRef<AppState> for RateLimiter {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.rate_limiter.clone()
+    }
+}
+
+impl FromRef<AppState> for PasswordHashing {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.password_hashing.clone()
+    }
+}
+
+impl FromRef<AppState> for PasswordPolicy {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.password_policy.clone()
+    }
+}
+
+impl FromRef<AppState> for LoginGuard {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.login_guard.clone()
+    }
+}
+
+impl FromRef<AppState> for TrustForwardedFor {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.trust_forwarded_for
+    }
+}
+
+impl FromRef<AppState> for SessionMaxLifetime {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.session_max_lifetime
+    }
+}
+
+impl FromRef<AppState> for SessionIdleTimeout {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.session_idle_timeout
+    }
+}
+
+impl FromRef<AppState> for SingleRedisPool {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.redis_pool.clone()
+    }
+}
+
+impl FromRef<AppState> for LogFilter {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.log_filter.clone()
+    }
+}
+
+impl FromRef<AppState> for SecurityHeaders {
+    fn from_ref(app_state: &AppState) -> Self {
+        app_state.security_headers.clone()
+    }
+}
+
 #[derive(Clone)]
 pub struct ApplicationBaseUrl(pub String);
 
@@ -0,0 +1,125 @@
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use uuid::Uuid;
+
+use crate::{
+    csv::csv_row,
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+};
+
+/// Narrows down the subscribers that are listed. Empty fields match everything.
+#[derive(Debug, Default)]
+pub struct SubscriberFilter {
+    /// `confirmed` or `pending_confirmation`.
+    pub status: Option<String>,
+    /// Matched case-insensitively against any part of the email address or name.
+    pub search: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct SubscriberRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: String,
+    pub subscribed_at: DateTime<Utc>,
+}
+
+impl TryFrom<&SubscriberRecord> for NewSubscriber {
+    type Error = String;
+
+    fn try_from(value: &SubscriberRecord) -> Result<Self, Self::Error> {
+        let name = SubscriberName::parse(value.name.clone())?;
+        let email = SubscriberEmail::parse(value.email.clone())?;
+        Ok(Self { name, email })
+    }
+}
+
+impl SubscriberRecord {
+    /// The stored details, if they pass the checks of the subscription form.
+    ///
+    /// Rows stored before a check was added may not, and are logged and left out of
+    /// listings, as they could not be contacted anyway.
+    pub fn validated(&self) -> Option<NewSubscriber> {
+        match NewSubscriber::try_from(self) {
+            Ok(new_subscriber) => Some(new_subscriber),
+            Err(e) => {
+                tracing::warn!("Leaving subscriber {} out: {}", self.id, e);
+                None
+            }
+        }
+    }
+}
+
+/// The matching subscribers, most recent first.
+#[tracing::instrument(name = "List subscribers", skip(pool))]
+pub async fn list_subscribers(
+    filter: &SubscriberFilter,
+    limit: Option<i64>,
+    offset: i64,
+    pool: &PgPool,
+) -> Result<Vec<SubscriberRecord>, anyhow::Error> {
+    let pattern = filter
+        .search
+        .as_deref()
+        .map(|search| format!("%{}%", escape_like_pattern(search)));
+    let subscribers = sqlx::query_as!(
+        SubscriberRecord,
+        r#"
+        SELECT id, email, name, status, subscribed_at
+        FROM subscriptions
+        WHERE
+            ($1::text IS NULL OR status = $1)
+            AND ($2::text IS NULL OR email ILIKE $2 OR name ILIKE $2)
+        ORDER BY subscribed_at DESC, id
+        LIMIT $3 OFFSET $4
+        "#,
+        filter.status,
+        pattern,
+        limit,
+        offset
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to perform a query to list subscribers.")?;
+    Ok(subscribers)
+}
+
+/// Render subscribers as CSV, with a header row.
+///
+/// Subscribers whose stored details are invalid are left out, see
+/// [`SubscriberRecord::validated`].
+pub fn subscribers_to_csv(subscribers: &[SubscriberRecord]) -> String {
+    let mut csv = csv_row(&["email", "name", "status", "subscribed_at"]);
+    for subscriber in subscribers {
+        let Some(validated) = subscriber.validated() else {
+            continue;
+        };
+        csv.push_str(&csv_row(&[
+            validated.email.as_ref(),
+            validated.name.as_ref(),
+            &subscriber.status,
+            &subscriber.subscribed_at.to_rfc3339(),
+        ]));
+    }
+    csv
+}
+
+/// Match `%` and `_` literally in LIKE patterns.
+fn escape_like_pattern(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::escape_like_pattern;
+
+    #[test]
+    fn like_wildcards_are_escaped() {
+        assert_eq!(escape_like_pattern(r"50%_off\"), r"50\%\_off\\");
+    }
+}
@@ -1,46 +1,156 @@
-use axum::Router;
+use std::{collections::HashMap, sync::Arc};
+
+use anyhow::Context;
+
+use axum::{body::Body, Router};
+use http::{
+    header::{AUTHORIZATION, COOKIE, SET_COOKIE},
+    HeaderMap, HeaderName, Request,
+};
+use opentelemetry::{
+    propagation::{Extractor, TextMapPropagator},
+    trace::{TraceError, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Sampler, TracerProvider},
+    Resource,
+};
 use tokio::task::JoinHandle;
 use tower::ServiceBuilder;
 use tower_http::{
     request_id::MakeRequestUuid,
-    trace::{DefaultMakeSpan, DefaultOnResponse, TraceLayer},
+    trace::{DefaultMakeSpan, DefaultOnResponse, MakeSpan, TraceLayer},
     ServiceBuilderExt,
 };
-use tracing::{Level, Subscriber};
+use tracing::{Level, Span, Subscriber};
 use tracing_bunyan_formatter::{BunyanFormattingLayer, JsonStorageLayer};
-use tracing_subscriber::{fmt::MakeWriter, prelude::*, EnvFilter, Registry};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    filter::ParseError,
+    fmt::{self, MakeWriter},
+    prelude::*,
+    reload, EnvFilter, Registry,
+};
+
+use crate::{
+    configuration::{LogFormat, OpenTelemetrySettings},
+    csrf::CSRF_HEADER,
+    error_chain_fmt,
+};
+
+const TRACEPARENT: &str = "traceparent";
 
-/// Sets up a tracing subscriber.
+/// Sets up a tracing subscriber, writing events to `sink` in the given `format`.
+///
+/// Spans are also exported through `tracer_provider`, if given. The returned [`LogFilter`]
+/// changes which events are recorded while the subscriber is in use.
 pub fn get_subscriber<Sink>(
     name: String,
     env_filter: String,
+    format: LogFormat,
     sink: Sink,
-) -> impl Subscriber + Send + Sync
+    tracer_provider: Option<&TracerProvider>,
+) -> (impl Subscriber + Send + Sync, LogFilter)
 where
     Sink: for<'a> MakeWriter<'a> + Send + Sync + 'static,
 {
-    let filter_layer =
-        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(env_filter));
+    let (filter_layer, filter_handle) = reload::Layer::new(filter);
 
-    // --This code uses tracing-subscriber--
-    // let fmt_layer = fmt::layer()
-    //     .compact()
-    //     .with_target(true)
-    //     .with_line_number(true)
-    //     .with_span_events(FmtSpan::NONE)
-    //     .with_writer(sink);
+    let format_layer = match format {
+        LogFormat::Bunyan => JsonStorageLayer
+            .and_then(BunyanFormattingLayer::new(name.clone(), sink))
+            .boxed(),
+        LogFormat::Json => fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_writer(sink)
+            .boxed(),
+        LogFormat::Pretty => fmt::layer().pretty().with_writer(sink).boxed(),
+        LogFormat::Compact => fmt::layer()
+            .compact()
+            .with_target(true)
+            .with_line_number(true)
+            .with_writer(sink)
+            .boxed(),
+    };
+    let opentelemetry_layer = tracer_provider
+        .map(|provider| tracing_opentelemetry::layer().with_tracer(provider.tracer(name)));
 
-    // tracing_subscriber::registry()
-    //     .with(filter_layer)
-    //     .with(fmt_layer)
-    // ----
+    let subscriber = Registry::default()
+        .with(filter_layer)
+        .with(format_layer)
+        .with(opentelemetry_layer);
+    (subscriber, LogFilter(filter_handle))
+}
 
-    let bunyan_format = BunyanFormattingLayer::new(name, sink);
+/// Changes the `EnvFilter` of a running subscriber, e.g. to debug an issue in production.
+#[derive(Clone)]
+pub struct LogFilter(reload::Handle<EnvFilter, Registry>);
 
-    Registry::default()
-        .with(filter_layer)
-        .with(JsonStorageLayer)
-        .with(bunyan_format)
+#[derive(thiserror::Error)]
+pub enum LogFilterError {
+    #[error("The filter is invalid.")]
+    InvalidDirectives(#[source] ParseError),
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for LogFilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl LogFilter {
+    /// The directives currently in effect.
+    pub fn current(&self) -> Result<String, anyhow::Error> {
+        self.0
+            .with_current(|filter| filter.to_string())
+            .context("The subscriber has been dropped.")
+    }
+
+    /// Replace the filter with `directives`, e.g. `info,zero2prod=debug`.
+    pub fn set(&self, directives: &str) -> Result<(), LogFilterError> {
+        let filter = EnvFilter::builder()
+            .parse(directives)
+            .map_err(LogFilterError::InvalidDirectives)?;
+        self.0
+            .reload(filter)
+            .context("Failed to replace the log filter.")?;
+        Ok(())
+    }
+}
+
+/// Build a provider exporting spans to the configured OTLP collector, if there is one.
+///
+/// It must be built inside the Tokio runtime, and shut down before exiting to flush the
+/// spans still buffered.
+pub fn get_tracer_provider(
+    settings: &OpenTelemetrySettings,
+) -> Result<Option<TracerProvider>, TraceError> {
+    let Some(endpoint) = &settings.endpoint else {
+        return Ok(None);
+    };
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(endpoint)
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            settings.sampling_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            settings.service_name.clone(),
+        )]))
+        .build();
+    Ok(Some(provider))
 }
 
 /// Sets the global default subscriber. Should only be called once.
@@ -67,19 +177,199 @@ where
     S: Clone + Send + Sync + 'static,
 {
     fn add_axum_tracing_layer(self) -> Self {
+        // Logged as `Sensitive` instead of their values. Requests are marked before they
+        // reach the trace layer, responses before they leave the router.
+        let sensitive_request_headers: Arc<[HeaderName]> =
+            Arc::new([AUTHORIZATION, COOKIE, HeaderName::from_static(CSRF_HEADER)]);
+        let sensitive_response_headers: Arc<[HeaderName]> = Arc::new([SET_COOKIE]);
         self.layer(
             ServiceBuilder::new()
                 .set_x_request_id(MakeRequestUuid)
+                .sensitive_request_headers(sensitive_request_headers)
                 .layer(
                     TraceLayer::new_for_http()
-                        .make_span_with(
-                            DefaultMakeSpan::new()
+                        .make_span_with(|request: &Request<Body>| {
+                            let span = DefaultMakeSpan::new()
                                 .include_headers(true)
-                                .level(Level::INFO),
-                        )
+                                .level(Level::INFO)
+                                .make_span(request);
+                            // Continue the trace of the caller, if it sent a `traceparent`
+                            span.set_parent(
+                                TraceContextPropagator::new()
+                                    .extract(&HeaderExtractor(request.headers())),
+                            );
+                            span
+                        })
                         .on_response(DefaultOnResponse::new().include_headers(true)),
                 )
+                .sensitive_response_headers(sensitive_response_headers)
                 .propagate_x_request_id(),
         )
     }
 }
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
+
+/// The W3C `traceparent` of the current span, to be stored with work that outlives it.
+///
+/// `None` when spans are not exported.
+pub fn current_traceparent() -> Option<String> {
+    let mut carrier = HashMap::new();
+    TraceContextPropagator::new().inject_context(&Span::current().context(), &mut carrier);
+    carrier.remove(TRACEPARENT)
+}
+
+/// Make `span` part of the trace `traceparent` was taken from, as a child of its span.
+pub fn set_remote_parent(span: &Span, traceparent: &str) {
+    let carrier = HashMap::from([(TRACEPARENT.to_string(), traceparent.to_string())]);
+    span.set_parent(TraceContextPropagator::new().extract(&carrier));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::{Arc, Mutex},
+    };
+
+    use axum::{body::Body, routing::get, Router};
+    use http::{header::SET_COOKIE, Request};
+    use opentelemetry_sdk::trace::TracerProvider;
+    use tower::ServiceExt;
+
+    use super::{current_traceparent, get_subscriber, set_remote_parent, RouterExt};
+    use crate::configuration::LogFormat;
+
+    /// Collects everything the subscriber writes, to look for what must not be logged.
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn trace_context_survives_a_round_trip_through_a_traceparent() {
+        let provider = TracerProvider::builder().build();
+        let (subscriber, _) = get_subscriber(
+            "test".into(),
+            "info".into(),
+            LogFormat::Bunyan,
+            std::io::sink,
+            Some(&provider),
+        );
+        let traceparent = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+        let propagated = tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("queued work");
+            set_remote_parent(&span, traceparent);
+            span.in_scope(current_traceparent)
+        })
+        .unwrap();
+
+        // Same trace, but the queued work is a span of its own
+        assert!(propagated.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        assert_ne!(propagated, traceparent);
+    }
+
+    #[test]
+    fn nothing_is_propagated_when_spans_are_not_exported() {
+        let (subscriber, _) = get_subscriber(
+            "test".into(),
+            "info".into(),
+            LogFormat::Bunyan,
+            std::io::sink,
+            None,
+        );
+
+        let propagated = tracing::subscriber::with_default(subscriber, || {
+            tracing::info_span!("queued work").in_scope(current_traceparent)
+        });
+
+        assert_eq!(propagated, None);
+    }
+
+    #[test]
+    fn the_log_filter_can_be_changed_at_runtime() {
+        let (subscriber, log_filter) = get_subscriber(
+            "test".into(),
+            "info".into(),
+            LogFormat::Compact,
+            std::io::sink,
+            None,
+        );
+
+        tracing::subscriber::with_default(subscriber, || {
+            assert!(!tracing::enabled!(tracing::Level::DEBUG));
+            log_filter.set("info,zero2prod=debug").unwrap();
+            assert!(tracing::enabled!(tracing::Level::DEBUG));
+            assert_eq!(log_filter.current().unwrap(), "zero2prod=debug,info");
+            assert!(log_filter.set("zero2prod=loud").is_err());
+        });
+    }
+
+    #[tokio::test]
+    async fn credentials_in_headers_are_not_logged() {
+        let logs = CapturedLogs::default();
+        let (subscriber, _) = get_subscriber(
+            "test".into(),
+            "debug".into(),
+            LogFormat::Bunyan,
+            {
+                let logs = logs.clone();
+                move || logs.clone()
+            },
+            None,
+        );
+        let _guard = tracing::subscriber::set_default(subscriber);
+        let router = Router::new()
+            .route(
+                "/",
+                get(|| async { [(SET_COOKIE, "session=set-cookie-secret")] }),
+            )
+            .add_axum_tracing_layer();
+
+        let response = router
+            .oneshot(
+                Request::get("/")
+                    .header("Authorization", "Bearer api-token-secret")
+                    .header("Cookie", "session=cookie-secret")
+                    .header("X-CSRF-Token", "csrf-secret")
+                    .header("User-Agent", "test-agent")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status().as_u16(), 200);
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        // Other headers are still there
+        assert!(logs.contains("test-agent"));
+        for secret in [
+            "api-token-secret",
+            "cookie-secret",
+            "csrf-secret",
+            "set-cookie-secret",
+        ] {
+            assert!(!logs.contains(secret), "{} was logged", secret);
+        }
+    }
+}
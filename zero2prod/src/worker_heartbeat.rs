@@ -0,0 +1,75 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+
+/// How a background worker checks in, so readiness checks can tell whether it is alive.
+#[derive(Clone, Copy, Debug)]
+pub struct Heartbeat {
+    pub worker_name: &'static str,
+    /// How often the worker beats while it is healthy.
+    pub interval: Duration,
+}
+
+impl Heartbeat {
+    /// A worker that missed this many beats in a row is considered stalled.
+    const MISSED_BEATS_TOLERATED: u32 = 3;
+
+    pub fn is_stale(&self, last_beat_at: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let silence = (now - last_beat_at).to_std().unwrap_or_default();
+        silence > self.interval * Self::MISSED_BEATS_TOLERATED
+    }
+
+    #[tracing::instrument(name = "Record worker heartbeat", skip(pool))]
+    pub async fn record(&self, pool: &PgPool) -> Result<(), anyhow::Error> {
+        sqlx::query!(
+            r#"
+            INSERT INTO worker_heartbeats (worker_name, last_beat_at)
+            VALUES ($1, now())
+            ON CONFLICT (worker_name) DO UPDATE SET last_beat_at = now()
+            "#,
+            self.worker_name
+        )
+        .execute(pool)
+        .await
+        .context("Failed to record a worker heartbeat.")?;
+        Ok(())
+    }
+
+    /// When the worker last checked in, if ever.
+    pub async fn last_beat_at(
+        &self,
+        pool: &PgPool,
+    ) -> Result<Option<DateTime<Utc>>, anyhow::Error> {
+        let last_beat_at = sqlx::query_scalar!(
+            r#"SELECT last_beat_at FROM worker_heartbeats WHERE worker_name = $1"#,
+            self.worker_name
+        )
+        .fetch_optional(pool)
+        .await
+        .context("Failed to perform a query to retrieve a worker heartbeat.")?;
+        Ok(last_beat_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::Utc;
+
+    use super::Heartbeat;
+
+    #[test]
+    fn a_worker_is_stale_after_missing_several_beats() {
+        let heartbeat = Heartbeat {
+            worker_name: "test",
+            interval: Duration::from_secs(10),
+        };
+        let now = Utc::now();
+
+        assert!(!heartbeat.is_stale(now - chrono::Duration::seconds(25), now));
+        assert!(heartbeat.is_stale(now - chrono::Duration::seconds(31), now));
+    }
+}
@@ -0,0 +1,202 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::{
+    helpers::{another_client, csrf_token_for, login_with, spawn_app, TestUser},
+    login::assert_is_redirect_to,
+};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_users() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_users().await;
+    let invite_response = app
+        .post_invite_user(&serde_json::json!({
+            "username": "ursula",
+            "email": "ursula@example.com",
+            "role": "editor",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    assert_is_redirect_to(&invite_response, "/login");
+}
+
+#[tokio::test]
+async fn an_invited_user_can_set_a_password_and_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Invite a new user
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": "ursula",
+            "email": "ursula@example.com",
+            "role": "editor",
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("An invitation has been sent to ursula@example.com."));
+
+    // Act - Part 2 - Follow the invitation link and choose a password
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let invitation_link = app.get_confirmation_links(email_request).html;
+    let client = another_client();
+    let html_page = client
+        .get(invitation_link.clone())
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("ursula"));
+
+    let token = invitation_link
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned();
+    let password = Uuid::new_v4().to_string();
+    let response = client
+        .post(format!("{}/invitations/accept", &app.address))
+        .form(&serde_json::json!({
+            "token": &token,
+            "password": &password,
+            "password_check": &password,
+        }))
+        .header("X-CSRF-Token", csrf_token_for(&client, &app).await)
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Log in as the new user
+    let new_user = TestUser {
+        user_id: Uuid::nil(),
+        username: "ursula".into(),
+        password,
+        email: "ursula@example.com".into(),
+        role: "editor",
+    };
+    let response = login_with(&client, &app, &new_user).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // An invitation can only be used once
+    let response = client.get(invitation_link).send().await.unwrap();
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn inviting_an_existing_username_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .post_invite_user(&serde_json::json!({
+            "username": &app.test_user.username,
+            "email": "someone-else@example.com",
+            "role": "editor",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("That username is already taken."));
+}
+
+#[tokio::test]
+async fn a_disabled_user_is_logged_out_and_cannot_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    let other_client = another_client();
+    login_with(&other_client, &app, &other_user).await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.post_user_action(other_user.user_id, "disable").await;
+    assert_is_redirect_to(&response, "/admin/users");
+
+    // Assert
+    let response = other_client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+    let response = login_with(&other_client, &app, &other_user).await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Re-enabling restores access
+    app.post_user_action(other_user.user_id, "enable").await;
+    let response = login_with(&other_client, &app, &other_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_last_active_owner_cannot_be_disabled_or_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    // Take everybody else out of service so the test user is the only active owner
+    sqlx::query!(
+        "UPDATE users SET disabled = true WHERE user_id != $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Disable
+    let response = app.post_user_action(app.test_user.user_id, "disable").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("At least one active owner must remain."));
+
+    // Act - Part 2 - Delete
+    let response = app.post_user_action(app.test_user.user_id, "delete").await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("At least one active owner must remain."));
+}
+
+#[tokio::test]
+async fn a_user_can_be_deleted() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.post_user_action(other_user.user_id, "delete").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("The user has been deleted."));
+    assert!(!html_page.contains(&other_user.username));
+}
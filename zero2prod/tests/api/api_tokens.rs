@@ -0,0 +1,186 @@
+use uuid::Uuid;
+
+use crate::{
+    helpers::{another_client, csrf_token_for, login_with, spawn_app, TestUser},
+    login::assert_is_redirect_to,
+};
+
+pub async fn assert_is_json_error(response: reqwest::Response, status: u16, code: &str) {
+    assert_eq!(response.status().as_u16(), status);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "application/problem+json"
+    );
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], status);
+    assert_eq!(body["code"], code);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_api_tokens() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/api-tokens", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_new_token_authenticates_api_requests() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app
+        .create_api_token(&["newsletters:read", "subscribers:read"])
+        .await;
+
+    // Act
+    let response = app.get_api("token", Some(&token)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["name"], "CMS integration");
+    assert_eq!(body["user_id"], app.test_user.user_id.to_string());
+    assert_eq!(
+        body["scopes"],
+        serde_json::json!(["newsletters:read", "subscribers:read"])
+    );
+}
+
+#[tokio::test]
+async fn the_token_is_stored_hashed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:read"]).await;
+
+    // Act
+    let stored = sqlx::query!("SELECT token_hash FROM api_tokens")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Assert
+    assert_ne!(stored.token_hash, token);
+    assert!(!stored.token_hash.contains(&token));
+}
+
+#[tokio::test]
+async fn requests_without_a_valid_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    for token in [None, Some("z2p_not-a-real-token")] {
+        // Act
+        let response = app.get_api("token", token).await;
+
+        // Assert
+        assert_eq!(
+            response.headers().get("WWW-Authenticate").unwrap(),
+            "Bearer"
+        );
+        assert_is_json_error(response, 401, "unauthorized").await;
+    }
+}
+
+#[tokio::test]
+async fn a_revoked_token_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:read"]).await;
+    let html_page = app
+        .api_client
+        .get(format!("{}/admin/api-tokens", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let id_regex = regex::Regex::new(r#"/admin/api-tokens/([0-9a-f-]+)/revoke"#).unwrap();
+    let api_token_id: Uuid = id_regex.captures(&html_page).unwrap()[1].parse().unwrap();
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/api-tokens/{}/revoke",
+            &app.address, api_token_id
+        ))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    assert_is_json_error(
+        app.get_api("token", Some(&token)).await,
+        401,
+        "unauthorized",
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn viewers_cannot_create_tokens_with_write_scopes() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = TestUser::generate_with_role("viewer");
+    viewer.store(&app.db_pool).await;
+    let client = another_client();
+    login_with(&client, &app, &viewer).await;
+
+    // Act
+    let response = client
+        .post(format!("{}/admin/api-tokens", &app.address))
+        .form(&[("name", "publisher"), ("scopes", "newsletters:write")])
+        .header("X-CSRF-Token", csrf_token_for(&client, &app).await)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/api-tokens");
+    let count = sqlx::query!(r#"SELECT count(*) AS "count!" FROM api_tokens"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap()
+        .count;
+    assert_eq!(count, 0);
+}
+
+#[tokio::test]
+async fn tokens_of_disabled_users_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:read"]).await;
+
+    // Act
+    sqlx::query!(
+        "UPDATE users SET disabled = true WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Assert
+    assert_is_json_error(
+        app.get_api("token", Some(&token)).await,
+        401,
+        "unauthorized",
+    )
+    .await;
+}
@@ -0,0 +1,410 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::{
+    api_tokens::assert_is_json_error,
+    helpers::{another_client, csrf_token_for, login_with, spawn_app, TestApp, TestUser},
+    newsletters::newsletter_helpers::{create_confirmed_subscriber, create_unconfirmed_subscriber},
+};
+
+const ALL_SCOPES: [&str; 3] = ["newsletters:read", "newsletters:write", "subscribers:read"];
+
+fn new_issue() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+    })
+}
+
+/// Log the test user in and create an API token with `scopes`.
+async fn token_with_scopes(app: &TestApp, scopes: &[&str]) -> String {
+    app.test_user.login(app).await;
+    app.create_api_token(scopes).await
+}
+
+/// Create a draft issue through the API and return its id.
+async fn create_draft(app: &TestApp, token: &str) -> String {
+    let response = app.post_api("issues", token, &new_issue()).await;
+    assert_eq!(response.status().as_u16(), 201);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    issue["id"].as_str().unwrap().to_owned()
+}
+
+#[tokio::test]
+async fn created_issues_are_drafts_until_published() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &ALL_SCOPES).await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act - Part 1 - Create a draft
+    let issue_id = create_draft(&app, &token).await;
+
+    // Assert - Part 1
+    let issues: serde_json::Value = app
+        .get_api("issues", Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(issues[0]["id"], issue_id);
+    assert_eq!(issues[0]["title"], "Newsletter title");
+    assert!(issues[0]["published_at"].is_null());
+
+    // Act - Part 2 - Publish it
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app
+        .post_api(
+            &format!("issues/{}/publish", issue_id),
+            &token,
+            &serde_json::json!({}),
+        )
+        .await;
+
+    // Assert - Part 2
+    assert_eq!(response.status().as_u16(), 200);
+    let issue: serde_json::Value = response.json().await.unwrap();
+    assert!(issue["published_at"].is_string());
+    app.dispatch_all_pending_emails().await;
+}
+
+#[tokio::test]
+async fn an_issue_cannot_be_published_twice() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &ALL_SCOPES).await;
+    let issue_id = create_draft(&app, &token).await;
+    let publish_path = format!("issues/{}/publish", issue_id);
+    app.post_api(&publish_path, &token, &serde_json::json!({}))
+        .await;
+
+    // Act
+    let response = app
+        .post_api(&publish_path, &token, &serde_json::json!({}))
+        .await;
+
+    // Assert
+    assert_is_json_error(response, 409, "conflict").await;
+}
+
+#[tokio::test]
+async fn unknown_issues_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &ALL_SCOPES).await;
+    let issue_id = Uuid::new_v4();
+
+    // Act
+    let get = app
+        .get_api(&format!("issues/{}", issue_id), Some(&token))
+        .await;
+    let publish = app
+        .post_api(
+            &format!("issues/{}/publish", issue_id),
+            &token,
+            &serde_json::json!({}),
+        )
+        .await;
+
+    // Assert
+    assert_is_json_error(get, 404, "not_found").await;
+    assert_is_json_error(publish, 404, "not_found").await;
+}
+
+#[tokio::test]
+async fn invalid_issues_are_rejected_with_json_errors() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &ALL_SCOPES).await;
+    let test_cases = [
+        (
+            serde_json::json!({"title": "No content"}),
+            400,
+            "bad_request",
+        ),
+        (
+            serde_json::json!({"title": " ", "text_content": "a", "html_content": "b"}),
+            422,
+            "validation_failed",
+        ),
+        (
+            serde_json::json!({"title": "Empty", "text_content": "", "html_content": "b"}),
+            422,
+            "validation_failed",
+        ),
+    ];
+
+    for (body, status, code) in test_cases {
+        // Act
+        let response = app.post_api("issues", &token, &body).await;
+
+        // Assert
+        assert_is_json_error(response, status, code).await;
+    }
+}
+
+#[tokio::test]
+async fn delivery_stats_track_the_queue() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &ALL_SCOPES).await;
+    create_confirmed_subscriber(&app).await;
+    create_confirmed_subscriber(&app).await;
+    let issue_id = create_draft(&app, &token).await;
+    app.post_api(
+        &format!("issues/{}/publish", issue_id),
+        &token,
+        &serde_json::json!({}),
+    )
+    .await;
+    let stats_path = format!("issues/{}/stats", issue_id);
+
+    // Act - Part 1 - Before delivery
+    let stats: serde_json::Value = app
+        .get_api(&stats_path, Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - Part 1
+    assert_eq!(
+        stats,
+        serde_json::json!({"recipients": 2, "delivered": 0, "failed": 0, "pending": 2})
+    );
+
+    // Act - Part 2 - After delivery
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.dispatch_all_pending_emails().await;
+    let stats: serde_json::Value = app
+        .get_api(&stats_path, Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert - Part 2
+    assert_eq!(
+        stats,
+        serde_json::json!({"recipients": 2, "delivered": 2, "failed": 0, "pending": 0})
+    );
+}
+
+#[tokio::test]
+async fn stats_of_issues_published_before_they_were_kept_are_unknown() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &["newsletters:read"]).await;
+    let issue_id = Uuid::new_v4();
+    sqlx::query!(
+        r#"
+        INSERT INTO newsletter_issues (
+            newsletter_issue_id, title, text_content, html_content, published_at,
+            recipient_count, delivered_count, failed_count
+        )
+        VALUES ($1, 'Old issue', 'Text', '<p>HTML</p>', now(), NULL, NULL, NULL)
+        "#,
+        issue_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let stats: serde_json::Value = app
+        .get_api(&format!("issues/{}/stats", issue_id), Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        stats,
+        serde_json::json!({"recipients": null, "delivered": null, "failed": null, "pending": 0})
+    );
+}
+
+#[tokio::test]
+async fn subscribers_the_form_would_reject_are_left_out() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &["subscribers:read"]).await;
+    create_confirmed_subscriber(&app).await;
+    for (email, name) in [
+        ("not-an-email", "Valid Name"),
+        ("valid@example.com", "<script>"),
+    ] {
+        sqlx::query!(
+            r#"
+            INSERT INTO subscriptions (id, email, name, subscribed_at, status)
+            VALUES ($1, $2, $3, now(), 'confirmed')
+            "#,
+            Uuid::new_v4(),
+            email,
+            name
+        )
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    }
+
+    // Act
+    let listed: serde_json::Value = app
+        .get_api("subscribers", Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let exported = app
+        .get_api("subscribers/export", Some(&token))
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(listed.as_array().unwrap().len(), 1);
+    assert_ne!(listed[0]["email"], "not-an-email");
+    assert_eq!(exported.lines().count(), 2);
+    assert!(!exported.contains("<script>"));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_filtered_and_searched() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &["subscribers:read"]).await;
+    create_confirmed_subscriber(&app).await;
+    create_unconfirmed_subscriber(&app).await;
+    let everyone: serde_json::Value = app
+        .get_api("subscribers", Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let email = everyone[0]["email"].as_str().unwrap().to_owned();
+
+    // Act
+    let confirmed: serde_json::Value = app
+        .get_api("subscribers?status=confirmed", Some(&token))
+        .await
+        .json()
+        .await
+        .unwrap();
+    let found: serde_json::Value = app
+        .get_api(
+            &format!("subscribers?q={}", email.to_uppercase()),
+            Some(&token),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+    let invalid_status = app
+        .get_api("subscribers?status=everyone", Some(&token))
+        .await;
+
+    // Assert
+    assert_eq!(everyone.as_array().unwrap().len(), 2);
+    assert_eq!(confirmed.as_array().unwrap().len(), 1);
+    assert_eq!(confirmed[0]["status"], "confirmed");
+    assert_eq!(found.as_array().unwrap().len(), 1);
+    assert_eq!(found[0]["email"], email);
+    assert_is_json_error(invalid_status, 400, "bad_request").await;
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_as_csv() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &["subscribers:read"]).await;
+    create_confirmed_subscriber(&app).await;
+
+    // Act
+    let response = app
+        .get_api("subscribers/export?status=confirmed", Some(&token))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["Content-Type"],
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text().await.unwrap();
+    let mut lines = csv.lines();
+    assert_eq!(lines.next(), Some("email,name,status,subscribed_at"));
+    assert!(lines.next().unwrap().contains(",confirmed,"));
+    assert_eq!(lines.next(), None);
+}
+
+#[tokio::test]
+async fn tokens_only_reach_the_endpoints_of_their_scopes() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = token_with_scopes(&app, &["newsletters:read"]).await;
+
+    // Act
+    let list = app.get_api("issues", Some(&token)).await;
+    let create = app.post_api("issues", &token, &new_issue()).await;
+    let subscribers = app.get_api("subscribers", Some(&token)).await;
+
+    // Assert
+    assert_eq!(list.status().as_u16(), 200);
+    assert_is_json_error(create, 403, "forbidden").await;
+    assert_is_json_error(subscribers, 403, "forbidden").await;
+}
+
+#[tokio::test]
+async fn write_scopes_stop_working_when_the_owner_is_demoted() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    let client = another_client();
+    login_with(&client, &app, &editor).await;
+    let html_page = client
+        .post(format!("{}/admin/api-tokens", &app.address))
+        .form(&[("name", "publisher"), ("scopes", "newsletters:write")])
+        .header("X-CSRF-Token", csrf_token_for(&client, &app).await)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    let token = regex::Regex::new(r"z2p_[A-Za-z0-9]+")
+        .unwrap()
+        .find(&html_page)
+        .unwrap()
+        .as_str()
+        .to_owned();
+
+    // Act
+    sqlx::query!(
+        "UPDATE users SET role = 'viewer' WHERE user_id = $1",
+        editor.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let response = app.post_api("issues", &token, &new_issue()).await;
+
+    // Assert
+    assert_is_json_error(response, 403, "forbidden").await;
+}
@@ -0,0 +1,150 @@
+use crate::{
+    helpers::{another_client, login_with, spawn_app, TestUser},
+    login::assert_is_redirect_to,
+};
+
+#[tokio::test]
+async fn logins_are_recorded_in_the_audit_log() {
+    // Arrange
+    let app = spawn_app().await;
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    }))
+    .await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let html_page = app
+        .get_audit("audit", &[("actor", &app.test_user.username)])
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(html_page.contains("login_succeeded"));
+    // The failed attempt has no actor, only the username that was tried
+    assert!(!html_page.contains("<td>login_failed</td>"));
+    let html_page = app
+        .get_audit("audit", &[("action", "login_failed")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains(&format!("username:{}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn audit_events_carry_the_ip_and_request_id() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let row = sqlx::query!(
+        "SELECT ip_address, request_id FROM audit_events WHERE actor_user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(row.ip_address.as_deref(), Some("127.0.0.1"));
+    assert!(row.request_id.is_some());
+}
+
+#[tokio::test]
+async fn admin_mutations_are_recorded() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // Act
+    app.post_user_action(editor.user_id, "disable").await;
+    app.post_logout().await;
+
+    // Assert
+    app.test_user.login(&app).await;
+    let html_page = app
+        .get_audit("audit", &[("actor", &app.test_user.username)])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("user_disabled"));
+    assert!(html_page.contains(&format!("user:{}", editor.user_id)));
+    assert!(html_page.contains("logged_out"));
+}
+
+#[tokio::test]
+async fn the_audit_log_can_be_exported_as_csv() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .get_audit(
+            "audit.csv",
+            &[
+                ("actor", app.test_user.username.as_str()),
+                ("action", "login_succeeded"),
+            ],
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.headers()["content-type"],
+        "text/csv; charset=utf-8"
+    );
+    let csv = response.text().await.unwrap();
+    let lines: Vec<&str> = csv.lines().collect();
+    assert_eq!(
+        lines[0],
+        "occurred_at,actor,action,target,ip_address,request_id"
+    );
+    assert_eq!(lines.len(), 2);
+    assert!(lines[1].contains(&format!(",{},login_succeeded,", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn an_invalid_filter_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.get_audit("audit", &[("from", "yesterday")]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    let problem: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(problem["detail"], "yesterday is not a valid date");
+}
+
+#[tokio::test]
+async fn only_owners_can_see_the_audit_log() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    let client = another_client();
+    let response = login_with(&client, &app, &editor).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act
+    let response = client
+        .get(format!("{}/admin/audit.csv", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
@@ -0,0 +1,126 @@
+use secrecy::Secret;
+use zero2prod::{
+    authentication::{bootstrap_admin, PasswordHashing, PasswordPolicy},
+    configuration::{get_configuration, BootstrapAdminSettings},
+};
+
+use crate::{
+    helpers::{spawn_app, TestApp},
+    login::assert_is_redirect_to,
+};
+
+const PASSWORD: &str = "Tangerine-Lighthouse-Quartz-1987";
+
+async fn bootstrap(app: &TestApp, password: Option<&str>) -> Option<uuid::Uuid> {
+    let configuration = get_configuration().unwrap();
+    let settings = BootstrapAdminSettings {
+        username: "first-owner".into(),
+        email: "first-owner@example.com".into(),
+        password: password.map(|p| Secret::new(p.to_string())),
+    };
+    bootstrap_admin(
+        &settings,
+        &PasswordPolicy::new(configuration.password_policy),
+        &PasswordHashing::new(&configuration.password_hashing).unwrap(),
+        &app.db_pool,
+    )
+    .await
+    .unwrap()
+}
+
+#[tokio::test]
+async fn the_seed_admin_is_disabled() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let disabled = sqlx::query_scalar!("SELECT disabled FROM users WHERE username = 'admin'")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(disabled);
+}
+
+async fn delete_all_but_the_seed_admin(app: &TestApp) {
+    sqlx::query!("DELETE FROM users WHERE username <> 'admin'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_first_owner_is_created_on_a_new_install() {
+    // Arrange
+    let app = spawn_app().await;
+    delete_all_but_the_seed_admin(&app).await;
+
+    // Act - Part 1 - Bootstrap
+    let user_id = bootstrap(&app, Some(PASSWORD)).await;
+
+    // Assert - Part 1
+    let user_id = user_id.unwrap();
+    let target =
+        sqlx::query_scalar!("SELECT target FROM audit_events WHERE action = 'user_created'")
+            .fetch_one(&app.db_pool)
+            .await
+            .unwrap();
+    assert_eq!(target, Some(format!("user:{} (owner)", user_id)));
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "first-owner",
+            "password": PASSWORD,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+
+    // Act - Part 2 - Later starts leave the users alone
+    let user_id = bootstrap(&app, Some(PASSWORD)).await;
+
+    // Assert - Part 2
+    assert_eq!(user_id, None);
+}
+
+#[tokio::test]
+async fn nobody_is_created_while_there_are_users_even_if_all_are_disabled() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!("UPDATE users SET disabled = true")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let user_id = bootstrap(&app, Some(PASSWORD)).await;
+
+    // Assert
+    assert_eq!(user_id, None);
+    let count = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM users"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn nobody_is_created_while_anybody_can_log_in_or_without_a_password() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act - Part 1 - The test user can log in
+    let created_with_active_user = bootstrap(&app, Some(PASSWORD)).await;
+
+    // Act - Part 2 - A new install, but no password is configured
+    delete_all_but_the_seed_admin(&app).await;
+    let created_without_password = bootstrap(&app, None).await;
+
+    // Assert
+    assert_eq!(created_with_active_user, None);
+    assert_eq!(created_without_password, None);
+    let count = sqlx::query_scalar!(r#"SELECT count(*) AS "count!" FROM users"#)
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+}
@@ -1,6 +1,23 @@
 use uuid::Uuid;
 
-use crate::{helpers::spawn_app, login::assert_is_redirect_to};
+use crate::{
+    helpers::{spawn_app, TestApp, BREACHED_PASSWORD},
+    login::assert_is_redirect_to,
+};
+
+/// Log in as the test user, try to change to `new_password` and return the resulting page.
+async fn change_password_page(app: &TestApp, new_password: &str) -> String {
+    app.test_user.login(app).await;
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": new_password,
+            "new_password_check": new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/password");
+    app.get_change_password_html().await
+}
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_see_the_change_password_form() {
@@ -183,3 +200,40 @@ async fn changing_password_works() {
     let response = app.post_login(&login_body).await;
     assert_is_redirect_to(&response, "/admin/dashboard");
 }
+
+#[tokio::test]
+async fn new_password_must_not_be_easy_to_guess() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let html_page = change_password_page(&app, "Password1234!").await;
+
+    // Assert
+    assert!(html_page.contains("The new password is too easy to guess."));
+}
+
+#[tokio::test]
+async fn new_password_must_not_have_been_breached() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let html_page = change_password_page(&app, BREACHED_PASSWORD).await;
+
+    // Assert
+    assert!(html_page.contains("The new password has appeared in a data breach"));
+}
+
+#[tokio::test]
+async fn new_password_must_not_contain_the_username() {
+    // Arrange
+    let app = spawn_app().await;
+    let new_password = format!("{}-and-more", app.test_user.username);
+
+    // Act
+    let html_page = change_password_page(&app, &new_password).await;
+
+    // Assert
+    assert!(html_page.contains("The new password must not contain your username or email address."));
+}
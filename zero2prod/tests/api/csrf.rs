@@ -0,0 +1,95 @@
+use crate::{
+    helpers::{another_client, csrf_token_for, spawn_app},
+    login::assert_is_redirect_to,
+};
+
+#[tokio::test]
+async fn forms_without_a_csrf_token_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.get_login_html().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("The form has expired. Please reload the page and try again."));
+    assert_eq!(
+        app.get_admin_dashboard().await.status().as_u16(),
+        303,
+        "The user must not have been logged in"
+    );
+}
+
+#[tokio::test]
+async fn the_csrf_token_of_another_session_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let attacker = another_client();
+    let attackers_token = csrf_token_for(&attacker, &app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/admin/logout", &app.address))
+        .form(&[("csrf_token", attackers_token.as_str())])
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+    assert_eq!(app.get_admin_dashboard().await.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn the_csrf_token_is_accepted_as_a_form_field() {
+    // Arrange
+    let app = spawn_app().await;
+    let csrf_token = app.csrf_token().await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!("{}/login", &app.address))
+        .form(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password,
+            "csrf_token": csrf_token,
+        }))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn rendered_forms_carry_the_csrf_token() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let csrf_token = app.csrf_token().await;
+
+    // Act
+    let html_page = app.get_admin_dashboard_html().await;
+
+    // Assert
+    assert!(html_page.contains(&format!(
+        r#"<input type="hidden" name="csrf_token" value="{}">"#,
+        csrf_token
+    )));
+}
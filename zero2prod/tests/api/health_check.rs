@@ -1,4 +1,17 @@
-use crate::helpers::spawn_app;
+use zero2prod::issue_delivery_worker;
+
+use crate::helpers::{spawn_app, TestApp};
+
+async fn get_ready(app: &TestApp) -> (u16, serde_json::Value) {
+    let response = app
+        .api_client
+        .get(format!("{}/ready", app.address))
+        .send()
+        .await
+        .expect("failed to execute request");
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
 
 #[tokio::test]
 async fn health_check_works() -> Result<(), Box<dyn std::error::Error>> {
@@ -18,3 +31,69 @@ async fn health_check_works() -> Result<(), Box<dyn std::error::Error>> {
     assert_eq!(Some(0), response.content_length());
     Ok(())
 }
+
+#[tokio::test]
+async fn ready_reports_every_component() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let (status, report) = get_ready(&app).await;
+
+    // Assert
+    assert_eq!(status, 200);
+    assert_eq!(report["status"], "ok");
+    for component in ["database", "redis", "migrations"] {
+        assert_eq!(report["components"][component]["status"], "ok");
+        assert!(report["components"][component]["latency_ms"].is_number());
+    }
+    // The workers do not run in the tests
+    assert_eq!(report["workers"]["issue_delivery"]["status"], "missing");
+    assert_eq!(
+        report["workers"]["idempotency_cleanup"]["status"],
+        "missing"
+    );
+}
+
+#[tokio::test]
+async fn ready_reports_worker_heartbeats() {
+    // Arrange
+    let app = spawn_app().await;
+    issue_delivery_worker::HEARTBEAT
+        .record(&app.db_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let (status, report) = get_ready(&app).await;
+
+    // Assert
+    assert_eq!(status, 200);
+    assert_eq!(report["workers"]["issue_delivery"]["status"], "ok");
+    assert!(report["workers"]["issue_delivery"]["last_beat_at"].is_string());
+}
+
+#[tokio::test]
+async fn ready_fails_while_migrations_are_pending() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query(
+        "DELETE FROM _sqlx_migrations WHERE version = (SELECT max(version) FROM _sqlx_migrations)",
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let (status, report) = get_ready(&app).await;
+
+    // Assert
+    assert_eq!(status, 503);
+    assert_eq!(report["status"], "failing");
+    assert_eq!(report["components"]["migrations"]["status"], "failing");
+    assert_eq!(
+        report["components"]["migrations"]["detail"],
+        "Migrations not applied yet: 1."
+    );
+    assert_eq!(report["components"]["database"]["status"], "ok");
+}
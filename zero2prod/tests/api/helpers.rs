@@ -5,32 +5,51 @@ use sqlx::{Connection, Executor, PgConnection, PgPool};
 use uuid::Uuid;
 use wiremock::MockServer;
 use zero2prod::{
-    configuration::{get_configuration, DatabaseSettings},
+    configuration::{get_configuration, DatabaseSettings, LogFormat, Settings},
     email_client::EmailClient,
     idempotency_remover_worker::remove_old_idempotency_entries,
     issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    shutdown::Shutdown,
     startup::{get_db_pool, Application},
-    telemetry::{get_subscriber, init_subscriber},
+    telemetry::{get_subscriber, init_subscriber, LogFilter},
 };
 
-static TRACING: Lazy<()> = Lazy::new(|| {
+/// The filter of the test subscriber, shared by every test application.
+static TRACING: Lazy<LogFilter> = Lazy::new(|| {
     if std::env::var("TEST_LOG").is_ok() {
-        let subscriber = get_subscriber(
+        let (subscriber, log_filter) = get_subscriber(
             "test".into(),
             "zero2prod=debug,info".into(),
+            LogFormat::Bunyan,
             std::io::stdout,
+            None,
         );
         init_subscriber(subscriber);
+        log_filter
     } else {
-        let subscriber =
-            get_subscriber("test".into(), "zero2prod=debug,info".into(), std::io::sink);
+        let (subscriber, log_filter) = get_subscriber(
+            "test".into(),
+            "zero2prod=debug,info".into(),
+            LogFormat::Bunyan,
+            std::io::sink,
+            None,
+        );
         init_subscriber(subscriber);
+        log_filter
     }
 });
 
+/// Strong enough for the password policy, but listed in the test breached passwords file.
+pub const BREACHED_PASSWORD: &str = "Leaked-but-random-9f8e7d";
+
 pub async fn spawn_app() -> TestApp {
+    spawn_app_with(|_| {}).await
+}
+
+/// Spawn the application with the test configuration adjusted by `configure`.
+pub async fn spawn_app_with(configure: impl FnOnce(&mut Settings)) -> TestApp {
     // Set up subscriber for logging, only first time per run. Other times use existing subscriber.
-    Lazy::force(&TRACING);
+    let log_filter = Lazy::force(&TRACING).clone();
 
     let email_server = MockServer::start().await;
     let configuration = {
@@ -41,6 +60,20 @@ pub async fn spawn_app() -> TestApp {
         // Use a random OS port
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Let tests submit the subscription form as soon as it has been rendered
+        c.subscriptions.form_token_min_age_seconds = 0;
+        // Keep rate limit counters from leaking between tests sharing a Redis instance
+        c.rate_limit.key_prefix = format!("rate_limit:{}", Uuid::new_v4());
+        c.login_protection.key_prefix = format!("login_protection:{}", Uuid::new_v4());
+        // A handful of made up breached passwords, see `BREACHED_PASSWORD`
+        c.password_policy.breached_passwords_directory = Some(
+            concat!(
+                env!("CARGO_MANIFEST_DIR"),
+                "/tests/fixtures/breached_passwords"
+            )
+            .to_string(),
+        );
+        configure(&mut c);
         c
     };
 
@@ -48,12 +81,13 @@ pub async fn spawn_app() -> TestApp {
     configure_database(&configuration.database).await;
 
     // Start the server
-    let app = Application::build(configuration.clone())
+    let app = Application::build(configuration.clone(), log_filter)
         .await
         .expect("Failed to build application");
     let port = app.port();
     let address = format!("http://127.0.0.1:{}", app.port());
-    tokio::spawn(app.run_until_stopped());
+    let shutdown = configuration.application.shutdown();
+    tokio::spawn(app.run_until_stopped(shutdown.clone()));
 
     let api_client = reqwest::Client::builder()
         .redirect(reqwest::redirect::Policy::none())
@@ -69,6 +103,7 @@ pub async fn spawn_app() -> TestApp {
         test_user: TestUser::generate(),
         api_client,
         email_client: configuration.email_client.client(),
+        shutdown,
     };
 
     test_app.test_user.store(&test_app.db_pool).await;
@@ -76,6 +111,51 @@ pub async fn spawn_app() -> TestApp {
     test_app
 }
 
+/// A client with its own cookie jar, so a second user or device can be logged in alongside the test user.
+pub fn another_client() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .build()
+        .unwrap()
+}
+
+/// The CSRF token of `client`'s session, as embedded in the login form.
+pub async fn csrf_token_for(client: &reqwest::Client, app: &TestApp) -> String {
+    let html_page = client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+        .text()
+        .await
+        .unwrap();
+    let token_regex = regex::Regex::new(r#"name="csrf_token" value="([A-Za-z0-9]+)""#).unwrap();
+    token_regex
+        .captures(&html_page)
+        .expect("The login form carries a CSRF token")[1]
+        .to_owned()
+}
+
+/// Log `user` in with `client` instead of the app's own client.
+pub async fn login_with(
+    client: &reqwest::Client,
+    app: &TestApp,
+    user: &TestUser,
+) -> reqwest::Response {
+    let csrf_token = csrf_token_for(client, app).await;
+    client
+        .post(format!("{}/login", &app.address))
+        .header("X-CSRF-Token", csrf_token)
+        .form(&serde_json::json!({
+            "username": &user.username,
+            "password": &user.password,
+        }))
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
 pub async fn configure_database(config: &DatabaseSettings) -> PgPool {
     // Create a database
     let mut connection = PgConnection::connect_with(&config.without_db())
@@ -104,14 +184,23 @@ pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
     pub password: String,
+    pub email: String,
+    pub role: &'static str,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::generate_with_role("owner")
+    }
+
+    pub fn generate_with_role(role: &'static str) -> Self {
+        let username = Uuid::new_v4().to_string();
         Self {
             user_id: Uuid::new_v4(),
-            username: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", username),
+            username,
             password: Uuid::new_v4().to_string(),
+            role,
         }
     }
 
@@ -122,7 +211,7 @@ impl TestUser {
         }))
         .await
     }
-    async fn store(&self, pool: &PgPool) {
+    pub async fn store(&self, pool: &PgPool) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             Algorithm::Argon2id,
@@ -135,12 +224,14 @@ impl TestUser {
 
         sqlx::query!(
             r#"
-            INSERT INTO users (user_id, username, password_hash)
-            VALUES ($1, $2, $3)
+            INSERT INTO users (user_id, username, password_hash, email, role)
+            VALUES ($1, $2, $3, $4, $5)
             "#,
             self.user_id,
             self.username,
             password_hash,
+            self.email,
+            self.role,
         )
         .execute(pool)
         .await
@@ -156,6 +247,8 @@ pub struct TestApp {
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
     pub email_client: EmailClient,
+    /// Stops the server of this application.
+    pub shutdown: Shutdown,
 }
 
 impl TestApp {
@@ -178,7 +271,7 @@ impl TestApp {
     /// Send a get request to the admin dashboard endpoint.
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -192,7 +285,7 @@ impl TestApp {
     /// Send a get request to the change admin password endpoint
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -233,10 +326,15 @@ impl TestApp {
         ConfirmationLinks { html, plain_text }
     }
 
+    /// The CSRF token of the app client's session.
+    pub async fn csrf_token(&self) -> String {
+        csrf_token_for(&self.api_client, self).await
+    }
+
     /// Send a get request to the login endpoint.
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -248,7 +346,7 @@ impl TestApp {
     /// Send a get request to the admin dashboard endpoint.
     pub async fn get_publish_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
             .send()
             .await
             .expect("Failed to execute request")
@@ -265,8 +363,9 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -278,8 +377,9 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
             .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -288,7 +388,8 @@ impl TestApp {
     /// Send a post request to the logout endpoint.
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", self.address))
+            .post(format!("{}/admin/logout", self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -300,24 +401,290 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/admin/newsletters", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .form(&body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a get request to the user management page.
+    pub async fn get_admin_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+    }
+
+    /// Return the html from the user management page.
+    pub async fn get_admin_users_html(&self) -> String {
+        self.get_admin_users().await.text().await.unwrap()
+    }
+
+    /// Send a post request to invite a new user.
+    pub async fn post_invite_user<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/users/invite", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a post request to disable, enable or delete a user.
+    pub async fn post_user_action(&self, user_id: Uuid, action: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/users/{}/{}",
+                &self.address, user_id, action
+            ))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Return the html from the security page.
+    pub async fn get_security_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/security", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Send a post request to one of the two-factor settings endpoints.
+    pub async fn post_two_factor_settings<Body>(
+        &self,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/security/2fa/{}", &self.address, action))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a post request to the second login step.
+    pub async fn post_login_two_factor(&self, code: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/2fa", &self.address))
+            .form(&serde_json::json!({ "code": code }))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a post request to the forgotten password endpoint.
+    pub async fn post_forgot_password(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/forgot", &self.address))
+            .form(&serde_json::json!({ "email": email }))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a post request to the password reset endpoint.
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login/reset", &self.address))
+            .form(body)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Return the html from the sessions page.
+    pub async fn get_sessions_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/sessions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Send a post request to one of the session revocation endpoints.
+    pub async fn post_revoke_sessions(&self, path: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/sessions/{}", &self.address, path))
+            .header("X-CSRF-Token", self.csrf_token().await)
             .send()
             .await
             .expect("Failed to execute request.")
     }
 
-    /// Send a post request to the subscriptions endpoint.
+    /// Send a get request to the audit log page or its CSV export, with a filter query.
+    pub async fn get_audit(&self, path: &str, query: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/{}", &self.address, path))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Return the html from the log filter page.
+    pub async fn get_log_filter_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/log-filter", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Send a post request to change the log filter.
+    pub async fn post_log_filter(&self, filter: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/log-filter", &self.address))
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .form(&[("filter", filter)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a post request to create an API token, with repeated `scopes` fields.
+    pub async fn post_create_api_token(&self, form: &[(&str, &str)]) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/api-tokens", &self.address))
+            .form(form)
+            .header("X-CSRF-Token", self.csrf_token().await)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Create an API token through the admin UI and return it, as shown once on the page.
+    pub async fn create_api_token(&self, scopes: &[&str]) -> String {
+        let mut form = vec![("name", "CMS integration"), ("expires_in_days", "30")];
+        form.extend(scopes.iter().map(|scope| ("scopes", *scope)));
+        let html_page = self
+            .post_create_api_token(&form)
+            .await
+            .text()
+            .await
+            .unwrap();
+        let token_regex = regex::Regex::new(r"z2p_[A-Za-z0-9]+").unwrap();
+        token_regex
+            .find(&html_page)
+            .expect("The new token is shown")
+            .as_str()
+            .to_owned()
+    }
+
+    /// Send a post request with a JSON body to the JSON API, authenticated with `token`.
+    pub async fn post_api<Body>(&self, path: &str, token: &str, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/api/v1/{}", &self.address, path))
+            .bearer_auth(token)
+            .json(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Send a get request to the JSON API, with `token` as the bearer token if given.
+    pub async fn get_api(&self, path: &str, token: Option<&str>) -> reqwest::Response {
+        let mut request = self
+            .api_client
+            .get(format!("{}/api/v1/{}", &self.address, path));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    /// Scrape `/metrics` from the application port, authenticated with `token`.
+    pub async fn get_metrics(&self, token: Option<&str>) -> reqwest::Response {
+        let mut request = self.api_client.get(format!("{}/metrics", &self.address));
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        request.send().await.expect("Failed to execute request.")
+    }
+
+    /// Render the subscription form and return the form token embedded in it.
+    pub async fn get_subscription_form_token(&self) -> String {
+        let html_page = self
+            .api_client
+            .get(format!("{}/subscriptions", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request")
+            .text()
+            .await
+            .unwrap();
+        let token_regex = regex::Regex::new(r#"name="form_token" value="([^"]+)""#).unwrap();
+        token_regex.captures(&html_page).unwrap()[1].to_owned()
+    }
+
+    /// Send a post request to the subscriptions endpoint, including a fresh form token.
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
+        let form_token = self.get_subscription_form_token().await;
+        let body = if body.is_empty() {
+            format!("form_token={}", form_token)
+        } else {
+            format!("{}&form_token={}", body, form_token)
+        };
+        self.post_subscriptions_raw(body).await
+    }
+
+    /// Send a post request to the subscriptions endpoint exactly as given.
+    pub async fn post_subscriptions_raw(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
             .await
             .expect("failed to execute request")
     }
+
+    /// Send a post request to the subscriptions endpoint as if a proxy forwarded it from
+    /// `client_ip`, including a fresh form token.
+    pub async fn post_subscriptions_from(&self, client_ip: &str, body: &str) -> reqwest::Response {
+        let form_token = self.get_subscription_form_token().await;
+        self.api_client
+            .post(format!("{}/subscriptions", &self.address))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .header("X-Forwarded-For", client_ip)
+            .body(format!("{}&form_token={}", body, form_token))
+            .send()
+            .await
+            .expect("failed to execute request")
+    }
 }
 
 pub struct ConfirmationLinks {
@@ -0,0 +1,63 @@
+use crate::{
+    helpers::{spawn_app, TestUser},
+    login::assert_is_redirect_to,
+};
+
+// Every test application shares the subscriber of the test run, so these tests only
+// ever set the filter it starts with.
+const TEST_FILTER: &str = "zero2prod=debug,info";
+
+#[tokio::test]
+async fn owners_can_change_the_log_filter() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.post_log_filter(TEST_FILTER).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/log-filter");
+    let html_page = app.get_log_filter_html().await;
+    assert!(html_page.contains("The log filter has been changed."));
+    assert!(html_page.contains(&format!("<code>{}</code>", TEST_FILTER)));
+    let action = sqlx::query_scalar!(
+        "SELECT action FROM audit_events WHERE target = $1",
+        format!("log_filter:{}", TEST_FILTER)
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap();
+    assert_eq!(action, "log_filter_changed");
+}
+
+#[tokio::test]
+async fn an_invalid_log_filter_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app.post_log_filter("zero2prod=loud").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/log-filter");
+    let html_page = app.get_log_filter_html().await;
+    assert!(html_page.contains("The filter is invalid."));
+    assert!(html_page.contains(&format!("<code>{}</code>", TEST_FILTER)));
+}
+
+#[tokio::test]
+async fn only_owners_can_change_the_log_filter() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    editor.login(&app).await;
+
+    // Act
+    let response = app.post_log_filter(TEST_FILTER).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
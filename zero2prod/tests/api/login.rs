@@ -1,3 +1,9 @@
+use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
 use crate::helpers::spawn_app;
 
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
@@ -58,3 +64,134 @@ async fn redirect_to_admin_dashboard_after_login_success() {
 
     // Assert - Part 2
 }
+
+#[tokio::test]
+async fn login_attempts_are_rate_limited_per_ip() {
+    // Arrange
+    let app = spawn_app().await;
+    let login_body = serde_json::json!({
+        "username": "random-username",
+        "password": "random-password"
+    });
+
+    // Act - Part 1 - Use up the allowance
+    for _ in 0..10 {
+        let response = app.post_login(&login_body).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // Act - Part 2 - One attempt too many
+    let response = app.post_login(&login_body).await;
+
+    // Assert
+    assert_eq!(429, response.status().as_u16());
+    let retry_after: u64 = response
+        .headers()
+        .get("Retry-After")
+        .expect("Missing Retry-After header")
+        .to_str()
+        .unwrap()
+        .parse()
+        .unwrap();
+    assert!(retry_after > 0 && retry_after <= 60);
+}
+
+#[tokio::test]
+async fn an_account_is_locked_after_repeated_failures_and_the_owner_is_notified() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    });
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Fail enough times to trigger the lock
+    for _ in 0..5 {
+        let response = app.post_login(&wrong_login_body).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+
+    // Act - Part 2 - The correct password no longer gets in
+    let response = app.test_user.login(&app).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Authentication failed"));
+
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["To"], app.test_user.email.as_str());
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failure_count() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password",
+    });
+
+    for _ in 0..2 {
+        // Act - Part 1 - Fail just below the lock threshold
+        for _ in 0..4 {
+            app.post_login(&wrong_login_body).await;
+        }
+
+        // Act - Part 2 - Log in successfully
+        let response = app.test_user.login(&app).await;
+
+        // Assert
+        assert_is_redirect_to(&response, "/admin/dashboard");
+    }
+}
+
+#[tokio::test]
+async fn a_password_hashed_with_weaker_parameters_is_upgraded_on_login() {
+    // Arrange
+    let app = spawn_app().await;
+    let salt = SaltString::generate(&mut rand::thread_rng());
+    let weak_hash = Argon2::new(
+        Algorithm::Argon2id,
+        Version::V0x13,
+        Params::new(4096, 1, 1, None).unwrap(),
+    )
+    .hash_password(app.test_user.password.as_bytes(), &salt)
+    .unwrap()
+    .to_string();
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1 WHERE user_id = $2",
+        weak_hash,
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = app.test_user.login(&app).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let stored_hash = sqlx::query!(
+        "SELECT password_hash FROM users WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .password_hash;
+    assert!(stored_hash.starts_with("$argon2id$v=19$m=15000,t=2,p=1$"));
+    // The upgraded hash still accepts the same password
+    app.post_logout().await;
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -1,8 +1,24 @@
 mod admin_dashboard;
+mod admin_users;
+mod api_tokens;
+mod api_v1;
+mod audit;
+mod bootstrap_admin;
 mod change_password;
+mod csrf;
+mod errors;
 mod health_check;
 mod helpers;
+mod log_filter;
 mod login;
+mod metrics;
 mod newsletters;
+mod openapi;
+mod password_reset;
+mod roles;
+mod security_headers;
+mod sessions;
+mod shutdown;
 mod subscriptions;
 mod subscriptions_confirm;
+mod two_factor;
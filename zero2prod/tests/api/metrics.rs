@@ -0,0 +1,110 @@
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::{configuration::get_configuration, startup::MetricsApplication};
+
+use crate::{helpers::spawn_app, newsletters::newsletter_helpers::create_confirmed_subscriber};
+
+#[tokio::test]
+async fn metrics_are_exposed_in_the_prometheus_format() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["metrics:read"]).await;
+
+    // Act
+    let response = app.get_metrics(Some(&token)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/plain; version=0.0.4"));
+    let body = response.text().await.unwrap();
+    assert!(body.contains("zero2prod_issue_delivery_queue_depth"));
+    assert!(body.contains(r#"zero2prod_db_pool_connections{state="max"}"#));
+}
+
+#[tokio::test]
+async fn metrics_on_the_application_port_need_a_token_with_the_metrics_scope() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:read"]).await;
+
+    // Act
+    let anonymous = app.get_metrics(None).await;
+    let without_scope = app.get_metrics(Some(&token)).await;
+
+    // Assert
+    assert_eq!(anonymous.status().as_u16(), 401);
+    assert_eq!(without_scope.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn requests_are_counted_by_route_rather_than_path() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["metrics:read"]).await;
+    let issue_id = Uuid::new_v4();
+
+    // Act
+    app.api_client
+        .get(format!(
+            "{}/admin/newsletters/{}/preview",
+            &app.address, issue_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    let body = app.get_metrics(Some(&token)).await.text().await.unwrap();
+
+    // Assert
+    assert!(body.contains(
+        r#"zero2prod_http_requests_total{method="GET",route="/admin/newsletters/:newsletter_issue_id/preview",status="404"}"#
+    ));
+    assert!(!body.contains(&issue_id.to_string()));
+}
+
+#[tokio::test]
+async fn processes_without_a_server_serve_the_metrics_of_their_workers() {
+    // Arrange
+    let app = spawn_app().await;
+    let mut configuration = get_configuration().unwrap();
+    configuration.metrics.port = Some(0);
+    // What `worker delivery` starts next to the worker
+    let metrics = MetricsApplication::build(&configuration, app.db_pool.clone())
+        .await
+        .unwrap()
+        .unwrap();
+    let metrics_address = format!("http://127.0.0.1:{}/metrics", metrics.port());
+    tokio::spawn(metrics.run_until_stopped(app.shutdown.clone()));
+    create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.test_user.login(&app).await;
+    app.post_publish_newsletter(&serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    }))
+    .await;
+
+    // Act
+    app.dispatch_all_pending_emails().await;
+    let response = reqwest::get(&metrics_address).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let body = response.text().await.unwrap();
+    assert!(body.contains(r#"zero2prod_issue_deliveries_total{outcome="delivered"}"#));
+    assert!(body.contains(r#"zero2prod_email_send_duration_seconds_count{outcome="success"}"#));
+}
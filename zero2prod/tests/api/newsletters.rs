@@ -4,11 +4,14 @@ use wiremock::{
     matchers::{any, method, path},
     Mock, ResponseTemplate,
 };
-use zero2prod::routes::newsletters::PUBLISH_SUCCESS_INFO_MESSAGE;
+use zero2prod::{
+    issue_delivery_worker::{get_queue_depths, requeue_dead_letters},
+    routes::newsletters::PUBLISH_SUCCESS_INFO_MESSAGE,
+};
 
 use crate::{helpers::spawn_app, login::assert_is_redirect_to};
 
-mod newsletter_helpers {
+pub(crate) mod newsletter_helpers {
     use fake::{
         faker::{internet::en::SafeEmail, name::en::Name},
         Fake,
@@ -400,3 +403,53 @@ async fn old_idempotency_entries_are_cleaned_up() {
 
     assert_eq!(1, count.value.unwrap());
 }
+
+#[tokio::test]
+async fn dead_lettered_deliveries_can_be_queued_again() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.test_user.login(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    let body = serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": uuid::Uuid::new_v4().to_string(),
+    });
+    app.post_publish_newsletter(&body).await;
+
+    // Pretend every retry has been used up, so the delivery fails for good
+    sqlx::query!("UPDATE issue_delivery_queue SET retries = 100")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    let depths = get_queue_depths(&app.db_pool).await.unwrap();
+    assert_eq!(depths.len(), 1);
+    assert_eq!(depths[0].pending, 0);
+    assert_eq!(depths[0].dead_letters, 1);
+
+    // Act
+    let requeued = requeue_dead_letters(None, &app.db_pool).await.unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_eq!(requeued, 1);
+    assert!(get_queue_depths(&app.db_pool).await.unwrap().is_empty());
+    let stats = sqlx::query!("SELECT delivered_count, failed_count FROM newsletter_issues")
+        .fetch_one(&app.db_pool)
+        .await
+        .unwrap();
+    assert_eq!(stats.delivered_count, Some(1));
+    assert_eq!(stats.failed_count, Some(0));
+    // Mock verifies on Drop that the requeued delivery was sent
+}
@@ -0,0 +1,105 @@
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_openapi_document_is_public() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/api/openapi.json", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let document: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(document["openapi"], "3.1.0");
+    for path in [
+        "/api/v1/issues",
+        "/api/v1/issues/{issue_id}/publish",
+        "/api/v1/subscribers/export",
+    ] {
+        assert!(document["paths"][path].is_object(), "{} is missing", path);
+    }
+}
+
+#[tokio::test]
+async fn the_committed_openapi_document_is_up_to_date() {
+    // Arrange
+    let app = spawn_app().await;
+    let committed: serde_json::Value =
+        serde_json::from_str(include_str!("../../openapi.json")).unwrap();
+
+    // Act
+    let served: serde_json::Value = app
+        .api_client
+        .get(format!("{}/api/openapi.json", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        served, committed,
+        "The API changed. Run `cargo xtask openapi` to update openapi.json"
+    );
+}
+
+#[tokio::test]
+async fn the_documentation_page_loads_the_openapi_document() {
+    // Arrange
+    let app = spawn_app().await;
+    let get = |path: &str| {
+        app.api_client
+            .get(format!("{}{}", &app.address, path))
+            .send()
+    };
+
+    // Act
+    let response = get("/api/docs").await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let content_security_policy = response.headers()["content-security-policy"]
+        .to_str()
+        .unwrap()
+        .to_owned();
+    assert!(!content_security_policy.contains("https:"));
+    let html_page = response.text().await.unwrap();
+    // Every script is served by the application itself
+    let script_regex = regex::Regex::new(r#"<script src="([^"]+)">"#).unwrap();
+    for script in script_regex.captures_iter(&html_page) {
+        let response = get(&script[1]).await.unwrap();
+        assert_eq!(response.status().as_u16(), 200, "{}", &script[1]);
+    }
+    let initializer = get("/api/docs/swagger-initializer.js")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(initializer.contains(r#""url": "/api/openapi.json""#));
+}
+
+#[tokio::test]
+async fn unknown_documentation_assets_are_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/api/docs/unknown.js", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
@@ -0,0 +1,269 @@
+use std::time::Duration;
+
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::{
+    helpers::{spawn_app, TestApp, BREACHED_PASSWORD},
+    login::assert_is_redirect_to,
+};
+
+/// The emails sent so far, once there are at least `count`. Reset links are sent in the
+/// background, after the response.
+async fn wait_for_emails(app: &TestApp, count: usize) -> Vec<wiremock::Request> {
+    for _ in 0..50 {
+        let received = app.email_server.received_requests().await.unwrap();
+        if received.len() >= count {
+            return received;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("Expected {} emails to be sent.", count);
+}
+
+/// Ask for a reset link for the test user and return its token.
+async fn request_reset_token(app: &TestApp) -> String {
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let response = app.post_forgot_password(&app.test_user.email).await;
+    assert_is_redirect_to(&response, "/login/forgot");
+
+    let email_request = &wait_for_emails(app, 1).await[0];
+    let reset_link = app.get_confirmation_links(email_request).html;
+    assert_eq!(reset_link.path(), "/login/reset");
+    reset_link
+        .query_pairs()
+        .find(|(k, _)| k == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+#[tokio::test]
+async fn unknown_email_addresses_get_the_same_answer_but_no_email() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_forgot_password("nobody@example.com").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/forgot");
+    let html_page = app
+        .api_client
+        .get(format!("{}/login/forgot", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("If an account with that email address exists"));
+}
+
+#[tokio::test]
+async fn a_failing_email_provider_gets_the_same_answer() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_forgot_password(&app.test_user.email).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/forgot");
+    wait_for_emails(&app, 1).await;
+}
+
+#[tokio::test]
+async fn reset_links_are_rate_limited_per_email_address() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Use up the allowance, whether or not the address has an account
+    for _ in 0..3 {
+        let response = app.post_forgot_password("nobody@example.com").await;
+        assert_is_redirect_to(&response, "/login/forgot");
+    }
+
+    // Act - Part 2 - One request too many
+    let response = app.post_forgot_password("nobody@example.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 429);
+    // Other addresses are unaffected
+    let response = app.post_forgot_password(&app.test_user.email).await;
+    assert_is_redirect_to(&response, "/login/forgot");
+}
+
+#[tokio::test]
+async fn a_reset_link_lets_you_choose_a_new_password() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    let form = app
+        .api_client
+        .get(format!("{}/login/reset?token={}", &app.address, token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(form.status().as_u16(), 200);
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(app
+        .get_login_html()
+        .await
+        .contains("Your password has been reset. You can now log in."));
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &new_password,
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn resetting_the_password_logs_out_every_session() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = request_reset_token(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    app.post_reset_password(&serde_json::json!({
+        "token": &token,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    }))
+    .await;
+
+    // Assert
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    let new_password = Uuid::new_v4().to_string();
+    let body = serde_json::json!({
+        "token": &token,
+        "new_password": &new_password,
+        "new_password_check": &new_password,
+    });
+    app.post_reset_password(&body).await;
+
+    // Act
+    let form = app
+        .api_client
+        .get(format!("{}/login/reset?token={}", &app.address, token))
+        .send()
+        .await
+        .unwrap();
+    let response = app.post_reset_password(&body).await;
+
+    // Assert
+    assert_eq!(form.status().as_u16(), 404);
+    assert_is_redirect_to(&response, "/login/forgot");
+}
+
+#[tokio::test]
+async fn the_new_password_must_follow_the_usual_rules() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    // Act
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": "too-short",
+            "new_password_check": "too-short",
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/login/reset?token={}", token));
+    let html_page = app
+        .api_client
+        .get(format!("{}/login/reset?token={}", &app.address, token))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("The new password should be between 12 and 128 characters long."));
+    // The link has not been used up
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_breached_password_cannot_be_chosen() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    // Act
+    let response = app
+        .post_reset_password(&serde_json::json!({
+            "token": &token,
+            "new_password": BREACHED_PASSWORD,
+            "new_password_check": BREACHED_PASSWORD,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/login/reset?token={}", token));
+    let html_page = app
+        .api_client
+        .get(format!("{}/login/reset?token={}", &app.address, token))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("The new password has appeared in a data breach"));
+}
@@ -0,0 +1,138 @@
+use uuid::Uuid;
+
+use crate::{
+    helpers::{spawn_app, TestApp, TestUser},
+    login::assert_is_redirect_to,
+};
+
+async fn login_as(app: &TestApp, role: &'static str) -> TestUser {
+    let user = TestUser::generate_with_role(role);
+    user.store(&app.db_pool).await;
+    user.login(app).await;
+    user
+}
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "text_content": "Newsletter body as plain text",
+        "html_content": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    })
+}
+
+#[tokio::test]
+async fn viewers_can_see_the_dashboard_but_cannot_publish() {
+    // Arrange
+    let app = spawn_app().await;
+    login_as(&app, "viewer").await;
+
+    // Act
+    let dashboard = app.get_admin_dashboard().await;
+    let form = app.get_publish_newsletter().await;
+    let publish = app.post_publish_newsletter(&newsletter_body()).await;
+
+    // Assert
+    assert_eq!(dashboard.status().as_u16(), 200);
+    let html_page = dashboard.text().await.unwrap();
+    assert!(html_page.contains("You are signed in as viewer."));
+    assert!(!html_page.contains("/admin/newsletters"));
+    assert_eq!(form.status().as_u16(), 403);
+    assert_eq!(publish.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn editors_can_publish_but_cannot_manage_users() {
+    // Arrange
+    let app = spawn_app().await;
+    login_as(&app, "editor").await;
+
+    // Act
+    let form = app.get_publish_newsletter().await;
+    let publish = app.post_publish_newsletter(&newsletter_body()).await;
+    let users = app.get_admin_users().await;
+    let disable = app.post_user_action(app.test_user.user_id, "disable").await;
+
+    // Assert
+    assert_eq!(form.status().as_u16(), 200);
+    assert_is_redirect_to(&publish, "/admin/newsletters");
+    assert_eq!(users.status().as_u16(), 403);
+    assert_eq!(disable.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn anonymous_users_are_still_sent_to_the_login_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_admin_users().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_role_change_takes_effect_on_the_next_request() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = TestUser::generate_with_role("editor");
+    editor.store(&app.db_pool).await;
+    app.test_user.login(&app).await;
+
+    // Act - Part 1 - Demote the editor to viewer
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/users/{}/role",
+            &app.address, editor.user_id
+        ))
+        .form(&serde_json::json!({ "role": "viewer" }))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("The user's role has been changed."));
+
+    // Act - Part 2 - Log in as the former editor
+    app.post_logout().await;
+    editor.login(&app).await;
+    let response = app.get_publish_newsletter().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn the_last_active_owner_cannot_give_up_the_role() {
+    // Arrange
+    let app = spawn_app().await;
+    sqlx::query!(
+        "UPDATE users SET disabled = true WHERE user_id != $1",
+        app.test_user.user_id
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    app.test_user.login(&app).await;
+
+    // Act
+    let response = app
+        .api_client
+        .post(format!(
+            "{}/admin/users/{}/role",
+            &app.address, app.test_user.user_id
+        ))
+        .form(&serde_json::json!({ "role": "editor" }))
+        .header("X-CSRF-Token", app.csrf_token().await)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_admin_users_html().await;
+    assert!(html_page.contains("At least one active owner must remain."));
+}
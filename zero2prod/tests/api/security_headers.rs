@@ -0,0 +1,115 @@
+use crate::{helpers::spawn_app, login::assert_is_redirect_to};
+
+#[tokio::test]
+async fn pages_carry_security_headers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    let headers = response.headers();
+    assert!(headers["Content-Security-Policy"]
+        .to_str()
+        .unwrap()
+        .contains("frame-ancestors 'none'"));
+    assert_eq!(headers["X-Frame-Options"], "DENY");
+    assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+    assert_eq!(headers["Referrer-Policy"], "no-referrer");
+    assert!(headers.contains_key("Permissions-Policy"));
+    // Served over plain HTTP locally
+    assert!(!headers.contains_key("Strict-Transport-Security"));
+}
+
+#[tokio::test]
+async fn the_session_cookie_follows_the_session_settings() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/login", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    let cookie = response
+        .cookies()
+        .find(|cookie| cookie.name() == "session")
+        .expect("The session cookie is set");
+    assert!(cookie.http_only());
+    assert!(cookie.same_site_lax());
+}
+
+#[tokio::test]
+async fn the_issue_preview_sets_its_own_content_security_policy() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let token = app.create_api_token(&["newsletters:write"]).await;
+    let issue: serde_json::Value = app
+        .post_api(
+            "issues",
+            &token,
+            &serde_json::json!({
+                "title": "Newsletter title",
+                "text_content": "Newsletter body as plain text",
+                "html_content": "<p>Newsletter body as HTML</p>",
+            }),
+        )
+        .await
+        .json()
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletters/{}/preview",
+            &app.address,
+            issue["id"].as_str().unwrap()
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let headers = response.headers();
+    assert!(headers["Content-Security-Policy"]
+        .to_str()
+        .unwrap()
+        .ends_with("sandbox"));
+    assert_eq!(headers["X-Frame-Options"], "SAMEORIGIN");
+    assert_eq!(headers["X-Content-Type-Options"], "nosniff");
+    assert_eq!(
+        response.text().await.unwrap(),
+        "<p>Newsletter body as HTML</p>"
+    );
+}
+
+#[tokio::test]
+async fn sessions_expire_after_their_absolute_lifetime() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    sqlx::query!("UPDATE user_sessions SET created_at = now() - interval '13 hours'")
+        .execute(&app.db_pool)
+        .await
+        .unwrap();
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
@@ -0,0 +1,220 @@
+use uuid::Uuid;
+
+use crate::{
+    helpers::{another_client, login_with, spawn_app, TestApp, TestUser},
+    login::assert_is_redirect_to,
+};
+
+/// Log the test user in on a second device, identified by its user agent.
+async fn login_on_another_device(app: &TestApp, user_agent: &str) -> reqwest::Client {
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .cookie_store(true)
+        .user_agent(user_agent)
+        .build()
+        .unwrap();
+    let response = login_with(&client, app, &app.test_user).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    client
+}
+
+async fn dashboard_response(client: &reqwest::Client, app: &TestApp) -> reqwest::Response {
+    client
+        .get(format!("{}/admin/dashboard", &app.address))
+        .send()
+        .await
+        .unwrap()
+}
+
+/// The id of the session revoke form next to `marker` on the sessions page.
+fn session_id_near(html_page: &str, marker: &str) -> Uuid {
+    let row = html_page
+        .split("<tr>")
+        .find(|row| row.contains(marker))
+        .unwrap();
+    let id_regex = regex::Regex::new(r#"/admin/sessions/([0-9a-f-]+)/revoke"#).unwrap();
+    id_regex.captures(row).unwrap()[1].parse().unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_your_sessions() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/sessions", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn every_login_is_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    login_on_another_device(&app, "Other <Browser>").await;
+
+    // Act
+    let html_page = app.get_sessions_html().await;
+
+    // Assert
+    assert_eq!(html_page.matches("/revoke\"").count(), 2);
+    assert!(html_page.contains("127.0.0.1 (this session)"));
+    assert!(html_page.contains("Other &lt;Browser&gt;"));
+}
+
+#[tokio::test]
+async fn expired_sessions_are_not_listed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    login_on_another_device(&app, "Forgotten Browser").await;
+    login_on_another_device(&app, "Idle Browser").await;
+    let html_page = app.get_sessions_html().await;
+    let forgotten = session_id_near(&html_page, "Forgotten Browser");
+    let idle = session_id_near(&html_page, "Idle Browser");
+
+    // Act - Part 1 - Past the absolute lifetime, however active
+    sqlx::query!(
+        "UPDATE user_sessions SET created_at = now() - interval '1 year' WHERE session_id = $1",
+        forgotten
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    // Act - Part 2 - Past the idle timeout
+    sqlx::query!(
+        "UPDATE user_sessions SET last_seen_at = now() - interval '1 day' WHERE session_id = $1",
+        idle
+    )
+    .execute(&app.db_pool)
+    .await
+    .unwrap();
+    let html_page = app.get_sessions_html().await;
+
+    // Assert
+    assert_eq!(html_page.matches("/revoke\"").count(), 1);
+    assert!(html_page.contains("127.0.0.1 (this session)"));
+}
+
+#[tokio::test]
+async fn a_revoked_session_is_logged_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = login_on_another_device(&app, "Other Browser").await;
+    let session_id = session_id_near(&app.get_sessions_html().await, "Other Browser");
+
+    // Act
+    let response = app
+        .post_revoke_sessions(&format!("{}/revoke", session_id))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let response = dashboard_response(&other_device, &app).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn revoking_the_current_session_logs_you_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let session_id = session_id_near(&app.get_sessions_html().await, "(this session)");
+
+    // Act
+    let response = app
+        .post_revoke_sessions(&format!("{}/revoke", session_id))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn sessions_of_other_users_cannot_be_revoked() {
+    // Arrange
+    let app = spawn_app().await;
+    let other_device = login_on_another_device(&app, "Other Browser").await;
+    let session_id = sqlx::query!(
+        "SELECT session_id FROM user_sessions WHERE user_id = $1",
+        app.test_user.user_id
+    )
+    .fetch_one(&app.db_pool)
+    .await
+    .unwrap()
+    .session_id;
+    let other_user = TestUser::generate();
+    other_user.store(&app.db_pool).await;
+    login_with(&app.api_client, &app, &other_user).await;
+
+    // Act
+    let response = app
+        .post_revoke_sessions(&format!("{}/revoke", session_id))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    let response = dashboard_response(&other_device, &app).await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn signing_out_everywhere_else_keeps_the_current_session() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let first_device = login_on_another_device(&app, "First Browser").await;
+    let second_device = another_client();
+    login_with(&second_device, &app, &app.test_user).await;
+
+    // Act
+    let response = app.post_revoke_sessions("revoke-others").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/sessions");
+    assert!(app
+        .get_sessions_html()
+        .await
+        .contains("You have been signed out everywhere else."));
+    for device in [first_device, second_device] {
+        let response = dashboard_response(&device, &app).await;
+        assert_is_redirect_to(&response, "/login");
+    }
+}
+
+#[tokio::test]
+async fn changing_your_password_signs_you_out_everywhere_else() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let other_device = login_on_another_device(&app, "Other Browser").await;
+    let new_password = Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .post_change_password(&serde_json::json!({
+            "current_password": &app.test_user.password,
+            "new_password": &new_password,
+            "new_password_check": &new_password,
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/password");
+    let response = dashboard_response(&other_device, &app).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
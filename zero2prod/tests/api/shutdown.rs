@@ -0,0 +1,29 @@
+use std::time::Duration;
+
+use crate::helpers::spawn_app;
+
+#[tokio::test]
+async fn the_server_stops_accepting_requests_once_shut_down() {
+    // Arrange
+    let app = spawn_app().await;
+    let health_check = format!("{}/health_check", &app.address);
+    let client = reqwest::Client::new();
+    assert!(client.get(&health_check).send().await.is_ok());
+
+    // Act
+    app.shutdown.trigger();
+
+    // Assert
+    let mut refused = false;
+    for _ in 0..50 {
+        if client.get(&health_check).send().await.is_err() {
+            refused = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(
+        refused,
+        "The server was still serving requests after shutting down"
+    );
+}
@@ -3,7 +3,7 @@ use wiremock::{
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{spawn_app, spawn_app_with};
 
 #[tokio::test]
 async fn subscribe_returns_200_for_valid_form_data() {
@@ -157,3 +157,173 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_form_token_is_missing() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions_raw(body.into()).await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_returns_400_when_the_form_token_is_tampered_with() {
+    // Arrange
+    let app = spawn_app().await;
+    let form_token = app.get_subscription_form_token().await;
+    let (_, rest) = form_token.split_once('.').unwrap();
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&form_token=1.{}",
+        rest
+    );
+
+    // Act
+    let response = app.post_subscriptions_raw(body).await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_rejects_a_replayed_form_token() {
+    // Arrange
+    let app = spawn_app().await;
+    let form_token = app.get_subscription_form_token().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Submit the form
+    let body = format!(
+        "name=le%20guin&email=ursula_le_guin%40gmail.com&form_token={}",
+        form_token
+    );
+    let response = app.post_subscriptions_raw(body).await;
+    assert_eq!(200, response.status().as_u16());
+
+    // Act - Part 2 - Replay the same token for another address
+    let body = format!(
+        "name=le%20guin&email=someone_else%40gmail.com&form_token={}",
+        form_token
+    );
+    let response = app.post_subscriptions_raw(body).await;
+
+    // Assert
+    assert_eq!(400, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn subscribe_silently_drops_submissions_that_fill_in_the_honeypot() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com&website=spam.example.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+    let saved = sqlx::query!("SELECT email FROM subscriptions")
+        .fetch_optional(&app.db_pool)
+        .await
+        .expect("Failed to fetch saved subscription.");
+    assert!(saved.is_none());
+}
+
+#[tokio::test]
+async fn subscriptions_are_rate_limited_per_email_address() {
+    // Arrange
+    let app = spawn_app().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Use up the allowance, varying case to show addresses are normalised
+    for email in [
+        "ursula%40gmail.com",
+        "Ursula%40gmail.com",
+        "URSULA%40gmail.com",
+    ] {
+        let body = format!("name=le%20guin&email={}", email);
+        let response = app.post_subscriptions(body).await;
+        assert_ne!(429, response.status().as_u16());
+    }
+
+    // Act - Part 2 - One subscription too many
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula%40gmail.com".into())
+        .await;
+
+    // Assert
+    assert_eq!(429, response.status().as_u16());
+    assert!(response.headers().get("Retry-After").is_some());
+
+    // Other addresses are unaffected
+    let response = app
+        .post_subscriptions("name=le%20guin&email=someone_else%40gmail.com".into())
+        .await;
+    assert_eq!(200, response.status().as_u16());
+}
+
+#[tokio::test]
+async fn requests_over_the_ip_limit_do_not_count_against_the_email_address() {
+    // Arrange
+    let app = spawn_app_with(|c| {
+        c.application.trust_forwarded_for = true;
+        c.rate_limit.subscriptions_per_ip.max_requests = 1;
+    })
+    .await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let attacker = "203.0.113.7";
+    let response = app
+        .post_subscriptions_from(attacker, "name=mallory&email=mallory%40gmail.com")
+        .await;
+    assert_eq!(200, response.status().as_u16());
+
+    // Act - Part 1 - The attacker is over their limit
+    for _ in 0..3 {
+        let response = app
+            .post_subscriptions_from(attacker, "name=le%20guin&email=ursula%40gmail.com")
+            .await;
+        assert_eq!(429, response.status().as_u16());
+    }
+
+    // Act - Part 2 - The owner of the address subscribes
+    let response = app
+        .post_subscriptions_from("198.51.100.1", "name=le%20guin&email=ursula%40gmail.com")
+        .await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+}
@@ -0,0 +1,252 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use totp_rs::{Algorithm, Secret, TOTP};
+
+use crate::{
+    helpers::{spawn_app, TestApp},
+    login::assert_is_redirect_to,
+};
+
+/// The enrollment key shown on the security page.
+fn extract_secret(html_page: &str) -> String {
+    let secret_regex = regex::Regex::new(r#"<code>([A-Z2-7]+)</code>"#).unwrap();
+    secret_regex.captures(html_page).unwrap()[1].to_owned()
+}
+
+/// The code an authenticator app would show `offset_steps` 30 second steps from now.
+fn totp_code(secret: &str, offset_steps: i64) -> String {
+    let totp = TOTP::new(
+        Algorithm::SHA1,
+        6,
+        0,
+        30,
+        Secret::Encoded(secret.to_owned()).to_bytes().unwrap(),
+        None,
+        "".into(),
+    )
+    .unwrap();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64;
+    totp.generate((now + offset_steps * 30) as u64)
+}
+
+/// Enable two-factor authentication for the logged in test user, returning the secret
+/// and the recovery codes.
+async fn enroll(app: &TestApp) -> (String, Vec<String>) {
+    let secret = extract_secret(&app.get_security_html().await);
+    let response = app
+        .post_two_factor_settings(
+            "enable",
+            &serde_json::json!({ "code": totp_code(&secret, 0) }),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let code_regex = regex::Regex::new(r#"<li><code>([a-z0-9-]+)</code></li>"#).unwrap();
+    let recovery_codes: Vec<String> = code_regex
+        .captures_iter(&html_page)
+        .map(|c| c[1].to_owned())
+        .collect();
+    assert_eq!(recovery_codes.len(), 10);
+    (secret, recovery_codes)
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_security_page() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!("{}/admin/security", &app.address))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_security_page_offers_an_otpauth_uri_and_qr_code() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    let html_page = app.get_security_html().await;
+
+    // Assert
+    assert!(html_page.contains("otpauth://totp/zero2prod:"));
+    assert!(html_page.contains("<svg"));
+    // The secret is kept until the user confirms it
+    assert_eq!(
+        extract_secret(&html_page),
+        extract_secret(&app.get_security_html().await)
+    );
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_enable_two_factor() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let secret = extract_secret(&app.get_security_html().await);
+
+    // Act
+    let response = app
+        .post_two_factor_settings(
+            "enable",
+            &serde_json::json!({ "code": totp_code(&secret, -10) }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/security");
+    let html_page = app.get_security_html().await;
+    assert!(html_page.contains("The code is invalid. Please try again."));
+    assert!(html_page.contains("Two-factor authentication is disabled."));
+}
+
+#[tokio::test]
+async fn login_requires_a_second_factor_once_enabled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (secret, _) = enroll(&app).await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Password only gets you half way
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/login/2fa");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - A wrong code is rejected
+    let response = app.post_login_two_factor("000000").await;
+    assert_is_redirect_to(&response, "/login/2fa");
+
+    // Act - Part 3 - The next code from the app is accepted
+    // The current one was used up during enrollment.
+    let response = app.post_login_two_factor(&totp_code(&secret, 1)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(html_page.contains(&format!("Welcome {}", app.test_user.username)));
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_send_you_back_to_the_login_form() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    enroll(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+
+    // Act
+    for _ in 0..4 {
+        let response = app.post_login_two_factor("000000").await;
+        assert_is_redirect_to(&response, "/login/2fa");
+    }
+    let response = app.post_login_two_factor("000000").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let response = app.post_login_two_factor("000000").await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_password_alone_does_not_clear_failed_codes() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    enroll(&app).await;
+    app.post_logout().await;
+    app.test_user.login(&app).await;
+    for _ in 0..4 {
+        let response = app.post_login_two_factor("000000").await;
+        assert_is_redirect_to(&response, "/login/2fa");
+    }
+
+    // Act - Part 1 - Logging in again starts a new session, but not a new count
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/login/2fa");
+    let response = app.post_login_two_factor("000000").await;
+
+    // Assert - Part 1
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - The account is locked, even with the right password
+    let response = app.test_user.login(&app).await;
+
+    // Assert - Part 2
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_recovery_code_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    let (_, recovery_codes) = enroll(&app).await;
+    app.post_logout().await;
+
+    // Act - Part 1 - Use a recovery code
+    app.test_user.login(&app).await;
+    let response = app
+        .post_login_two_factor(&recovery_codes[0].to_uppercase())
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    assert!(app
+        .get_security_html()
+        .await
+        .contains("You have 9 unused recovery codes left."));
+    app.post_logout().await;
+
+    // Act - Part 2 - Try it again
+    app.test_user.login(&app).await;
+    let response = app.post_login_two_factor(&recovery_codes[0]).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/2fa");
+}
+
+#[tokio::test]
+async fn disabling_two_factor_requires_the_current_password() {
+    // Arrange
+    let app = spawn_app().await;
+    app.test_user.login(&app).await;
+    enroll(&app).await;
+
+    // Act - Part 1 - Wrong password
+    let response = app
+        .post_two_factor_settings(
+            "disable",
+            &serde_json::json!({ "current_password": "not-my-password" }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/security");
+    let html_page = app.get_security_html().await;
+    assert!(html_page.contains("The current password is incorrect."));
+    assert!(html_page.contains("Two-factor authentication is enabled."));
+
+    // Act - Part 2 - Right password
+    let response = app
+        .post_two_factor_settings(
+            "disable",
+            &serde_json::json!({ "current_password": &app.test_user.password }),
+        )
+        .await;
+    assert_is_redirect_to(&response, "/admin/security");
+    app.post_logout().await;
+
+    // Assert
+    let response = app.test_user.login(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}